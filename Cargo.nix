@@ -2353,6 +2353,8 @@ in
       refinery = rustPackages."git+https://github.com/rust-db/refinery".refinery."0.8.9" { inherit profileName; };
       regex = rustPackages."registry+https://github.com/rust-lang/crates.io-index".regex."1.8.1" { inherit profileName; };
       rusqlite = rustPackages."registry+https://github.com/rust-lang/crates.io-index".rusqlite."0.25.4" { inherit profileName; };
+      serde = rustPackages."registry+https://github.com/rust-lang/crates.io-index".serde."1.0.162" { inherit profileName; };
+      serde_json = rustPackages."registry+https://github.com/rust-lang/crates.io-index".serde_json."1.0.96" { inherit profileName; };
       surf = rustPackages."registry+https://github.com/rust-lang/crates.io-index".surf."2.3.2" { inherit profileName; };
       tap = rustPackages."registry+https://github.com/rust-lang/crates.io-index".tap."1.0.1" { inherit profileName; };
       thiserror = rustPackages."registry+https://github.com/rust-lang/crates.io-index".thiserror."1.0.40" { inherit profileName; };
@@ -0,0 +1,37 @@
+CREATE TABLE raffles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    channel TEXT NOT NULL,
+    keyword TEXT NOT NULL,
+    started TEXT NOT NULL,
+    -- When entries close, or NULL if they stay open until a winner is drawn.
+    closes TEXT,
+    closed INTEGER NOT NULL DEFAULT 0,
+    -- Whether people who won an earlier raffle in the channel can't win.
+    exclude_winners INTEGER NOT NULL
+);
+
+CREATE TABLE raffle_entries (
+    raffle INTEGER NOT NULL REFERENCES raffles(id),
+    username TEXT NOT NULL,
+    -- How many chances to win the entry has, from the user's role.
+    weight INTEGER NOT NULL CHECK(weight > 0),
+    entered TEXT NOT NULL,
+    PRIMARY KEY(raffle, username)
+);
+
+CREATE TABLE raffle_winners (
+    raffle INTEGER NOT NULL REFERENCES raffles(id),
+    position INTEGER NOT NULL,
+    username TEXT NOT NULL,
+    drawn TEXT NOT NULL,
+    -- Whether a moderator re-rolled this win, so it doesn't count.
+    rerolled INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY(raffle, position)
+);
+
+CREATE TABLE raffle_luck (
+    channel TEXT NOT NULL,
+    role TEXT NOT NULL,
+    weight INTEGER NOT NULL CHECK(weight > 0),
+    PRIMARY KEY(channel, role)
+);
@@ -0,0 +1,10 @@
+CREATE TABLE counters (
+    channel TEXT NOT NULL,
+    name TEXT NOT NULL,
+    value INTEGER NOT NULL DEFAULT 0,
+    -- How the counter is shown, with {name} and {count} placeholders.
+    format TEXT NOT NULL,
+    -- The lowest role that can change the counter.
+    role TEXT NOT NULL,
+    PRIMARY KEY(channel, name)
+);
@@ -0,0 +1,17 @@
+CREATE TABLE message_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    channel TEXT NOT NULL,
+    username TEXT NOT NULL,
+    message TEXT NOT NULL,
+    -- When the message was received.
+    time TEXT NOT NULL
+);
+
+CREATE INDEX message_log_user ON message_log (channel, username, time);
+CREATE INDEX message_log_time ON message_log (time);
+
+-- Users whose messages are never logged in any channel.
+CREATE TABLE message_log_opt_outs (
+    username TEXT PRIMARY KEY NOT NULL,
+    time TEXT NOT NULL
+);
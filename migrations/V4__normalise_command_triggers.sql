@@ -0,0 +1,24 @@
+-- Triggers are now matched case-insensitively and stored in lowercase. Where
+-- lowercasing would make two triggers in the same channel collide, the one
+-- that was already lowercase (or failing that, the most recently added one)
+-- keeps the trigger, and the others are renamed to `<trigger>_<rowid>` so that
+-- they're still usable.
+UPDATE commands
+SET trigger = lower(trigger) || '_' || rowid
+WHERE EXISTS (
+    SELECT 1
+    FROM commands AS other
+    WHERE other.channel = commands.channel
+        AND lower(other.trigger) = lower(commands.trigger)
+        AND other.rowid <> commands.rowid
+        AND (
+            (other.trigger = lower(other.trigger)) > (commands.trigger = lower(commands.trigger))
+            OR (
+                (other.trigger = lower(other.trigger)) = (commands.trigger = lower(commands.trigger))
+                AND other.rowid > commands.rowid
+            )
+        )
+);
+
+UPDATE commands
+SET trigger = lower(trigger);
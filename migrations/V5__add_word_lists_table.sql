@@ -0,0 +1,6 @@
+CREATE TABLE word_lists (
+    name TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    word TEXT NOT NULL,
+    PRIMARY KEY(name, position)
+);
@@ -0,0 +1,13 @@
+CREATE TABLE word_search_sessions (
+    channel TEXT NOT NULL PRIMARY KEY,
+    started TEXT NOT NULL
+);
+
+CREATE TABLE word_search_constraints (
+    channel TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    bound TEXT NOT NULL,
+    word TEXT NOT NULL,
+    distance INTEGER,
+    PRIMARY KEY(channel, position)
+);
@@ -0,0 +1,7 @@
+CREATE TABLE scores (
+    game TEXT NOT NULL,
+    channel TEXT NOT NULL,
+    username TEXT NOT NULL,
+    score INTEGER NOT NULL,
+    PRIMARY KEY(game, channel, username)
+);
@@ -0,0 +1,13 @@
+CREATE TABLE trivia_questions (
+    bank TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    question TEXT NOT NULL,
+    answer TEXT NOT NULL,
+    PRIMARY KEY(bank, position)
+);
+
+CREATE TABLE trivia_alternatives (
+    bank TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    answer TEXT NOT NULL
+);
@@ -0,0 +1,20 @@
+CREATE TABLE points_balances (
+    channel TEXT NOT NULL,
+    username TEXT NOT NULL,
+    balance INTEGER NOT NULL CHECK(balance >= 0),
+    -- When the user last earned points for chatting, in seconds since the Unix
+    -- epoch.
+    last_earned INTEGER,
+    PRIMARY KEY(channel, username)
+);
+
+CREATE TABLE points_transactions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    channel TEXT NOT NULL,
+    username TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    reason TEXT NOT NULL,
+    time TEXT NOT NULL
+);
+
+ALTER TABLE commands ADD COLUMN cost INTEGER NOT NULL DEFAULT 0;
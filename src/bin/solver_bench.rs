@@ -0,0 +1,120 @@
+//! Play the word search against every word in a word list, and report how many
+//! guesses it takes to find them.
+//!
+//! A summary of each run is printed to stderr as it finishes, and a JSON
+//! summary of all of them is printed to stdout at the end.
+
+use std::{path::PathBuf, time::Instant};
+
+use clap::Parser;
+use eyre::Result;
+use oxbow::wordsearch::{
+    bench::{self, Summary},
+    list::WordList,
+    Strategy,
+};
+use serde::Serialize;
+
+#[derive(Parser, Debug)]
+struct Opts {
+    /// A file with the word list to use, with one word on each line. Defaults
+    /// to the built-in list.
+    #[clap(long)]
+    word_list: Option<PathBuf>,
+
+    /// A strategy to try: `midpoint` or `optimal`. Can be given more than
+    /// once. Defaults to trying every strategy.
+    #[clap(long = "strategy", number_of_values = 1)]
+    strategies: Vec<Strategy>,
+
+    /// Only use every `step`th word as an answer, for a quicker run.
+    #[clap(long, default_value = "1")]
+    step: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    word_list: String,
+    words: usize,
+    answers: usize,
+    runs: Vec<Run>,
+}
+
+#[derive(Debug, Serialize)]
+struct Run {
+    strategy: String,
+    /// Whether the search was told the distance to the answer after each
+    /// guess.
+    distances: bool,
+    seconds: f64,
+    #[serde(flatten)]
+    summary: Summary,
+}
+
+fn main() -> Result<()> {
+    let opts: Opts = Opts::parse();
+
+    let (word_list, words) = match &opts.word_list {
+        Some(path) => (path.display().to_string(), WordList::from_file(path)?),
+        None => ("builtin".to_owned(), WordList::built_in()),
+    };
+
+    let strategies = if opts.strategies.is_empty() {
+        vec![Strategy::Midpoint, Strategy::Optimal]
+    } else {
+        opts.strategies
+    };
+
+    let answers = words
+        .words()
+        .iter()
+        .step_by(opts.step.max(1))
+        .collect::<Vec<_>>();
+
+    let mut runs = vec![];
+
+    for strategy in strategies {
+        for distances in [true, false] {
+            let start = Instant::now();
+            let summary = Summary::new(
+                answers
+                    .iter()
+                    .map(|answer| bench::solve(&words, strategy, answer, distances)),
+            );
+            let seconds = start.elapsed().as_secs_f64();
+
+            eprintln!(
+                "{:>8}, {:<14} mean {:.3}, p95 {}, max {}, {} failed ({:.1}s)",
+                strategy,
+                if distances {
+                    "distances:"
+                } else {
+                    "no distances:"
+                },
+                summary.mean,
+                summary.p95,
+                summary.max,
+                summary.failures,
+                seconds,
+            );
+
+            runs.push(Run {
+                strategy: strategy.to_string(),
+                distances,
+                seconds,
+                summary,
+            });
+        }
+    }
+
+    let report = Report {
+        word_list,
+        words: words.words().len(),
+        answers: answers.len(),
+        runs,
+    };
+
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    Ok(())
+}
@@ -1,32 +1,52 @@
 mod builder;
 mod handler;
+mod router;
+mod supervisor;
 
-use std::{collections::HashMap, ops::DerefMut};
+use std::{ops::DerefMut, sync::Arc};
 
 use eyre::Result;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use thiserror::Error;
-use tokio::sync::{broadcast, mpsc};
-use tracing::{debug, info, instrument};
-use twitch_irc::{login::RefreshingLoginCredentials, ClientConfig, TCPTransport, TwitchIRCClient};
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::{
+    sync::{mpsc, watch, Mutex},
+    task::JoinError,
+};
+use tracing::{debug, error, info, instrument};
+use twitch_irc::{
+    login::RefreshingLoginCredentials, message::ServerMessage, ClientConfig, TCPTransport,
+    TwitchIRCClient,
+};
 
 pub use self::{
     builder::{BotBuilder, BotTheBuilder},
     handler::{ProcessHandler, ReceiveHandler, RespondHandler},
+    router::{ChannelMetricsSnapshot, ResponseRouter, RouteError, DEFAULT_CHANNEL_CAPACITY},
+    supervisor::{RestartPolicy, Supervisor, SupervisorError},
 };
 use crate::{
-    auth::SQLiteTokenStore, commands::CommandsStore, parse::oxbow::CommandParser,
-    quotes::QuotesStore,
+    auth::SQLiteTokenStore,
+    chat::ChatClient,
+    messagelog::MessageLog,
+    module::{ModuleError, ModuleRegistry},
+    msg::cooldown::Cooldowns,
+    parse::oxbow::HelpParser,
 };
 
 /// The main `oxbow` bot entry point.
 pub struct Bot {
-    twitch_client_id: String,
-    twitch_client_secret: String,
+    /// The client ID and client secret used to connect to Twitch.
+    twitch_credentials: Option<(String, String)>,
     twitch_name: String,
     channels: Vec<String>,
     prefix: char,
+    owner: String,
+    help_url: String,
+    modules: ModuleRegistry,
+    message_log: MessageLog,
     conn_pool: Pool<SqliteConnectionManager>,
 }
 
@@ -43,104 +63,218 @@ impl Bot {
 
     /// Main run loop for the bot.
     ///
-    /// Spawns tasks to receive messages, and to send messages to each connected
-    /// channel.
+    /// Connects to Twitch, then runs the bot as described in
+    /// [`Bot::run_with`].
     #[instrument(skip(self), fields(channels = ?self.channels, twitch_name = %self.twitch_name, prefix = %self.prefix))]
     pub async fn run(&mut self) -> Result<(), BotError> {
         info!("starting bot");
 
-        let mut conn = self.conn_pool.get()?;
-        let report = crate::db::migrations::runner().run(conn.deref_mut())?;
-        debug!(?report);
+        let (client_id, client_secret) = self
+            .twitch_credentials
+            .clone()
+            .ok_or(BotError::NoCredentials)?;
+
+        self.migrate()?;
 
         let mut store = SQLiteTokenStore::new(self.conn_pool.clone());
-        crate::auth::authenticate(
-            &mut store,
-            &self.twitch_client_id,
-            &self.twitch_client_secret,
-        )
-        .await?;
+        crate::auth::authenticate(&mut store, &client_id, &client_secret).await?;
 
         let creds = RefreshingLoginCredentials::new(
             self.twitch_name.clone(),
-            self.twitch_client_id.clone(),
-            self.twitch_client_secret.clone(),
+            client_id,
+            client_secret,
             store,
         );
         let config = ClientConfig::new_simple(creds);
         let (msg_rx, client) = TwitchIRCClient::<TCPTransport, _>::new(config);
 
+        self.run_with(msg_rx, client).await
+    }
+
+    /// Run any database migrations that haven't been run yet.
+    pub fn migrate(&self) -> Result<(), BotError> {
+        let mut conn = self.conn_pool.get()?;
+        let report = crate::db::migrations::runner().run(conn.deref_mut())?;
+        debug!(?report);
+
+        Ok(())
+    }
+
+    /// Run the bot using an existing chat connection, reading incoming
+    /// messages from `msg_rx` and responding using `client`. The database must
+    /// already have been migrated with [`Bot::migrate`].
+    ///
+    /// Spawns tasks to receive messages, and to send messages to each connected
+    /// channel, and restarts them if they crash. Runs until SIGINT or SIGTERM
+    /// is received, or `msg_rx` is closed, at which point any pending
+    /// responses are sent before the bot disconnects.
+    #[instrument(skip(self, msg_rx, client), fields(channels = ?self.channels, prefix = %self.prefix))]
+    pub async fn run_with<C: ChatClient>(
+        &mut self,
+        msg_rx: mpsc::UnboundedReceiver<ServerMessage>,
+        client: C,
+    ) -> Result<(), BotError> {
+        self.modules.start()?;
+
+        // Channel that is set to `true` once the bot should shut down, either
+        // because of a signal or because one of the loops keeps crashing.
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let shutdown_tx = Arc::new(shutdown_tx);
+
+        tokio::spawn({
+            let shutdown_tx = shutdown_tx.clone();
+
+            async move {
+                match shutdown_signal().await {
+                    Ok(()) => {
+                        info!("received shutdown signal");
+                        let _ = shutdown_tx.send(true);
+                    }
+                    Err(err) => error!(%err, "failed to listen for shutdown signals"),
+                }
+            }
+        });
+
+        let supervisor = Supervisor::new(RestartPolicy::default(), shutdown_tx);
+
         // Channel for the receive loop to trigger tasks in the process loop.
         let (task_tx, task_rx) = mpsc::unbounded_channel();
 
-        // Channel for the process loop to trigger responses in the response
-        // loops.
-        let (res_tx_orig, _) = broadcast::channel(16);
+        // Router for the process loop to send responses to the response loop
+        // of the channel each one belongs to.
+        let router = ResponseRouter::default();
+
+        // Spawn a task to write the messages the receive loop logs to the
+        // database in batches. It stops once the receive loop has stopped and
+        // everything it logged has been written.
+        let (message_logger, message_log_writer) = self.message_log.spawn();
 
         // Spawn a receive loop to interpret incoming messages and turn them
-        // into Tasks if necessary.
-        let prefix = self.prefix;
-        let twitch_name = self.twitch_name.clone();
-        let receive_loop = tokio::spawn(async move {
-            let mut handler = ReceiveHandler {
-                msg_rx,
-                task_tx,
-                prefix,
-                twitch_name,
-                parser: CommandParser::new(),
-            };
-
-            handler.receive_loop().await;
+        // into Tasks if necessary. Handlers are kept behind a mutex so that
+        // their state survives if the loop crashes and has to be restarted.
+        let receive_handler = Arc::new(Mutex::new(ReceiveHandler {
+            msg_rx,
+            task_tx,
+            prefix: self.prefix,
+            owner: self.owner.clone(),
+            parser: HelpParser::new(),
+            modules: self.modules.clone(),
+            shutdown_rx,
+            usage_hints_sent: Cooldowns::new(handler::USAGE_HINT_COOLDOWN),
+            message_log: message_logger,
+        }));
+
+        let receive_loop = tokio::spawn({
+            let supervisor = supervisor.clone();
+
+            async move {
+                supervisor
+                    .supervise("receive", || {
+                        let handler = receive_handler.clone();
+                        async move { handler.lock().await.receive_loop().await }
+                    })
+                    .await
+            }
         });
 
         // Spawn a processing loop to interpret Tasks and turn them into
-        // Responses if necessary.
-        let res_tx = res_tx_orig.clone();
-        let commands = CommandsStore::new(self.conn_pool.clone());
-        let quotes = QuotesStore::new(self.conn_pool.clone());
-        let prefix = self.prefix;
-        let process_loop = tokio::spawn(async move {
-            let mut handler = ProcessHandler {
-                task_rx,
-                res_tx,
-                commands,
-                quotes,
-                prefix,
-                word_searches: HashMap::new(),
-            };
-
-            handler.process_loop().await;
+        // Responses if necessary. It stops once the receive loop has stopped
+        // and every task it sent has been handled.
+        let process_handler = Arc::new(Mutex::new(ProcessHandler {
+            task_rx,
+            router: router.clone(),
+            modules: self.modules.clone(),
+            prefix: self.prefix,
+            help_url: self.help_url.clone(),
+        }));
+
+        let process_loop = tokio::spawn({
+            let supervisor = supervisor.clone();
+
+            async move {
+                supervisor
+                    .supervise("process", || {
+                        let handler = process_handler.clone();
+                        async move { handler.lock().await.process_loop().await }
+                    })
+                    .await
+            }
         });
 
-        // For every channel, we need a response loop to perform Responses if
-        // they're relevant to that channel.
+        // For every channel, we need a response loop to perform the Responses
+        // routed to that channel.
+        let mut respond_loops = Vec::with_capacity(self.channels.len());
+
         for channel in self.channels.iter() {
             info!(?channel, "joining channel");
 
-            let res_rx = res_tx_orig.subscribe();
-            let client = client.clone();
-            let channel = channel.to_owned();
+            let respond_handler = Arc::new(Mutex::new(RespondHandler {
+                res_rx: router.add_channel(channel),
+                client: client.clone(),
+                channel: channel.to_owned(),
+            }));
 
-            tokio::spawn(async move {
-                let mut handler = RespondHandler {
-                    res_rx,
-                    client,
-                    channel,
-                };
+            let supervisor = supervisor.clone();
 
-                handler.respond_loop().await;
-            });
+            respond_loops.push(tokio::spawn(async move {
+                supervisor
+                    .supervise("respond", || {
+                        let handler = respond_handler.clone();
+                        async move { handler.lock().await.respond_loop().await }
+                    })
+                    .await
+            }));
         }
 
-        receive_loop.await.unwrap();
-        process_loop.await.unwrap();
+        let receive_result = receive_loop.await?;
+        message_log_writer.await?;
+        let process_result = process_loop.await?;
 
-        Ok(())
+        // Every task has been processed, so once the response loops have sent
+        // what's left in their buffers, they can leave their channels.
+        for channel in router.channels() {
+            router.remove_channel(&channel);
+        }
+
+        let mut respond_result = Ok(());
+
+        for respond_loop in respond_loops {
+            respond_result = respond_result.and(respond_loop.await?);
+        }
+
+        drop(client);
+
+        info!("bot stopped");
+
+        receive_result
+            .and(process_result)
+            .and(respond_result)
+            .map_err(Into::into)
     }
 }
 
+/// Wait until the process is asked to stop, by either SIGINT or SIGTERM.
+#[cfg(unix)]
+async fn shutdown_signal() -> std::io::Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+
+    tokio::select! {
+        res = tokio::signal::ctrl_c() => res,
+        _ = terminate.recv() => Ok(()),
+    }
+}
+
+/// Wait until the process is asked to stop by Ctrl-C.
+#[cfg(not(unix))]
+async fn shutdown_signal() -> std::io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
+
 #[derive(Debug, Error)]
 pub enum BotError {
+    #[error("no Twitch client ID and client secret provided")]
+    NoCredentials,
+
     #[error("migration error: {0}")]
     Migration(#[from] refinery::Error),
 
@@ -152,4 +286,750 @@ pub enum BotError {
 
     #[error("r2d2 error: {0}")]
     R2d2(#[from] r2d2::Error),
+
+    #[error("module error: {0}")]
+    Module(#[from] ModuleError),
+
+    #[error("supervisor error: {0}")]
+    Supervisor(#[from] SupervisorError),
+
+    #[error("failed to join task: {0}")]
+    Join(#[from] JoinError),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tempfile::{tempdir, TempDir};
+    use tokio::{task::JoinHandle, time::timeout};
+
+    use super::*;
+    use crate::{
+        chat::fake::{self, FakeServer, Said},
+        wordsearch::{auto::AutoPlay, list::WordListSource},
+    };
+
+    /// A builder for a bot in the channel `channel`, using a database in
+    /// `dir`.
+    fn builder(dir: &TempDir) -> BotBuilder {
+        Bot::builder()
+            .twitch_credentials("id", "secret")
+            .twitch_name("oxbow")
+            .add_channel("channel")
+            .prefix('!')
+            .owner("owner")
+            .help_url("oxbow.cacti.dev/commands")
+            .db_path(dir.path().join("db.sqlite3"))
+    }
+
+    /// Start a bot in the channel `channel`, connected to a fake chat and using
+    /// a database in a temporary directory.
+    fn start() -> (TempDir, FakeServer, JoinHandle<Result<(), BotError>>) {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let (server, bot) = start_with(builder(&dir));
+
+        (dir, server, bot)
+    }
+
+    fn start_with(builder: BotBuilder) -> (FakeServer, JoinHandle<Result<(), BotError>>) {
+        let mut bot = builder.build().expect("building the bot should succeed");
+
+        bot.migrate().expect("migrations should succeed");
+
+        let (msg_rx, chat, server) = fake::connect();
+        let bot = tokio::spawn(async move { bot.run_with(msg_rx, chat).await });
+
+        (server, bot)
+    }
+
+    async fn said(server: &mut FakeServer) -> String {
+        let Said { channel, message } = timeout(Duration::from_secs(5), server.said())
+            .await
+            .expect("the bot should respond in time")
+            .expect("the bot should still be connected");
+
+        assert_eq!(channel, "channel");
+
+        message
+    }
+
+    async fn stop(server: FakeServer, bot: JoinHandle<Result<(), BotError>>) {
+        drop(server);
+
+        timeout(Duration::from_secs(5), bot)
+            .await
+            .expect("the bot should stop in time")
+            .expect("the bot task should not panic")
+            .expect("the bot should stop without an error");
+    }
+
+    #[tokio::test]
+    async fn add_quote_then_fetch() {
+        let (_dir, mut server, bot) = start();
+
+        server
+            .privmsg(
+                "channel",
+                "someone",
+                &[],
+                r#"!quote @nerosnm #hi "hello there""#,
+            )
+            .expect("sending a message should succeed");
+
+        assert!(said(&mut server)
+            .await
+            .starts_with("Quote #hi added from @nerosnm"));
+
+        server
+            .privmsg("channel", "someone", &[], "!quote #hi")
+            .expect("sending a message should succeed");
+
+        assert!(said(&mut server).await.contains("hello there"));
+
+        stop(server, bot).await;
+    }
+
+    #[tokio::test]
+    async fn custom_command_with_arguments() {
+        let (_dir, mut server, bot) = start();
+
+        server
+            .privmsg(
+                "channel",
+                "someone",
+                &[],
+                r#"!command hug "{sender} hugs {1}""#,
+            )
+            .expect("sending a message should succeed");
+
+        assert_eq!(said(&mut server).await, "Added !hug");
+
+        server
+            .privmsg("channel", "someone", &[], "!HUG @friend")
+            .expect("sending a message should succeed");
+
+        assert_eq!(said(&mut server).await, "someone hugs @friend");
+
+        stop(server, bot).await;
+    }
+
+    #[tokio::test]
+    async fn usage_hint_and_greeting() {
+        let (_dir, mut server, bot) = start();
+
+        server
+            .privmsg("channel", "someone", &[], "!quote @nerosnm hi there")
+            .expect("sending a message should succeed");
+
+        assert!(said(&mut server)
+            .await
+            .starts_with("@someone Usage error in !quote"));
+
+        server
+            .privmsg("channel", "someone", &[], "hi @oxbow")
+            .expect("sending a message should succeed");
+
+        assert_eq!(said(&mut server).await, "uwu *nuzzles @someone*");
+
+        stop(server, bot).await;
+    }
+
+    #[tokio::test]
+    async fn commands_depend_on_role() {
+        let (_dir, mut server, bot) = start();
+
+        server
+            .privmsg("channel", "someone", &[], "!search")
+            .expect("sending a message should succeed");
+        server
+            .privmsg("channel", "owner", &[], "!search")
+            .expect("sending a message should succeed");
+
+        // Only the owner's search is answered, so it's the first response.
+        assert!(said(&mut server).await.starts_with("!wg "));
+
+        stop(server, bot).await;
+    }
+
+    #[tokio::test]
+    async fn channel_word_list() {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let path = dir.path().join("words.txt");
+        std::fs::write(&path, "apple\nbanana\ncherry\n").expect("writing words should succeed");
+
+        let (mut server, bot) = start_with(
+            builder(&dir)
+                .import_word_list("fruit", &path)
+                .word_list("channel", WordListSource::Database("fruit".to_owned())),
+        );
+
+        server
+            .privmsg("channel", "owner", &[], "!search")
+            .expect("sending a message should succeed");
+
+        assert_eq!(said(&mut server).await, "!wg banana");
+
+        stop(server, bot).await;
+    }
+
+    #[tokio::test]
+    async fn invalid_word_list_stops_startup() {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let path = dir.path().join("words.txt");
+        std::fs::write(&path, "banana\napple\n").expect("writing words should succeed");
+
+        let (_server, bot) =
+            start_with(builder(&dir).word_list("channel", WordListSource::File(path)));
+
+        let result = timeout(Duration::from_secs(5), bot)
+            .await
+            .expect("the bot should stop in time")
+            .expect("the bot task should not panic");
+
+        assert!(matches!(
+            result,
+            Err(BotError::Module(ModuleError::WordList { .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn auto_play_word_search() {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let path = dir.path().join("words.txt");
+        std::fs::write(&path, "apple\nbanana\ncherry\ndate\nelderberry\n")
+            .expect("writing words should succeed");
+
+        let (mut server, bot) = start_with(
+            builder(&dir)
+                .word_list("channel", WordListSource::File(path))
+                .auto_play(AutoPlay::new("game").guess_interval(Duration::from_secs(1))),
+        );
+
+        // The game bot's replies are ignored until a search is started.
+        server
+            .privmsg("channel", "game", &[], "cherry is before the word")
+            .expect("sending a message should succeed");
+        server
+            .privmsg("channel", "owner", &[], "!search")
+            .expect("sending a message should succeed");
+
+        assert_eq!(said(&mut server).await, "!wg banana");
+
+        server
+            .privmsg(
+                "channel",
+                "game",
+                &[],
+                "@oxbow banana is before the word, distance 6",
+            )
+            .expect("sending a message should succeed");
+        server
+            .privmsg("channel", "someone", &[], "!help")
+            .expect("sending a message should succeed");
+
+        // The next guess waits for the guess interval without holding up
+        // anything else.
+        assert!(said(&mut server).await.starts_with("@someone See"));
+        assert_eq!(said(&mut server).await, "!wg cherry");
+
+        server
+            .privmsg("channel", "game", &[], "@oxbow cherry is the word!")
+            .expect("sending a message should succeed");
+        server
+            .privmsg("channel", "game", &[], "@someone apple is before the word")
+            .expect("sending a message should succeed");
+        server
+            .privmsg("channel", "someone", &[], "!help")
+            .expect("sending a message should succeed");
+
+        // Once the word has been found, no more guesses are made.
+        assert!(said(&mut server).await.starts_with("@someone See"));
+
+        stop(server, bot).await;
+    }
+
+    #[tokio::test]
+    async fn resume_after_restart() {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let path = dir.path().join("words.txt");
+        std::fs::write(&path, "apple\nbanana\ncherry\ndate\nelderberry\n")
+            .expect("writing words should succeed");
+        let builder = || builder(&dir).word_list("channel", WordListSource::File(path.clone()));
+
+        let (mut server, bot) = start_with(builder());
+
+        server
+            .privmsg("channel", "owner", &[], "!search resume")
+            .expect("sending a message should succeed");
+
+        assert!(said(&mut server)
+            .await
+            .starts_with("No word search to resume!"));
+
+        server
+            .privmsg("channel", "owner", &[], "!search")
+            .expect("sending a message should succeed");
+
+        assert_eq!(said(&mut server).await, "!wg banana");
+
+        server
+            .privmsg("channel", "owner", &[], "!lower banana")
+            .expect("sending a message should succeed");
+
+        let guess = said(&mut server).await;
+
+        stop(server, bot).await;
+
+        let (mut server, bot) = start_with(builder());
+
+        server
+            .privmsg("channel", "owner", &[], "!search resume")
+            .expect("sending a message should succeed");
+
+        assert_eq!(said(&mut server).await, guess);
+
+        stop(server, bot).await;
+    }
+
+    #[tokio::test]
+    async fn search_status_undo_and_contradiction() {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let path = dir.path().join("words.txt");
+        std::fs::write(&path, "apple\nbanana\ncherry\ndate\nelderberry\n")
+            .expect("writing words should succeed");
+
+        let (mut server, bot) =
+            start_with(builder(&dir).word_list("channel", WordListSource::File(path)));
+
+        for message in ["!search", "!lower banana"] {
+            server
+                .privmsg("channel", "owner", &[], message)
+                .expect("sending a message should succeed");
+
+            assert!(said(&mut server).await.starts_with("!wg "));
+        }
+
+        server
+            .privmsg("channel", "owner", &[], "!upper apple")
+            .expect("sending a message should succeed");
+
+        assert!(said(&mut server)
+            .await
+            .starts_with("@owner That doesn't leave any possible words"));
+
+        server
+            .privmsg("channel", "owner", &[], "!search status")
+            .expect("sending a message should succeed");
+
+        assert_eq!(
+            said(&mut server).await,
+            "Word search in progress: 3 possible words, after banana"
+        );
+
+        server
+            .privmsg("channel", "owner", &[], "!search undo")
+            .expect("sending a message should succeed");
+
+        assert_eq!(
+            said(&mut server).await,
+            "Forgot that the word is after banana. Word search in progress: 5 possible words"
+        );
+
+        stop(server, bot).await;
+    }
+
+    #[tokio::test]
+    async fn word_game_with_cooldown() {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let path = dir.path().join("words.txt");
+        std::fs::write(&path, "apple\n").expect("writing words should succeed");
+
+        let (mut server, bot) =
+            start_with(builder(&dir).word_list("channel", WordListSource::File(path)));
+
+        server
+            .privmsg("channel", "owner", &[], "!wordgame")
+            .expect("sending a message should succeed");
+
+        assert!(said(&mut server)
+            .await
+            .starts_with("I'm thinking of a word!"));
+
+        server
+            .privmsg("channel", "someone", &[], "!guess durian")
+            .expect("sending a message should succeed");
+
+        assert_eq!(
+            said(&mut server).await,
+            "@someone durian isn't in my word list"
+        );
+
+        // Words that aren't in the list don't start a cooldown.
+        server
+            .privmsg("channel", "someone", &[], "!guess apple")
+            .expect("sending a message should succeed");
+
+        assert_eq!(
+            said(&mut server).await,
+            "@someone got it, the word was apple! Found in 1 guess. @someone has won 1 game"
+        );
+
+        server
+            .privmsg("channel", "owner", &[], "!wordgame")
+            .expect("sending a message should succeed");
+
+        assert!(said(&mut server)
+            .await
+            .starts_with("I'm thinking of a word!"));
+
+        // Too soon after the last guess, so it's ignored.
+        server
+            .privmsg("channel", "someone", &[], "!guess apple")
+            .expect("sending a message should succeed");
+        server
+            .privmsg("channel", "other", &[], "!guess Apple")
+            .expect("sending a message should succeed");
+
+        assert_eq!(
+            said(&mut server).await,
+            "@other got it, the word was apple! Found in 1 guess. @other has won 1 game"
+        );
+
+        server
+            .privmsg("channel", "someone", &[], "!wordscores")
+            .expect("sending a message should succeed");
+
+        assert_eq!(
+            said(&mut server).await,
+            "Word game leaderboard: 1. @other (1), 2. @someone (1)"
+        );
+
+        stop(server, bot).await;
+    }
+
+    #[tokio::test]
+    async fn trivia_answer_and_timeout() {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let path = dir.path().join("geography.csv");
+        std::fs::write(&path, "question,answer\nCapital of France?,Paris\n")
+            .expect("writing questions should succeed");
+
+        let (mut server, bot) = start_with(
+            builder(&dir)
+                .import_trivia("geography", &path)
+                .trivia_round_time(Duration::from_secs(1)),
+        );
+
+        server
+            .privmsg("channel", "owner", &[], "!trivia geography")
+            .expect("sending a message should succeed");
+
+        assert_eq!(
+            said(&mut server).await,
+            "Trivia time! Capital of France? (you have 1 second)"
+        );
+
+        for (sender, answer) in [
+            ("someone", "london"),
+            ("other", "pariss"),
+            ("someone", "Paris"),
+        ] {
+            server
+                .privmsg("channel", sender, &[], answer)
+                .expect("sending a message should succeed");
+        }
+
+        // Only the first right answer counts, even with a typo.
+        assert_eq!(
+            said(&mut server).await,
+            "@other got it, the answer was Paris! @other has 1 point"
+        );
+
+        server
+            .privmsg("channel", "owner", &[], "!trivia")
+            .expect("sending a message should succeed");
+
+        assert!(said(&mut server).await.starts_with("Trivia time!"));
+        assert_eq!(said(&mut server).await, "Time's up! The answer was Paris");
+
+        server
+            .privmsg("channel", "someone", &[], "!triviascores")
+            .expect("sending a message should succeed");
+
+        assert_eq!(said(&mut server).await, "Trivia leaderboard: 1. @other (1)");
+
+        stop(server, bot).await;
+    }
+
+    #[tokio::test]
+    async fn raffle_entries_close_and_draw() {
+        let (_dir, mut server, bot) = start();
+
+        server
+            .privmsg("channel", "owner", &[], "!raffle start join 1")
+            .expect("sending a message should succeed");
+
+        assert_eq!(
+            said(&mut server).await,
+            "Raffle time! Type join to enter, entries close in 1 second"
+        );
+
+        // Entering twice doesn't give anyone a second entry.
+        for (sender, text) in [("someone", "join"), ("someone", "Join"), ("other", "joint")] {
+            server
+                .privmsg("channel", sender, &[], text)
+                .expect("sending a message should succeed");
+        }
+
+        assert_eq!(
+            said(&mut server).await,
+            "Entries for the raffle are closed, with 1 entry! Draw a winner with !raffle draw"
+        );
+
+        for (text, response) in [
+            ("!raffle draw", "@someone won the raffle, out of 1 entry!"),
+            (
+                "!raffle reroll",
+                "Re-rolled @someone, but there's nobody left who can win the raffle!",
+            ),
+            (
+                "!raffle luck subscriber 2",
+                "Raffle entries from a subscriber now count 2 times",
+            ),
+        ] {
+            server
+                .privmsg("channel", "owner", &[], text)
+                .expect("sending a message should succeed");
+
+            assert_eq!(said(&mut server).await, response);
+        }
+
+        stop(server, bot).await;
+    }
+
+    #[tokio::test]
+    async fn poll_votes_and_result() {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let (mut server, bot) = start_with(builder(&dir).poll_updates(Duration::from_secs(1)));
+
+        server
+            .privmsg(
+                "channel",
+                "owner",
+                &[],
+                r#"!poll "Best snack?" "crisps" "fruit" 18446744073709551615"#,
+            )
+            .expect("sending a message should succeed");
+
+        assert_eq!(
+            said(&mut server).await,
+            "@owner That's not a length of time I can use!"
+        );
+
+        server
+            .privmsg(
+                "channel",
+                "owner",
+                &[],
+                r#"!poll "Best snack?" "crisps" "fruit" 3"#,
+            )
+            .expect("sending a message should succeed");
+
+        assert_eq!(
+            said(&mut server).await,
+            "Poll time! Best snack? Vote with !vote <number> or by typing an option: 1. crisps, \
+             2. fruit (closes in 3 seconds)"
+        );
+
+        // The second vote from someone replaces their first.
+        for (sender, text) in [
+            ("someone", "!vote 1"),
+            ("other", "Fruit"),
+            ("someone", "fruit"),
+        ] {
+            server
+                .privmsg("channel", sender, &[], text)
+                .expect("sending a message should succeed");
+        }
+
+        server
+            .privmsg("channel", "someone", &[], "!vote 3")
+            .expect("sending a message should succeed");
+
+        assert_eq!(
+            said(&mut server).await,
+            "@someone Pick an option from 1 to 2"
+        );
+        assert_eq!(
+            said(&mut server).await,
+            "Poll update: Best snack? 1. crisps: 0 (0%), 2. fruit: 2 (100%)"
+        );
+        assert_eq!(
+            said(&mut server).await,
+            "Poll closed! Best snack? fruit wins with 2 of 2 votes. Final votes: 1. crisps: 0 \
+             (0%), 2. fruit: 2 (100%)"
+        );
+
+        stop(server, bot).await;
+    }
+
+    #[tokio::test]
+    async fn counter_replaces_command() {
+        let (_dir, mut server, bot) = start();
+
+        for (sender, badges, text, response) in [
+            ("owner", &[][..], r#"!command deaths "41""#, "Added !deaths"),
+            (
+                "owner",
+                &[],
+                "!counter add Points",
+                "@owner There's already a !points built-in command",
+            ),
+            (
+                "owner",
+                &[],
+                "!counter add deaths",
+                "Turned !deaths into a counter, starting at 41",
+            ),
+            ("owner", &[], "!deaths +1", "deaths: 42"),
+            (
+                "owner",
+                &[],
+                r#"!counter format deaths "Deaths: {count}""#,
+                "Changed the format of !deaths, it now looks like: Deaths: 42",
+            ),
+            ("owner", &[], "!deaths set -2", "Deaths: -2"),
+            ("owner", &[], "!Deaths reset", "Deaths: 0"),
+            (
+                "owner",
+                &[],
+                "!counter role deaths vip",
+                "Now anyone who's a vip or higher can change !deaths",
+            ),
+            ("vip", &["vip"], "!deaths -1", "Deaths: -1"),
+            (
+                "owner",
+                &[],
+                "!deaths +x",
+                "@owner Usage error in !deaths: expected reset, set or a number like +1 or -1 at \
+                 character 9, but found +x",
+            ),
+        ] {
+            server
+                .privmsg("channel", sender, badges, text)
+                .expect("sending a message should succeed");
+
+            assert_eq!(said(&mut server).await, response);
+        }
+
+        // Changes from chatters without the role are ignored.
+        server
+            .privmsg("channel", "someone", &[], "!deaths +1")
+            .expect("sending a message should succeed");
+        server
+            .privmsg("channel", "someone", &[], "!deaths")
+            .expect("sending a message should succeed");
+
+        assert_eq!(said(&mut server).await, "Deaths: -1");
+
+        stop(server, bot).await;
+    }
+
+    #[tokio::test]
+    async fn message_log_last_seen_and_opt_out() {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        // Messages are written as soon as they're sent, so they can be looked
+        // up straight away.
+        let (mut server, bot) = start_with(
+            builder(&dir)
+                .log_messages("channel")
+                .message_log_flush_interval(Duration::ZERO),
+        );
+
+        server
+            .privmsg("channel", "friend", &[], "hello everyone")
+            .expect("sending a message should succeed");
+        server
+            .privmsg("channel", "someone", &[], "!firstseen @nobody")
+            .expect("sending a message should succeed");
+
+        assert_eq!(said(&mut server).await, "I haven't seen @nobody here");
+
+        server
+            .privmsg("channel", "someone", &[], "!lastseen @Friend")
+            .expect("sending a message should succeed");
+
+        assert_eq!(said(&mut server).await, "@friend was last seen just now");
+
+        server
+            .privmsg("channel", "friend", &[], "!optout")
+            .expect("sending a message should succeed");
+
+        assert!(said(&mut server)
+            .await
+            .starts_with("@friend Your messages won't be logged any more"));
+
+        server
+            .privmsg("channel", "friend", &[], "still here")
+            .expect("sending a message should succeed");
+
+        server
+            .privmsg("channel", "someone", &[], "!lastseen @friend")
+            .expect("sending a message should succeed");
+
+        assert_eq!(said(&mut server).await, "I haven't seen @friend here");
+
+        stop(server, bot).await;
+    }
+
+    #[tokio::test]
+    async fn points_earned_and_spent() {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let (mut server, bot) =
+            start_with(builder(&dir).chat_points(5, Duration::from_secs(60 * 60)));
+
+        // Only the first message in the interval earns points.
+        for text in ["hello", "hello again"] {
+            server
+                .privmsg("channel", "someone", &[], text)
+                .expect("sending a message should succeed");
+        }
+
+        for (sender, text, response) in [
+            ("owner", r#"!command hug "{sender} hugs {1}""#, "Added !hug"),
+            ("owner", "!cost hug 3", "!hug now costs 3 points"),
+            ("someone", "!hug @friend", "someone hugs @friend"),
+            ("someone", "!points", "@someone You have 2 points"),
+            (
+                "someone",
+                "!hug @friend",
+                "@someone !hug costs 3 points, but you only have 2 points",
+            ),
+            (
+                "someone",
+                "!give @other 3",
+                "@someone You can't give away 3 points, you only have 2 points",
+            ),
+            (
+                "someone",
+                "!give @Other 2",
+                "@someone gave @other 2 points, and has 0 points left",
+            ),
+            ("someone", "!points @other", "@other has 2 points"),
+            (
+                "owner",
+                "!addpoints @someone 10",
+                "Gave @someone 10 points, they now have 10 points",
+            ),
+        ] {
+            server
+                .privmsg("channel", sender, &[], text)
+                .expect("sending a message should succeed");
+
+            assert_eq!(said(&mut server).await, response);
+        }
+
+        stop(server, bot).await;
+    }
 }
@@ -1,4 +1,7 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use eyre::Result;
 use r2d2::Pool;
@@ -6,7 +9,28 @@ use r2d2_sqlite::SqliteConnectionManager;
 use thiserror::Error;
 use tracing::error;
 
-use crate::bot::Bot;
+use crate::{
+    bot::Bot,
+    commands::CommandsStore,
+    counters::CountersStore,
+    messagelog::{MessageLog, MessageLogStore, OptOuts, DEFAULT_FLUSH_INTERVAL, DEFAULT_RETENTION},
+    module::{
+        CountersModule, CustomCommandsModule, GreetModule, MessageLogModule, Module,
+        ModuleRegistry, PointsModule, PollModule, QuotesModule, RaffleModule, TriviaModule,
+        WordGameModule, WordSearchModule, DEFAULT_CHAT_INTERVAL, DEFAULT_CHAT_POINTS,
+        DEFAULT_GUESS_COOLDOWN, DEFAULT_POLL_UPDATES, DEFAULT_ROUND_TIME,
+    },
+    points::PointsStore,
+    quotes::QuotesStore,
+    raffle::RaffleStore,
+    scores::ScoresStore,
+    trivia::TriviaStore,
+    wordsearch::{
+        auto::AutoPlay,
+        list::{WordListSource, WordListStore},
+        session::SessionStore,
+    },
+};
 
 /// The number one single when Twitch user @NinthRoads was born was Bob The
 /// Builder.
@@ -21,10 +45,27 @@ pub struct BotBuilder {
     channels: Option<Vec<String>>,
     db_path: Option<PathBuf>,
     prefix: Option<char>,
+    owner: Option<String>,
+    help_url: Option<String>,
+    disabled_modules: Vec<(String, String)>,
+    word_lists: Vec<(String, WordListSource)>,
+    word_list_imports: Vec<(String, PathBuf)>,
+    auto_play: Option<AutoPlay>,
+    word_game_cooldown: Option<Duration>,
+    trivia_imports: Vec<(String, PathBuf)>,
+    trivia_round_time: Option<Duration>,
+    chat_points: Option<(u32, Duration)>,
+    poll_updates: Option<Duration>,
+    logged_channels: Vec<String>,
+    message_log_retention: Option<Option<Duration>>,
+    message_log_flush_interval: Option<Duration>,
 }
 
 impl BotBuilder {
     /// Set the client ID and client secret this bot will use for authentication with Twitch.
+    ///
+    /// These are only needed to connect to Twitch with [`Bot::run`], and not to
+    /// run the bot using another chat connection.
     pub fn twitch_credentials<S1: ToString, S2: ToString>(
         mut self,
         client_id: S1,
@@ -68,6 +109,112 @@ impl BotBuilder {
         self
     }
 
+    /// Set the Twitch username of the bot's owner, who is allowed to run every
+    /// command in every channel.
+    pub fn owner<S: ToString>(mut self, owner: S) -> Self {
+        self.owner = Some(owner.to_string());
+        self
+    }
+
+    /// Set the URL of the page that help responses link to, such as
+    /// `oxbow.cacti.dev/commands`.
+    pub fn help_url<S: ToString>(mut self, help_url: S) -> Self {
+        self.help_url = Some(help_url.to_string());
+        self
+    }
+
+    /// Disable the module called `module` in `channel`, so that none of its
+    /// commands can be used there.
+    pub fn disable_module<S1: ToString, S2: ToString>(mut self, channel: S1, module: S2) -> Self {
+        self.disabled_modules
+            .push((channel.to_string(), module.to_string()));
+        self
+    }
+
+    /// Use the word list from `source` for word searches in `channel`,
+    /// instead of the built-in list.
+    pub fn word_list<S: ToString>(mut self, channel: S, source: WordListSource) -> Self {
+        self.word_lists.push((channel.to_string(), source));
+        self
+    }
+
+    /// Save the word list in the file at `path` to the database when the bot
+    /// starts, with the name `name`, so that it can be used with
+    /// [`WordListSource::Database`].
+    pub fn import_word_list<S: ToString, P: AsRef<Path>>(mut self, name: S, path: P) -> Self {
+        self.word_list_imports
+            .push((name.to_string(), path.as_ref().to_owned()));
+        self
+    }
+
+    /// Play word searches automatically, by reading the game bot's replies to
+    /// each guess, once a search has been started.
+    pub fn auto_play(mut self, auto_play: AutoPlay) -> Self {
+        self.auto_play = Some(auto_play);
+        self
+    }
+
+    /// Make users wait for `cooldown` between guesses in word games, instead
+    /// of [`DEFAULT_GUESS_COOLDOWN`].
+    pub fn word_game_cooldown(mut self, cooldown: Duration) -> Self {
+        self.word_game_cooldown = Some(cooldown);
+        self
+    }
+
+    /// Save the trivia questions in the file at `path` to the database when the
+    /// bot starts, in the bank called `bank`.
+    ///
+    /// See [`read_bank`](crate::trivia::read_bank) for the formats that can be read.
+    pub fn import_trivia<S: ToString, P: AsRef<Path>>(mut self, bank: S, path: P) -> Self {
+        self.trivia_imports
+            .push((bank.to_string(), path.as_ref().to_owned()));
+        self
+    }
+
+    /// Give chatters `round_time` to answer each trivia question, instead of
+    /// [`DEFAULT_ROUND_TIME`].
+    pub fn trivia_round_time(mut self, round_time: Duration) -> Self {
+        self.trivia_round_time = Some(round_time);
+        self
+    }
+
+    /// Give chatters `amount` points for chatting, at most once every
+    /// `interval`, instead of [`DEFAULT_CHAT_POINTS`] every
+    /// [`DEFAULT_CHAT_INTERVAL`].
+    pub fn chat_points(mut self, amount: u32, interval: Duration) -> Self {
+        self.chat_points = Some((amount, interval));
+        self
+    }
+
+    /// Announce the running totals of polls at most once every `updates`,
+    /// instead of every [`DEFAULT_POLL_UPDATES`].
+    pub fn poll_updates(mut self, updates: Duration) -> Self {
+        self.poll_updates = Some(updates);
+        self
+    }
+
+    /// Log the messages sent in `channel`, so that chatters can look up when
+    /// someone was first and last seen there.
+    pub fn log_messages<S: ToString>(mut self, channel: S) -> Self {
+        self.logged_channels.push(channel.to_string());
+        self
+    }
+
+    /// Delete logged messages once they're older than `retention`, or never if
+    /// it's `None`, instead of after [`DEFAULT_RETENTION`].
+    pub fn message_log_retention(mut self, retention: Option<Duration>) -> Self {
+        self.message_log_retention = Some(retention);
+        self
+    }
+
+    /// Let logged messages wait up to `flush_interval` to be written to the
+    /// database, instead of [`DEFAULT_FLUSH_INTERVAL`]. If it's zero, each
+    /// message is written as soon as it's sent.
+    pub fn message_log_flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.message_log_flush_interval = Some(flush_interval);
+        self
+    }
+
     /// Set the bot to attempt to open the SQLite3 database at `path` and use
     /// that as its database, instead of using an in-memory database.
     pub fn db_path<P: AsRef<Path>>(mut self, path: P) -> Self {
@@ -77,13 +224,12 @@ impl BotBuilder {
 
     /// Create a [`Bot`] from this builder, validating the provided values.
     pub fn build(self) -> Result<Bot, BotBuildError> {
-        let twitch_client_id = self.twitch_client_id.ok_or(BotBuildError::NoClientId)?;
-        let twitch_client_secret = self
-            .twitch_client_secret
-            .ok_or(BotBuildError::NoClientSecret)?;
+        let twitch_credentials = self.twitch_client_id.zip(self.twitch_client_secret);
         let twitch_name = self.twitch_name.ok_or(BotBuildError::NoTwitchName)?;
         let channels = self.channels.ok_or(BotBuildError::NoChannels)?;
         let prefix = self.prefix.ok_or(BotBuildError::NoPrefix)?;
+        let owner = self.owner.ok_or(BotBuildError::NoOwner)?;
+        let help_url = self.help_url.ok_or(BotBuildError::NoHelpUrl)?;
 
         let manager = self.db_path.map_or_else(
             SqliteConnectionManager::memory,
@@ -92,12 +238,118 @@ impl BotBuilder {
 
         let conn_pool = Pool::new(manager)?;
 
+        let mut modules = ModuleRegistry::new();
+        modules.register(GreetModule::new(&twitch_name));
+        modules.register(QuotesModule::new(QuotesStore::new(conn_pool.clone())));
+
+        let word_search = self.word_lists.into_iter().fold(
+            WordSearchModule::new(
+                prefix,
+                WordListStore::new(conn_pool.clone()),
+                SessionStore::new(conn_pool.clone()),
+            ),
+            |module, (channel, source)| module.use_list(&channel, source),
+        );
+        let word_search = self
+            .word_list_imports
+            .into_iter()
+            .fold(word_search, |module, (name, path)| {
+                module.import_list(&name, path)
+            });
+        let word_search = match self.auto_play {
+            Some(auto_play) => word_search.auto_play(auto_play),
+            None => word_search,
+        };
+
+        // The game has to pick words from the same lists the word search uses.
+        let word_game = WordGameModule::new(
+            prefix,
+            word_search.lists(),
+            ScoresStore::new(conn_pool.clone(), "wordgame"),
+        )
+        .cooldown(self.word_game_cooldown.unwrap_or(DEFAULT_GUESS_COOLDOWN));
+
+        modules.register(word_search);
+        modules.register(word_game);
+
+        let trivia = self.trivia_imports.into_iter().fold(
+            TriviaModule::new(
+                TriviaStore::new(conn_pool.clone()),
+                ScoresStore::new(conn_pool.clone(), "trivia"),
+            ),
+            |module, (bank, path)| module.import_bank(&bank, path),
+        );
+        modules.register(trivia.round_time(self.trivia_round_time.unwrap_or(DEFAULT_ROUND_TIME)));
+
+        modules.register(RaffleModule::new(
+            prefix,
+            RaffleStore::new(conn_pool.clone()),
+        ));
+
+        modules.register(
+            PollModule::new(prefix).updates(self.poll_updates.unwrap_or(DEFAULT_POLL_UPDATES)),
+        );
+
+        let message_log_store = MessageLogStore::new(conn_pool.clone());
+        let opt_outs = OptOuts::default();
+        let retention = self
+            .message_log_retention
+            .unwrap_or(Some(DEFAULT_RETENTION));
+        let message_log = self.logged_channels.iter().fold(
+            MessageLog::new(message_log_store.clone(), opt_outs.clone())
+                .retention(retention)
+                .flush_interval(
+                    self.message_log_flush_interval
+                        .unwrap_or(DEFAULT_FLUSH_INTERVAL),
+                ),
+            |log, channel| log.channel(channel),
+        );
+        modules.register(MessageLogModule::new(
+            message_log_store,
+            message_log.clone(),
+            opt_outs,
+        ));
+
+        let points = PointsStore::new(conn_pool.clone());
+        let (chat_points, chat_interval) = self
+            .chat_points
+            .unwrap_or((DEFAULT_CHAT_POINTS, DEFAULT_CHAT_INTERVAL));
+        modules.register(PointsModule::new(points.clone()).earn(chat_points, chat_interval));
+
+        // Counters and custom commands can have any name, so they're registered
+        // last to give every other module the first chance at a command. A
+        // counter replaces any custom command with the same name, so counters
+        // come first, but can't take the name of any built-in command.
+        let custom_commands =
+            CustomCommandsModule::new(CommandsStore::new(conn_pool.clone()), prefix).points(points);
+        let counters = CountersModule::new(prefix, CountersStore::new(conn_pool.clone())).reserve(
+            modules.command_names().into_iter().chain(
+                custom_commands
+                    .commands()
+                    .iter()
+                    .map(|built_in| built_in.name),
+            ),
+        );
+        modules.register(counters);
+        modules.register(custom_commands);
+
+        for (channel, module) in self.disabled_modules {
+            if !modules.names().contains(&module.as_str()) {
+                return Err(BotBuildError::UnknownModule(module));
+            }
+
+            modules.disable(&channel, &module);
+        }
+
         Ok(Bot {
-            twitch_client_id,
-            twitch_client_secret,
+            twitch_credentials,
             twitch_name,
             channels,
             prefix,
+            owner,
+            help_url,
+            modules,
+            message_log,
             conn_pool,
         })
     }
@@ -105,12 +357,6 @@ impl BotBuilder {
 
 #[derive(Debug, Error)]
 pub enum BotBuildError {
-    #[error("no client ID provided")]
-    NoClientId,
-
-    #[error("no client secret provided")]
-    NoClientSecret,
-
     #[error("no twitch name provided")]
     NoTwitchName,
 
@@ -120,6 +366,15 @@ pub enum BotBuildError {
     #[error("no prefix provided")]
     NoPrefix,
 
+    #[error("no owner provided")]
+    NoOwner,
+
+    #[error("no help URL provided")]
+    NoHelpUrl,
+
+    #[error("no module called {0}")]
+    UnknownModule(String),
+
     #[error("rusqlite error: {0}")]
     Rusqlite(#[from] rusqlite::Error),
 
@@ -3,5 +3,5 @@ mod receive;
 mod respond;
 
 pub use process::ProcessHandler;
-pub use receive::ReceiveHandler;
+pub use receive::{ReceiveHandler, USAGE_HINT_COOLDOWN};
 pub use respond::RespondHandler;
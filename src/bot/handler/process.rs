@@ -1,63 +1,118 @@
-use std::{collections::HashMap, iter};
+use std::iter;
 
-use chrono::Utc;
 use indoc::formatdoc;
-use tap::{Pipe, TapFallible, TapOptional};
+use tap::{Pipe, TapFallible};
 use thiserror::Error;
-use tokio::sync::{
-    broadcast::{self, error::SendError},
-    mpsc,
+use tokio::{
+    sync::mpsc,
+    time::{self, MissedTickBehavior},
 };
 use tracing::{debug, error, info, instrument, trace, warn};
 
 use crate::{
-    commands::{CommandsError, CommandsStore},
-    msg::{BuiltInCommand, Help, ImplicitTask, Metadata, Response, Task, WithMeta},
-    quotes::{QuotesError, QuotesStore},
-    wordsearch::WordSearch,
+    bot::router::{ResponseRouter, RouteError},
+    module::{ModuleError, ModuleRegistry, TICK_INTERVAL},
+    msg::{Help, Metadata, Response, Task, WithMeta},
 };
 
 pub struct ProcessHandler {
     pub(in crate::bot) task_rx: mpsc::UnboundedReceiver<(Task, Metadata)>,
-    pub(in crate::bot) res_tx: broadcast::Sender<(Response, Metadata)>,
-    pub(in crate::bot) commands: CommandsStore,
-    pub(in crate::bot) quotes: QuotesStore,
+    pub(in crate::bot) router: ResponseRouter,
+    pub(in crate::bot) modules: ModuleRegistry,
     pub(in crate::bot) prefix: char,
-    pub(in crate::bot) word_searches: HashMap<String, WordSearch>,
+    pub(in crate::bot) help_url: String,
 }
 
 impl ProcessHandler {
-    /// Loops over incoming [`Task`]s, acts on them, and if necessary, sends
-    /// [`Response`]s in `res_tx`.
+    /// Loops over incoming [`Task`]s, acts on them, and if necessary, routes
+    /// [`Response`]s to the channels they belong to. Every [`TICK_INTERVAL`]
+    /// in between, modules are given the chance to send responses of their
+    /// own.
+    ///
+    /// Stops once every pending task has been handled and there are no more
+    /// senders of tasks.
     #[instrument(skip(self))]
     pub async fn process_loop(&mut self) {
         debug!("starting");
 
+        let mut ticker = time::interval(TICK_INTERVAL);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
         loop {
-            match self.process().await {
-                Ok(()) => {}
-                Err(err) => error!(%err),
+            trace!("waiting for task message");
+
+            // Only waiting is raced against the ticker, so that a task that
+            // has been received is always handled to completion.
+            let result = tokio::select! {
+                received = self.task_rx.recv() => match received {
+                    Some((task, meta)) => self.process(task, meta).await,
+                    None => break,
+                },
+                _ = ticker.tick() => self.tick().await,
+            };
+
+            if let Err(err) = result {
+                error!(%err);
             }
         }
+
+        info!("stopping");
     }
 
-    /// Gets an incoming [`Task`], acts on it, and if necessary, sends
-    /// [`Response`]s in `res_tx`.
+    /// Acts on a [`Task`], and if necessary, routes [`Response`]s to the
+    /// channels they belong to.
     #[instrument(skip(self))]
-    async fn process(&mut self) -> Result<(), ProcessError> {
-        trace!("waiting for task message");
+    async fn process(&mut self, task: Task, meta: Metadata) -> Result<(), ProcessError> {
+        trace!("received task message");
 
-        let (task, meta) = self.task_rx.recv().await.ok_or(ProcessError::ReceiveTask)?;
+        let responses = match self.handle_task(task, meta.clone()).await {
+            Ok(responses) => responses,
+            Err(err) => self.error_response(err, meta).pipe(iter::once).collect(),
+        };
 
-        trace!("received task message");
+        for (response, meta) in responses {
+            self.send_response(response, meta).await?;
+        }
 
-        for (response, meta) in self.handle_task(task, meta).await? {
+        Ok(())
+    }
+
+    /// Route any responses modules want to send without being asked.
+    async fn tick(&mut self) -> Result<(), ProcessError> {
+        for (response, meta) in self.modules.tick().await {
             self.send_response(response, meta).await?;
         }
 
         Ok(())
     }
 
+    /// Turn an error from handling a task into a response explaining what went
+    /// wrong to the user who caused it.
+    ///
+    /// Errors that are the user's fault get a specific explanation. Anything
+    /// else gets a generic message with a correlation ID, which is logged
+    /// alongside the full error so the two can be matched up later.
+    #[instrument(skip(self))]
+    fn error_response(&self, err: ProcessError, meta: Metadata) -> (Response, Metadata) {
+        let message = match &err {
+            ProcessError::Module(ModuleError::User(message)) => {
+                warn!(?meta, %err, "user error");
+                format!("@{} {}", meta.sender, message)
+            }
+            _ => {
+                let correlation_id = format!("{:08x}", rand::random::<u32>());
+                error!(?meta, %err, %correlation_id, "failed to handle task");
+
+                format!(
+                    "@{} Sorry, something went wrong (error ID {})",
+                    meta.sender, correlation_id
+                )
+            }
+        };
+
+        Response::say(message).with_meta(meta)
+    }
+
     #[instrument(skip(self))]
     async fn handle_task(
         &mut self,
@@ -65,228 +120,107 @@ impl ProcessHandler {
         meta: Metadata,
     ) -> Result<Vec<(Response, Metadata)>, ProcessError> {
         let responses = match task {
-            Task::Command { command } => {
-                info!(?meta, ?command, "user-defined command task");
-
-                self.commands
-                    .get_command(&meta.channel, &command)?
-                    .tap_none(|| warn!(?meta, ?command, "command not found"))
-                    .map(|message| vec![Response::Say { message }])
-                    .unwrap_or_default()
+            Task::Module { module, task } => {
+                info!(?meta, module, ?task, "module task");
+
+                self.modules
+                    .handle(module, task, &meta)
+                    .await?
                     .into_iter()
                     .map(|ea| ea.with_cloned_meta(&meta))
                     .collect()
             }
-            Task::Implicit(ImplicitTask::Greet) => {
-                info!(?meta, "implicit greet task");
-
-                Response::Say {
-                    message: format!("uwu *nuzzles @{}*", meta.sender),
-                }
-                .with_meta(meta)
-                .pipe(iter::once)
-                .collect()
-            }
-            Task::BuiltIn(BuiltInCommand::AddCommand { trigger, response }) => {
-                info!(?meta, ?trigger, ?response, "add command task");
-
-                let already_exists = self
-                    .commands
-                    .get_command(&meta.channel, &trigger)?
-                    .is_some();
-
-                self.commands
-                    .set_command(&meta.channel, &trigger, &response)?;
-
-                let verb = if already_exists { "Updated" } else { "Added" };
-
-                Response::Say {
-                    message: format!("{} {}{}", verb, self.prefix, trigger),
-                }
-                .with_meta(meta)
-                .pipe(iter::once)
-                .collect()
-            }
             Task::Help(Help::General) => {
                 info!(?meta, "general help task");
 
-                Response::Say {
-                    message: formatdoc!(
-                        "
-                        @{sender} See oxbow.cacti.dev/commands for help
-                        ",
-                        sender = meta.sender,
-                    ),
-                }
+                Response::say(formatdoc!(
+                    "
+                    @{sender} See {help_url} for help
+                    ",
+                    sender = meta.sender,
+                    help_url = self.help_url,
+                ))
                 .with_meta(meta)
                 .pipe(iter::once)
                 .collect()
             }
-            Task::Help(Help::Quote) => {
-                info!(?meta, "quote help task");
+            Task::Help(Help::Command { name }) => {
+                info!(?meta, ?name, "command help task");
 
-                Response::Say {
-                    message: formatdoc!(
+                let message = if let Some(built_in) = self.modules.find_command(&name) {
+                    formatdoc!(
                         "
-                        @{sender} See oxbow.cacti.dev/commands#quotes for help with {prefix}quote
+                        @{sender} {usage} - {description}. See {help_url}#{anchor} for more
                         ",
                         sender = meta.sender,
-                        prefix = self.prefix,
-                    ),
-                }
-                .with_meta(meta)
-                .pipe(iter::once)
-                .collect()
-            }
-            Task::BuiltIn(BuiltInCommand::AddQuote {
-                username,
-                key,
-                text,
-            }) => {
-                info!(?meta, ?username, ?key, ?text, "add quote task");
-
-                let when = Utc::now();
-                let date_str = when.format("%d %b %Y");
-                let time_str = when.format("%H:%M");
-
-                if let Some(key) = key {
-                    self.quotes
-                        .add_quote_keyed(&meta.channel, &username, &key, &text, when)?;
-
-                    Response::Say {
-                        message: format!(
-                            "Quote #{key} added from @{username} on {date_str} at {time_str} UTC",
-                        ),
-                    }
-                    .with_meta(meta)
-                    .pipe(iter::once)
-                    .collect()
-                } else {
-                    self.quotes
-                        .add_quote_unkeyed(&meta.channel, &username, &text, when)?;
-
-                    Response::Say {
-                        message: format!(
-                            "Quote added from @{username} on {date_str} at {time_str} UTC",
-                        ),
-                    }
-                    .with_meta(meta)
-                    .pipe(iter::once)
-                    .collect()
-                }
-            }
-            Task::BuiltIn(BuiltInCommand::GetQuote { key }) => {
-                info!(?meta, ?key, "get quote by key task");
-
-                if let Some(quote) = self.quotes.get_quote_keyed(&meta.channel, &key)? {
-                    Response::Say {
-                        message: format!("{}", quote),
-                    }
-                    .with_meta(meta)
-                    .pipe(iter::once)
-                    .collect()
+                        usage = built_in.usage(self.prefix),
+                        description = built_in.description,
+                        help_url = self.help_url,
+                        anchor = built_in.anchor,
+                    )
+                } else if let Some(description) = self.modules.describe(&meta.channel, &name)? {
+                    format!("@{} {}", meta.sender, description)
                 } else {
-                    iter::empty().collect()
-                }
-            }
-            Task::BuiltIn(BuiltInCommand::RandomQuote) => {
-                info!(?meta, "get random quote task");
+                    format!(
+                        "@{} There's no {}{} command here",
+                        meta.sender, self.prefix, name
+                    )
+                };
 
-                if let Some(quote) = self.quotes.get_quote_random(&meta.channel)? {
-                    Response::Say {
-                        message: format!("{}", quote),
-                    }
+                Response::say(message)
                     .with_meta(meta)
                     .pipe(iter::once)
                     .collect()
-                } else {
-                    iter::empty().collect()
-                }
             }
-            Task::BuiltIn(BuiltInCommand::WordSearch) => {
-                info!(?meta, "word search task");
-
-                let word_search = self
-                    .word_searches
-                    .entry(meta.channel.to_string())
-                    .and_modify(|ws| ws.reset())
-                    .or_default();
-
-                Response::Say {
-                    message: format!("!wg {}", word_search.guess()),
-                }
-                .with_meta(meta)
-                .pipe(iter::once)
-                .collect()
-            }
-            Task::BuiltIn(BuiltInCommand::WordLower { word, distance }) => {
-                info!(?meta, ?word, "word lower task");
+            Task::Help(Help::ListCommands) => {
+                info!(?meta, "list commands task");
 
-                if let Some(word_search) = self.word_searches.get_mut(&*meta.channel) {
-                    word_search.set_lower(&word, distance);
-
-                    Response::Say {
-                        message: format!("!wg {}", word_search.guess()),
-                    }
-                    .with_meta(meta)
-                    .pipe(iter::once)
-                    .collect()
-                } else {
-                    Response::Say {
-                        message: format!(
-                            "No word search in progress! Start one with {}search",
-                            self.prefix
-                        ),
-                    }
-                    .with_meta(meta)
-                    .pipe(iter::once)
-                    .collect()
-                }
-            }
-            Task::BuiltIn(BuiltInCommand::WordUpper { word, distance }) => {
-                info!(?meta, ?word, "word upper task");
+                let built_ins = self
+                    .modules
+                    .available_to(&meta.channel, meta.role)
+                    .into_iter()
+                    .map(|built_in| format!("{}{}", self.prefix, built_in.name))
+                    .collect::<Vec<_>>();
 
-                if let Some(word_search) = self.word_searches.get_mut(&*meta.channel) {
-                    word_search.set_upper(&word, distance);
+                let custom = self
+                    .modules
+                    .list(&meta.channel)?
+                    .into_iter()
+                    .map(|name| format!("{}{}", self.prefix, name))
+                    .collect::<Vec<_>>();
 
-                    Response::Say {
-                        message: format!("!wg {}", word_search.guess()),
-                    }
-                    .with_meta(meta)
-                    .pipe(iter::once)
-                    .collect()
+                let message = if custom.is_empty() {
+                    format!("@{} Commands: {}", meta.sender, built_ins.join(" "))
                 } else {
-                    Response::Say {
-                        message: format!(
-                            "No word search in progress! Start one with {}search",
-                            self.prefix
-                        ),
-                    }
+                    format!(
+                        "@{} Commands: {} | Custom: {}",
+                        meta.sender,
+                        built_ins.join(" "),
+                        custom.join(" ")
+                    )
+                };
+
+                Response::say(message)
                     .with_meta(meta)
                     .pipe(iter::once)
                     .collect()
-                }
             }
-            Task::BuiltIn(BuiltInCommand::WordFound) => {
-                info!(?meta, "word found task");
-
-                if let Some(word_search) = self.word_searches.get_mut(&*meta.channel) {
-                    word_search.reset();
-
-                    Response::Say {
-                        message: "Word search stopped".to_owned(),
-                    }
-                    .with_meta(meta)
-                    .pipe(iter::once)
-                    .collect()
-                } else {
-                    Response::Say {
-                        message: "No word search in progress!".to_owned(),
-                    }
-                    .with_meta(meta)
-                    .pipe(iter::once)
-                    .collect()
-                }
+            Task::Help(Help::Usage { keyword, hint }) => {
+                info!(?meta, ?keyword, ?hint, "usage help task");
+
+                let usage = self
+                    .modules
+                    .find_command(&keyword)
+                    .map(|built_in| format!(" (usage: {})", built_in.usage(self.prefix)))
+                    .unwrap_or_default();
+
+                Response::say(format!(
+                    "@{} Usage error in {}{}: {}{}",
+                    meta.sender, self.prefix, keyword, hint, usage
+                ))
+                .with_meta(meta)
+                .pipe(iter::once)
+                .collect()
             }
         };
 
@@ -299,9 +233,8 @@ impl ProcessHandler {
     async fn send_response(&self, response: Response, meta: Metadata) -> Result<(), ProcessError> {
         debug!(?meta, ?response, "sending response");
 
-        let _ = self
-            .res_tx
-            .send(response.with_cloned_meta(&meta))
+        self.router
+            .route(response, meta.clone())
             .tap_err(|e| error!(?meta, error = ?e, "failed to send response message"))?;
 
         Ok(())
@@ -310,15 +243,9 @@ impl ProcessHandler {
 
 #[derive(Debug, Error)]
 enum ProcessError {
-    #[error("failed to receive task")]
-    ReceiveTask,
-
-    #[error("command error: {0}")]
-    CommandError(#[from] CommandsError),
-
-    #[error("quote error: {0}")]
-    QuoteError(#[from] QuotesError),
+    #[error("module error: {0}")]
+    Module(#[from] ModuleError),
 
     #[error("failed to send response: {0}")]
-    SendResponse(#[from] SendError<(Response, Metadata)>),
+    SendResponse(#[from] RouteError),
 }
@@ -1,40 +1,64 @@
-use std::iter;
+use std::{iter, time::Duration};
 
+use chrono::Utc;
 use tap::Pipe;
 use thiserror::Error;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use tracing::{debug, error, info, instrument, trace};
 use twitch_irc::message::ServerMessage;
 
 use crate::{
-    msg::{BuiltInCommand, Help, ImplicitTask, Metadata, Task, WithMeta},
-    parse::{
-        ast::{Command, Help as AstHelp, MetaCommand, PotentialUser, Quote, Search},
-        oxbow::CommandParser,
-    },
+    messagelog::MessageLogger,
+    module::{keyword, ModuleRegistry, UsageError},
+    msg::{cooldown::Cooldowns, Help, Metadata, Role, Task, WithMeta},
+    parse::{ast::Help as AstHelp, oxbow::HelpParser},
 };
 
+/// How long a user has to wait after being sent a usage hint before they can
+/// be sent another one in the same channel.
+pub const USAGE_HINT_COOLDOWN: Duration = Duration::from_secs(30);
+
 pub struct ReceiveHandler {
     pub(in crate::bot) msg_rx: mpsc::UnboundedReceiver<ServerMessage>,
     pub(in crate::bot) task_tx: mpsc::UnboundedSender<(Task, Metadata)>,
     pub(in crate::bot) prefix: char,
-    pub(in crate::bot) twitch_name: String,
-    pub(in crate::bot) parser: CommandParser,
+    pub(in crate::bot) owner: String,
+    pub(in crate::bot) parser: HelpParser,
+    pub(in crate::bot) modules: ModuleRegistry,
+    pub(in crate::bot) shutdown_rx: watch::Receiver<bool>,
+    /// How long each user in each channel has to wait before they can be sent
+    /// another usage hint.
+    pub(in crate::bot) usage_hints_sent: Cooldowns,
+    pub(in crate::bot) message_log: MessageLogger,
 }
 
 impl ReceiveHandler {
     /// Loops over incoming messages and if any are a recognised command, sends
     /// a [`Task`] in `task_tx` with the appropriate task to perform.
+    ///
+    /// Stops when shutdown is signalled, or when there are no more incoming
+    /// messages.
     #[instrument(skip(self))]
     pub async fn receive_loop(&mut self) {
         debug!("starting");
 
-        loop {
-            match self.receive().await {
-                Ok(()) => {}
-                Err(err) => error!(%err),
+        let mut shutdown_rx = self.shutdown_rx.clone();
+
+        while !*shutdown_rx.borrow() {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {}
+                res = self.receive() => match res {
+                    Ok(()) => {}
+                    Err(ReceiveError::ReceiveMessage) => {
+                        error!("incoming message channel closed");
+                        break;
+                    }
+                    Err(err) => error!(%err),
+                },
             }
         }
+
+        info!("stopping");
     }
 
     /// Gets an incoming message, and if it is a recognised command, sends
@@ -65,149 +89,37 @@ impl ReceiveHandler {
     ) -> Result<Vec<(Task, Metadata)>, ReceiveError> {
         let tasks = match message {
             ServerMessage::Privmsg(msg) => {
+                self.message_log.log(
+                    &msg.channel_login,
+                    &msg.sender.login,
+                    &msg.message_text,
+                    Utc::now(),
+                );
+
+                let role = if msg.sender.login == self.owner {
+                    Role::Owner
+                } else {
+                    Role::from_badges(msg.badges.iter().map(|badge| badge.name.as_str()))
+                };
+
                 let meta = Metadata {
                     id: msg.message_id.into(),
                     channel: msg.channel_login.into(),
                     sender: msg.sender.login.into(),
+                    role,
                 };
 
                 if let Some(potential_command) = msg.message_text.strip_prefix(self.prefix) {
-                    if let Ok(parsed) = self.parser.parse(potential_command) {
-                        match parsed {
-                            Command::Quote(Quote::Add {
-                                username,
-                                key,
-                                text,
-                            }) => {
-                                debug!(?meta, command = "add quote", "identified command");
-                                Task::BuiltIn(BuiltInCommand::AddQuote {
-                                    username,
-                                    key,
-                                    text,
-                                })
-                                .with_meta(meta)
-                                .pipe(iter::once)
-                                .collect()
-                            }
-                            Command::Quote(Quote::Get { key }) => {
-                                debug!(?meta, command = "get quote by key", "identified command");
-                                Task::BuiltIn(BuiltInCommand::GetQuote { key })
-                                    .with_meta(meta)
-                                    .pipe(iter::once)
-                                    .collect()
-                            }
-                            Command::Quote(Quote::Random) => {
-                                debug!(?meta, command = "get random quote", "identified command");
-                                Task::BuiltIn(BuiltInCommand::RandomQuote)
-                                    .with_meta(meta)
-                                    .pipe(iter::once)
-                                    .collect()
-                            }
-                            Command::Help(AstHelp::General) => {
-                                debug!(?meta, "identified general help request");
-                                Task::Help(Help::General)
-                                    .with_meta(meta)
-                                    .pipe(iter::once)
-                                    .collect()
-                            }
-                            Command::Help(AstHelp::Quote) => {
-                                debug!(
-                                    ?meta,
-                                    command = "quote",
-                                    "identified help request for command"
-                                );
-                                Task::Help(Help::Quote)
-                                    .with_meta(meta)
-                                    .pipe(iter::once)
-                                    .collect()
-                            }
-                            Command::Meta(MetaCommand { trigger, response }) => {
-                                debug!(?meta, command = "command", "identified command");
-                                Task::BuiltIn(BuiltInCommand::AddCommand { trigger, response })
-                                    .with_meta(meta)
-                                    .pipe(iter::once)
-                                    .collect()
-                            }
-                            Command::Search(Search::Search) => {
-                                debug!(?meta, command = "search", "identified command");
-                                if &*meta.sender == "nerosnm" {
-                                    Task::BuiltIn(BuiltInCommand::WordSearch)
-                                        .with_meta(meta)
-                                        .pipe(iter::once)
-                                        .collect()
-                                } else {
-                                    iter::empty().collect()
-                                }
-                            }
-                            Command::Search(Search::Lower { word, distance }) => {
-                                debug!(?meta, command = "lower", "identified command");
-
-                                if &*meta.sender == "nerosnm" {
-                                    Task::BuiltIn(BuiltInCommand::WordLower { word, distance })
-                                        .with_meta(meta)
-                                        .pipe(iter::once)
-                                        .collect()
-                                } else {
-                                    iter::empty().collect()
-                                }
-                            }
-                            Command::Search(Search::Upper { word, distance }) => {
-                                debug!(?meta, command = "upper", "identified command");
-
-                                if &*meta.sender == "nerosnm" {
-                                    Task::BuiltIn(BuiltInCommand::WordUpper { word, distance })
-                                        .with_meta(meta)
-                                        .pipe(iter::once)
-                                        .collect()
-                                } else {
-                                    iter::empty().collect()
-                                }
-                            }
-                            Command::Search(Search::Found) => {
-                                debug!(?meta, command = "found", "identified command");
-
-                                if &*meta.sender == "nerosnm" {
-                                    Task::BuiltIn(BuiltInCommand::WordFound)
-                                        .with_meta(meta)
-                                        .pipe(iter::once)
-                                        .collect()
-                                } else {
-                                    iter::empty().collect()
-                                }
-                            }
-                            Command::PotentialUser(PotentialUser { trigger }) => {
-                                Task::Command { command: trigger }
-                                    .with_meta(meta)
-                                    .pipe(iter::once)
-                                    .collect()
-                            }
-                        }
-                    } else {
-                        iter::empty().collect()
-                    }
-                } else if msg
-                    .message_text
-                    .to_lowercase()
-                    .split_whitespace()
-                    .any(|ea| ea == "hi")
-                    && msg
-                        .message_text
-                        .to_lowercase()
-                        .contains(&format!("@{}", self.twitch_name))
-                {
-                    trace!(
-                        ?meta,
-                        implicit_command = "greeting",
-                        "implicit command identified"
-                    );
-                    info!(?meta, ?msg.message_text);
-
-                    Task::Implicit(ImplicitTask::Greet)
-                        .with_meta(meta)
-                        .pipe(iter::once)
-                        .collect()
+                    self.handle_command(potential_command, meta)
                 } else {
-                    iter::empty().collect()
+                    self.modules
+                        .observe(&msg.message_text, &meta)
+                        .into_iter()
+                        .map(|(module, task)| {
+                            trace!(?meta, module, ?task, "implicit task identified");
+                            Task::Module { module, task }.with_cloned_meta(&meta)
+                        })
+                        .collect()
                 }
             }
             ServerMessage::Notice(notice)
@@ -229,6 +141,54 @@ impl ReceiveHandler {
         Ok(tasks)
     }
 
+    /// Turn the text of a command (without the prefix) into tasks, either by
+    /// handling it as a request for help, or by passing it to the module that
+    /// claims it.
+    #[instrument(skip(self))]
+    fn handle_command(&mut self, input: &str, meta: Metadata) -> Vec<(Task, Metadata)> {
+        let keyword = match keyword(input) {
+            Some(keyword) => keyword,
+            None => return vec![],
+        };
+
+        let parsed = if keyword == "help" || keyword == "commands" {
+            self.parser
+                .parse(input)
+                .map(|help| {
+                    debug!(?meta, command = %keyword, "identified command");
+
+                    let help = match help {
+                        AstHelp::General => Help::General,
+                        AstHelp::Command(name) => Help::Command { name },
+                        AstHelp::ListCommands => Help::ListCommands,
+                    };
+
+                    Some(Task::Help(help))
+                })
+                .map_err(|err| UsageError::new(&keyword, input, &err))
+        } else {
+            self.modules.parse(input, &meta).map(|parsed| {
+                parsed.map(|(module, task)| {
+                    debug!(?meta, module, command = %keyword, "identified command");
+                    Task::Module { module, task }
+                })
+            })
+        };
+
+        match parsed {
+            Ok(task) => task.map(|task| task.with_meta(meta)).into_iter().collect(),
+            Err(UsageError { keyword, hint }) if self.usage_hints_sent.start(&meta) => {
+                debug!(?meta, ?keyword, ?hint, "built-in command failed to parse");
+
+                Task::Help(Help::Usage { keyword, hint })
+                    .with_meta(meta)
+                    .pipe(iter::once)
+                    .collect()
+            }
+            Err(_) => vec![],
+        }
+    }
+
     #[instrument(skip(self))]
     async fn send_task(&mut self, task: Task, meta: Metadata) -> Result<(), ReceiveError> {
         let _ = self.task_tx.send(task.with_cloned_meta(&meta))?;
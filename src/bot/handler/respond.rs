@@ -1,36 +1,30 @@
 use std::time::Duration;
 
 use thiserror::Error;
-use tokio::sync::broadcast;
+use tokio::sync::mpsc;
 use tracing::{debug, error, info, instrument, trace};
-use twitch_irc::{login::LoginCredentials, Transport, TwitchIRCClient};
 
-use crate::msg::{Metadata, Response};
+use crate::{
+    chat::ChatClient,
+    msg::{Metadata, Response},
+};
 
-pub struct RespondHandler<T, L>
-where
-    T: Transport,
-    L: LoginCredentials,
-{
-    pub(in crate::bot) res_rx: broadcast::Receiver<(Response, Metadata)>,
-    pub(in crate::bot) client: TwitchIRCClient<T, L>,
+pub struct RespondHandler<C: ChatClient> {
+    pub(in crate::bot) res_rx: mpsc::Receiver<(Response, Metadata)>,
+    pub(in crate::bot) client: C,
     pub(in crate::bot) channel: String,
 }
 
-impl<T, L> RespondHandler<T, L>
-where
-    T: Transport,
-    L: LoginCredentials,
-{
+impl<C: ChatClient> RespondHandler<C> {
     /// Loops over incoming [`Response`]s and acts on them, such as by sending
-    /// messages in a channel.
+    /// messages in a channel, until the channel is removed from the router.
     #[instrument(skip(self), fields(channel = %self.channel))]
     pub async fn respond_loop(&mut self) {
         debug!("starting");
 
         self.client.join(self.channel.clone());
 
-        while self.client.get_channel_status(self.channel.clone()).await != (true, true) {
+        while !self.client.is_joined(self.channel.clone()).await {
             tokio::time::sleep(Duration::from_millis(100)).await;
         }
 
@@ -39,22 +33,29 @@ where
         loop {
             match self.respond().await {
                 Ok(()) => {}
+                Err(RespondError::ReceiveResponse) => break,
                 Err(err) => error!(%err),
             }
         }
+
+        self.client.part(self.channel.clone());
+
+        info!("left channel");
     }
 
     /// Gets an incoming [`Response`] and acts on it, such as by sending a
     /// message in a channel.
     #[instrument(skip(self), fields(channel = %self.channel))]
-    async fn respond(&mut self) -> Result<(), RespondError<T, L>> {
+    async fn respond(&mut self) -> Result<(), RespondError<C>> {
         trace!("waiting for response message");
 
-        let (res, meta) = self.res_rx.recv().await?;
+        let (res, meta) = self
+            .res_rx
+            .recv()
+            .await
+            .ok_or(RespondError::ReceiveResponse)?;
 
-        if *meta.channel == self.channel {
-            self.send_response(res, meta).await?;
-        }
+        self.send_response(res, meta).await?;
 
         Ok(())
     }
@@ -64,12 +65,19 @@ where
         &mut self,
         res: Response,
         meta: Metadata,
-    ) -> Result<(), RespondError<T, L>> {
+    ) -> Result<(), RespondError<C>> {
         match res {
-            Response::Say { message } => {
-                info!(?meta, ?message, "sending response");
-
-                self.client.say(self.channel.clone(), message).await?;
+            Response::Say { .. } => {
+                // Parts are sent one after another so that they arrive in
+                // order.
+                for message in res.parts() {
+                    info!(?meta, ?message, "sending response");
+
+                    self.client
+                        .say(self.channel.clone(), message)
+                        .await
+                        .map_err(RespondError::Say)?;
+                }
             }
         }
 
@@ -78,14 +86,10 @@ where
 }
 
 #[derive(Debug, Error)]
-enum RespondError<T, L>
-where
-    T: Transport,
-    L: LoginCredentials,
-{
-    #[error("failed to receive response: {0}")]
-    ReceiveResponse(#[from] broadcast::error::RecvError),
+enum RespondError<C: ChatClient> {
+    #[error("failed to receive response")]
+    ReceiveResponse,
 
     #[error("failed to send response message: {0}")]
-    Say(#[from] twitch_irc::Error<T, L>),
+    Say(#[source] C::Error),
 }
@@ -0,0 +1,264 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use thiserror::Error;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{debug, instrument, warn};
+
+use crate::msg::{Metadata, Response};
+
+/// The default number of responses that can be waiting to be sent in a single
+/// channel before any more are dropped.
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 16;
+
+/// Routes each [`Response`] only to the response loop of the channel it belongs
+/// to.
+///
+/// Every channel gets its own bounded buffer, so a busy or slow channel can't
+/// cause responses for any other channel to be lost. Channels can be added and
+/// removed while the bot is running.
+#[derive(Debug, Clone)]
+pub struct ResponseRouter {
+    capacity: usize,
+    routes: Arc<RwLock<HashMap<String, Route>>>,
+    unrouted: Arc<AtomicU64>,
+}
+
+#[derive(Debug)]
+struct Route {
+    tx: mpsc::Sender<(Response, Metadata)>,
+    metrics: Arc<ChannelMetrics>,
+}
+
+impl ResponseRouter {
+    /// Create a `ResponseRouter` that buffers up to `capacity` responses per
+    /// channel.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            routes: Arc::new(RwLock::new(HashMap::new())),
+            unrouted: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Start routing responses for `channel`, returning the receiver its
+    /// response loop should read from.
+    ///
+    /// If `channel` was already being routed, the old receiver is closed and
+    /// replaced.
+    #[instrument(skip(self))]
+    pub fn add_channel(&self, channel: &str) -> mpsc::Receiver<(Response, Metadata)> {
+        debug!("adding route");
+
+        let (tx, rx) = mpsc::channel(self.capacity);
+
+        self.routes.write().expect("router lock poisoned").insert(
+            channel.to_owned(),
+            Route {
+                tx,
+                metrics: Arc::default(),
+            },
+        );
+
+        rx
+    }
+
+    /// Stop routing responses for `channel`.
+    ///
+    /// The receiver for `channel` will be closed once any buffered responses
+    /// have been read from it. Returns `false` if `channel` wasn't being
+    /// routed.
+    #[instrument(skip(self))]
+    pub fn remove_channel(&self, channel: &str) -> bool {
+        debug!("removing route");
+
+        self.routes
+            .write()
+            .expect("router lock poisoned")
+            .remove(channel)
+            .is_some()
+    }
+
+    /// The channels responses are currently being routed to.
+    pub fn channels(&self) -> Vec<String> {
+        self.routes
+            .read()
+            .expect("router lock poisoned")
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /// Deliver `response` to the response loop for the channel in `meta`.
+    ///
+    /// This never waits: if the channel's buffer is full, the response is
+    /// dropped and counted in that channel's [`ChannelMetrics`].
+    #[instrument(skip(self))]
+    pub fn route(&self, response: Response, meta: Metadata) -> Result<(), RouteError> {
+        let routes = self.routes.read().expect("router lock poisoned");
+
+        let route = match routes.get(&*meta.channel) {
+            Some(route) => route,
+            None => {
+                self.unrouted.fetch_add(1, Ordering::Relaxed);
+                return Err(RouteError::UnknownChannel(meta.channel.to_string()));
+            }
+        };
+
+        match route.tx.try_send((response, meta)) {
+            Ok(()) => {
+                route.metrics.delivered.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(TrySendError::Full((_, meta))) => {
+                let dropped = route.metrics.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                warn!(?meta, dropped, "response buffer full, dropping response");
+
+                Err(RouteError::Overflow(meta.channel.to_string()))
+            }
+            Err(TrySendError::Closed((_, meta))) => {
+                self.unrouted.fetch_add(1, Ordering::Relaxed);
+                Err(RouteError::Closed(meta.channel.to_string()))
+            }
+        }
+    }
+
+    /// A snapshot of the delivery counters for `channel`, if it's being routed.
+    pub fn metrics(&self, channel: &str) -> Option<ChannelMetricsSnapshot> {
+        self.routes
+            .read()
+            .expect("router lock poisoned")
+            .get(channel)
+            .map(|route| route.metrics.snapshot())
+    }
+
+    /// The number of responses that couldn't be routed because their channel
+    /// wasn't known, or its response loop had stopped.
+    pub fn unrouted(&self) -> u64 {
+        self.unrouted.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for ResponseRouter {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHANNEL_CAPACITY)
+    }
+}
+
+/// Counters of what has happened to the responses routed to a channel.
+#[derive(Debug, Default)]
+struct ChannelMetrics {
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl ChannelMetrics {
+    fn snapshot(&self) -> ChannelMetricsSnapshot {
+        ChannelMetricsSnapshot {
+            delivered: self.delivered.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The values of a channel's delivery counters at a point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelMetricsSnapshot {
+    /// Responses placed in the channel's buffer.
+    pub delivered: u64,
+    /// Responses dropped because the channel's buffer was full.
+    pub dropped: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum RouteError {
+    #[error("no route for channel {0}")]
+    UnknownChannel(String),
+
+    #[error("response buffer for channel {0} is full")]
+    Overflow(String),
+
+    #[error("response loop for channel {0} has stopped")]
+    Closed(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::Role;
+
+    fn meta(channel: &str) -> Metadata {
+        Metadata {
+            id: "id".into(),
+            channel: channel.into(),
+            sender: "sender".into(),
+            role: Role::Everyone,
+        }
+    }
+
+    fn say(message: &str) -> Response {
+        Response::say(message.to_owned())
+    }
+
+    #[tokio::test]
+    async fn routes_only_to_own_channel() {
+        let router = ResponseRouter::new(4);
+        let mut rx_a = router.add_channel("a");
+        let mut rx_b = router.add_channel("b");
+
+        router
+            .route(say("for a"), meta("a"))
+            .expect("routing to a known channel should succeed");
+
+        let (_, received) = rx_a.recv().await.expect("a should receive a response");
+        assert_eq!(&*received.channel, "a");
+
+        assert!(
+            rx_b.try_recv().is_err(),
+            "b should not receive responses for a"
+        );
+    }
+
+    #[test]
+    fn counts_overflow() {
+        let router = ResponseRouter::new(1);
+        let _rx = router.add_channel("a");
+
+        router
+            .route(say("first"), meta("a"))
+            .expect("the first response should fit in the buffer");
+
+        assert!(matches!(
+            router.route(say("second"), meta("a")),
+            Err(RouteError::Overflow(_))
+        ));
+
+        assert_eq!(
+            router.metrics("a"),
+            Some(ChannelMetricsSnapshot {
+                delivered: 1,
+                dropped: 1,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn removed_channel_closes() {
+        let router = ResponseRouter::new(4);
+        let mut rx = router.add_channel("a");
+
+        assert!(router.remove_channel("a"));
+        assert!(rx.recv().await.is_none(), "receiver should be closed");
+
+        assert!(matches!(
+            router.route(say("gone"), meta("a")),
+            Err(RouteError::UnknownChannel(_))
+        ));
+        assert_eq!(router.unrouted(), 1);
+    }
+}
@@ -0,0 +1,175 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use thiserror::Error;
+use tokio::{sync::watch, time::Instant};
+use tracing::{error, info, instrument, warn};
+
+/// How a [`Supervisor`] should react when a loop it's supervising crashes.
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// The number of consecutive crashes after which the loop is given up on.
+    pub max_failures: u32,
+    /// How long to wait before the first restart.
+    pub initial_backoff: Duration,
+    /// The longest to ever wait before a restart; the wait doubles after every
+    /// consecutive crash until it reaches this.
+    pub max_backoff: Duration,
+    /// A loop that has been running for at least this long before crashing is
+    /// considered healthy again, so its crash count and backoff are reset.
+    pub healthy_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_failures: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            healthy_after: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Runs the bot's loops, restarting them if they crash and shutting the whole
+/// bot down if one keeps crashing.
+#[derive(Debug, Clone)]
+pub struct Supervisor {
+    policy: RestartPolicy,
+    shutdown_tx: Arc<watch::Sender<bool>>,
+}
+
+impl Supervisor {
+    /// Create a `Supervisor` that restarts loops according to `policy`, and
+    /// signals `shutdown_tx` if it has to give up on one.
+    pub fn new(policy: RestartPolicy, shutdown_tx: Arc<watch::Sender<bool>>) -> Self {
+        Self {
+            policy,
+            shutdown_tx,
+        }
+    }
+
+    /// Run the future returned by `start` in its own task until it finishes,
+    /// calling `start` again to restart it every time it panics.
+    ///
+    /// Returns `Ok(())` once the loop finishes normally, or an error if it
+    /// crashed more than [`RestartPolicy::max_failures`] times in a row.
+    #[instrument(skip(self, start))]
+    pub async fn supervise<F, Fut>(
+        &self,
+        name: &'static str,
+        mut start: F,
+    ) -> Result<(), SupervisorError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut failures = 0;
+        let mut backoff = self.policy.initial_backoff;
+
+        loop {
+            let started = Instant::now();
+
+            match tokio::spawn(start()).await {
+                Ok(()) => {
+                    info!("loop finished");
+                    return Ok(());
+                }
+                Err(err) if err.is_cancelled() => {
+                    warn!("loop was cancelled");
+                    return Ok(());
+                }
+                Err(err) => {
+                    if started.elapsed() >= self.policy.healthy_after {
+                        failures = 0;
+                        backoff = self.policy.initial_backoff;
+                    }
+
+                    failures += 1;
+
+                    if failures > self.policy.max_failures {
+                        error!(%err, failures, "loop keeps crashing, giving up");
+
+                        let _ = self.shutdown_tx.send(true);
+
+                        return Err(SupervisorError::TooManyFailures { name, failures });
+                    }
+
+                    error!(%err, failures, ?backoff, "loop crashed, restarting");
+
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.policy.max_backoff);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SupervisorError {
+    #[error("{name} loop crashed {failures} times in a row")]
+    TooManyFailures { name: &'static str, failures: u32 },
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    fn supervisor(max_failures: u32) -> (Supervisor, watch::Receiver<bool>) {
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        let policy = RestartPolicy {
+            max_failures,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+            healthy_after: Duration::from_secs(60),
+        };
+
+        (Supervisor::new(policy, Arc::new(shutdown_tx)), shutdown_rx)
+    }
+
+    #[tokio::test]
+    async fn restarts_crashed_loop() {
+        let (supervisor, shutdown_rx) = supervisor(3);
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let result = supervisor
+            .supervise("test", || {
+                let runs = runs.clone();
+                async move {
+                    if runs.fetch_add(1, Ordering::SeqCst) < 2 {
+                        panic!("crashing on purpose");
+                    }
+                }
+            })
+            .await;
+
+        assert!(result.is_ok(), "loop should eventually finish normally");
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert!(!*shutdown_rx.borrow(), "shutdown should not be signalled");
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_repeated_failures() {
+        let (supervisor, shutdown_rx) = supervisor(2);
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let result = supervisor
+            .supervise("test", || {
+                let runs = runs.clone();
+                async move {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                    panic!("crashing on purpose");
+                }
+            })
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(SupervisorError::TooManyFailures { failures: 3, .. })
+        ));
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert!(*shutdown_rx.borrow(), "shutdown should be signalled");
+    }
+}
@@ -0,0 +1,100 @@
+//! The connection to chat that the bot receives messages from and sends
+//! responses to.
+
+pub mod console;
+pub mod fake;
+
+use std::{convert::TryFrom, error::Error};
+
+use async_trait::async_trait;
+use thiserror::Error;
+use twitch_irc::{
+    login::LoginCredentials,
+    message::{IRCMessage, IRCParseError, ServerMessage, ServerMessageParseError},
+    Transport, TwitchIRCClient,
+};
+
+/// A client connected to chat, which can join channels and send messages in
+/// them.
+///
+/// Incoming messages aren't read through this trait. Instead, each client is
+/// created alongside a receiver of the messages it gets, in the same way as
+/// [`TwitchIRCClient::new`].
+#[async_trait]
+pub trait ChatClient: Clone + Send + Sync + 'static {
+    /// The error returned when a message can't be sent.
+    type Error: Error + Send + Sync + 'static;
+
+    /// Start joining `channel`.
+    fn join(&self, channel: String);
+
+    /// Leave `channel`.
+    fn part(&self, channel: String);
+
+    /// Check whether `channel` has been joined successfully.
+    async fn is_joined(&self, channel: String) -> bool;
+
+    /// Send `message` in `channel`.
+    async fn say(&self, channel: String, message: String) -> Result<(), Self::Error>;
+}
+
+#[async_trait]
+impl<T, L> ChatClient for TwitchIRCClient<T, L>
+where
+    T: Transport,
+    L: LoginCredentials,
+{
+    type Error = twitch_irc::Error<T, L>;
+
+    fn join(&self, channel: String) {
+        TwitchIRCClient::join(self, channel);
+    }
+
+    fn part(&self, channel: String) {
+        TwitchIRCClient::part(self, channel);
+    }
+
+    async fn is_joined(&self, channel: String) -> bool {
+        self.get_channel_status(channel).await == (true, true)
+    }
+
+    async fn say(&self, channel: String, message: String) -> Result<(), Self::Error> {
+        TwitchIRCClient::say(self, channel, message).await
+    }
+}
+
+/// Parse a raw IRC line, such as a `PRIVMSG` with tags, into a message from
+/// the server.
+pub fn parse_line(line: &str) -> Result<ServerMessage, ParseLineError> {
+    Ok(ServerMessage::try_from(IRCMessage::parse(line)?)?)
+}
+
+/// Build a message sent in `channel` by `sender`, who has the given `badges`
+/// (such as `moderator/1`), as if it had come from the server.
+pub fn privmsg(
+    channel: &str,
+    sender: &str,
+    badges: &[&str],
+    id: &str,
+    text: &str,
+) -> Result<ServerMessage, ParseLineError> {
+    parse_line(&format!(
+        "@badge-info=;badges={badges};color=;display-name={sender};emotes=;\
+         id={id};room-id=1;tmi-sent-ts=0;user-id=2 \
+         :{sender}!{sender}@{sender}.tmi.twitch.tv PRIVMSG #{channel} :{text}",
+        badges = badges.join(","),
+        sender = sender,
+        id = id,
+        channel = channel,
+        text = text,
+    ))
+}
+
+#[derive(Debug, Error)]
+pub enum ParseLineError {
+    #[error("failed to parse IRC message: {0}")]
+    Irc(#[from] IRCParseError),
+
+    #[error("not a valid server message: {0}")]
+    ServerMessage(#[from] ServerMessageParseError),
+}
@@ -0,0 +1,99 @@
+//! A chat connection that reads messages from stdin and prints responses to
+//! stdout, for trying the bot out locally without connecting to Twitch.
+
+use std::{io, sync::Arc};
+
+use async_trait::async_trait;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    sync::mpsc,
+};
+use tracing::{debug, error, instrument};
+use twitch_irc::message::ServerMessage;
+
+use crate::chat::{self, ChatClient};
+
+/// Start reading lines from stdin as messages sent by `sender` in `channel`,
+/// returning the receiver of those messages and a [`ConsoleChat`] that prints
+/// what the bot says as `twitch_name`.
+///
+/// The receiver is closed once stdin reaches the end of its input.
+pub fn connect(
+    channel: &str,
+    sender: &str,
+    twitch_name: &str,
+) -> (mpsc::UnboundedReceiver<ServerMessage>, ConsoleChat) {
+    let (msg_tx, msg_rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(read_stdin(channel.to_owned(), sender.to_owned(), msg_tx));
+
+    let chat = ConsoleChat {
+        twitch_name: twitch_name.into(),
+    };
+
+    (msg_rx, chat)
+}
+
+#[instrument(skip(msg_tx))]
+async fn read_stdin(channel: String, sender: String, msg_tx: mpsc::UnboundedSender<ServerMessage>) {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut next_id = 0u64;
+
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(err) => {
+                error!(%err, "failed to read from stdin");
+                break;
+            }
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let id = format!("console-{}", next_id);
+        next_id += 1;
+
+        match chat::privmsg(&channel, &sender, &[], &id, &line) {
+            Ok(message) => {
+                if msg_tx.send(message).is_err() {
+                    break;
+                }
+            }
+            Err(err) => error!(%err, ?line, "failed to build message"),
+        }
+    }
+
+    debug!("end of input");
+}
+
+/// The bot's side of a console chat connection. Every channel is joined
+/// immediately, and everything said is printed to stdout.
+#[derive(Debug, Clone)]
+pub struct ConsoleChat {
+    twitch_name: Arc<str>,
+}
+
+#[async_trait]
+impl ChatClient for ConsoleChat {
+    type Error = io::Error;
+
+    fn join(&self, _channel: String) {}
+
+    fn part(&self, _channel: String) {}
+
+    async fn is_joined(&self, _channel: String) -> bool {
+        true
+    }
+
+    async fn say(&self, _channel: String, message: String) -> Result<(), io::Error> {
+        let mut stdout = tokio::io::stdout();
+
+        stdout
+            .write_all(format!("{}: {}\n", self.twitch_name, message.trim_end()).as_bytes())
+            .await?;
+        stdout.flush().await
+    }
+}
@@ -0,0 +1,166 @@
+//! An in-process chat connection, for running the bot without connecting to
+//! Twitch.
+
+use std::{
+    collections::HashSet,
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use async_trait::async_trait;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use twitch_irc::message::ServerMessage;
+
+use crate::chat::{self, ChatClient, ParseLineError};
+
+/// Create a connected [`FakeChat`] and [`FakeServer`], along with the receiver
+/// of incoming messages that the bot should read from.
+pub fn connect() -> (mpsc::UnboundedReceiver<ServerMessage>, FakeChat, FakeServer) {
+    let (msg_tx, msg_rx) = mpsc::unbounded_channel();
+    let (said_tx, said_rx) = mpsc::unbounded_channel();
+
+    let chat = FakeChat {
+        said_tx,
+        joined: Arc::default(),
+    };
+
+    let server = FakeServer {
+        msg_tx,
+        said_rx,
+        joined: chat.joined.clone(),
+        next_id: AtomicU64::new(0),
+    };
+
+    (msg_rx, chat, server)
+}
+
+/// The bot's side of a fake chat connection.
+///
+/// Every channel is joined as soon as it's asked for, and everything said is
+/// passed to the [`FakeServer`].
+#[derive(Debug, Clone)]
+pub struct FakeChat {
+    said_tx: mpsc::UnboundedSender<Said>,
+    joined: Arc<Mutex<HashSet<String>>>,
+}
+
+#[async_trait]
+impl ChatClient for FakeChat {
+    type Error = Infallible;
+
+    fn join(&self, channel: String) {
+        self.joined
+            .lock()
+            .expect("joined lock poisoned")
+            .insert(channel);
+    }
+
+    fn part(&self, channel: String) {
+        self.joined
+            .lock()
+            .expect("joined lock poisoned")
+            .remove(&channel);
+    }
+
+    async fn is_joined(&self, channel: String) -> bool {
+        self.joined
+            .lock()
+            .expect("joined lock poisoned")
+            .contains(&channel)
+    }
+
+    async fn say(&self, channel: String, message: String) -> Result<(), Infallible> {
+        // If the server has gone away, there's nobody left to hear it.
+        let _ = self.said_tx.send(Said { channel, message });
+
+        Ok(())
+    }
+}
+
+/// The chat server's side of a fake chat connection, which can send messages
+/// to the bot and see what it says.
+///
+/// Dropping the `FakeServer` disconnects the bot, which causes it to stop once
+/// it has finished responding to everything it has received.
+#[derive(Debug)]
+pub struct FakeServer {
+    msg_tx: mpsc::UnboundedSender<ServerMessage>,
+    said_rx: mpsc::UnboundedReceiver<Said>,
+    joined: Arc<Mutex<HashSet<String>>>,
+    next_id: AtomicU64,
+}
+
+impl FakeServer {
+    /// Send a raw IRC line, such as a `PRIVMSG` with tags, to the bot.
+    pub fn send_line(&self, line: &str) -> Result<(), FakeChatError> {
+        let message = chat::parse_line(line)?;
+
+        self.msg_tx
+            .send(message)
+            .map_err(|_| FakeChatError::Disconnected)
+    }
+
+    /// Send a message in `channel` from `sender`, who has the given `badges`
+    /// (such as `moderator/1`), to the bot.
+    pub fn privmsg(
+        &self,
+        channel: &str,
+        sender: &str,
+        badges: &[&str],
+        text: &str,
+    ) -> Result<(), FakeChatError> {
+        let id = format!("fake-{}", self.next_id.fetch_add(1, Ordering::Relaxed));
+        let message = chat::privmsg(channel, sender, badges, &id, text)?;
+
+        self.msg_tx
+            .send(message)
+            .map_err(|_| FakeChatError::Disconnected)
+    }
+
+    /// Wait for the bot to say something, returning `None` if it has
+    /// disconnected.
+    pub async fn said(&mut self) -> Option<Said> {
+        self.said_rx.recv().await
+    }
+
+    /// Whatever the bot has said that hasn't been read yet, without waiting.
+    pub fn said_so_far(&mut self) -> Vec<Said> {
+        let mut said = vec![];
+
+        while let Ok(ea) = self.said_rx.try_recv() {
+            said.push(ea);
+        }
+
+        said
+    }
+
+    /// Check whether the bot has joined `channel`.
+    pub fn has_joined(&self, channel: &str) -> bool {
+        self.joined
+            .lock()
+            .expect("joined lock poisoned")
+            .contains(channel)
+    }
+}
+
+/// A message the bot said in a channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Said {
+    /// The channel the message was sent in.
+    pub channel: String,
+    /// The text of the message.
+    pub message: String,
+}
+
+#[derive(Debug, Error)]
+pub enum FakeChatError {
+    #[error("{0}")]
+    Parse(#[from] ParseLineError),
+
+    #[error("the bot has disconnected")]
+    Disconnected,
+}
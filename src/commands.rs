@@ -1,6 +1,8 @@
+use std::ops::Range;
+
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::params;
+use rusqlite::{params, OptionalExtension};
 use thiserror::Error;
 
 /// Storage of custom commands in an SQLite3 database.
@@ -15,6 +17,8 @@ impl CommandsStore {
         Self { conn_pool }
     }
 
+    /// Set the response to `trigger` in `channel`, replacing any existing
+    /// response. Triggers are case-insensitive.
     pub fn set_command(
         &self,
         channel: &str,
@@ -22,6 +26,7 @@ impl CommandsStore {
         response: &str,
     ) -> Result<(), CommandsError> {
         let conn = self.conn_pool.get()?;
+        let trigger = trigger.to_lowercase();
 
         conn.execute(
             r#"
@@ -36,12 +41,15 @@ impl CommandsStore {
         Ok(())
     }
 
+    /// Get the response to `trigger` in `channel`, if there is one. Triggers
+    /// are case-insensitive.
     pub fn get_command(
         &self,
         channel: &str,
         trigger: &str,
     ) -> Result<Option<String>, CommandsError> {
         let conn = self.conn_pool.get()?;
+        let trigger = trigger.to_lowercase();
 
         let mut stmt = conn.prepare(
             r#"
@@ -60,6 +68,122 @@ impl CommandsStore {
             Ok(None)
         }
     }
+
+    /// Set the number of points it costs to run `trigger` in `channel`,
+    /// returning `false` if there's no such command.
+    pub fn set_cost(&self, channel: &str, trigger: &str, cost: u32) -> Result<bool, CommandsError> {
+        let conn = self.conn_pool.get()?;
+        let trigger = trigger.to_lowercase();
+
+        let updated = conn.execute(
+            r#"
+            UPDATE commands
+            SET cost = ?3
+            WHERE channel = ?1 AND trigger = ?2;
+            "#,
+            params![channel, trigger, cost],
+        )?;
+
+        Ok(updated > 0)
+    }
+
+    /// Get the number of points it costs to run `trigger` in `channel`, which
+    /// is zero if there's no such command.
+    pub fn get_cost(&self, channel: &str, trigger: &str) -> Result<u32, CommandsError> {
+        let conn = self.conn_pool.get()?;
+        let trigger = trigger.to_lowercase();
+
+        let cost = conn
+            .query_row(
+                r#"
+                SELECT cost
+                FROM commands
+                WHERE channel = ?1 AND trigger = ?2;
+                "#,
+                params![channel, trigger],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(cost.unwrap_or(0))
+    }
+
+    /// List the triggers of every custom command in `channel`, in alphabetical
+    /// order.
+    pub fn list_triggers(&self, channel: &str) -> Result<Vec<String>, CommandsError> {
+        let conn = self.conn_pool.get()?;
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT trigger
+            FROM commands
+            WHERE channel = ?1
+            ORDER BY trigger;
+            "#,
+        )?;
+
+        let triggers = stmt
+            .query_map(params![channel], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(triggers)
+    }
+}
+
+/// The number of arguments a custom command's `response` needs, which is the
+/// highest `{n}` placeholder it contains.
+pub fn required_args(response: &str) -> usize {
+    placeholders(response)
+        .filter_map(|(_, name)| name.parse::<usize>().ok())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Fill in the placeholders in a custom command's `response`.
+///
+/// `{sender}` is replaced with the name of the user who ran the command,
+/// `{args}` with all of the arguments as they were typed, and `{1}`, `{2}` and
+/// so on with individual arguments. Any other text in braces is left alone.
+pub fn render_response(response: &str, sender: &str, args: &[String], raw_args: &str) -> String {
+    let mut rendered = String::with_capacity(response.len());
+    let mut last = 0;
+
+    for (range, name) in placeholders(response) {
+        let value = match name {
+            "sender" => Some(sender),
+            "args" => Some(raw_args),
+            n => n
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|i| args.get(i))
+                .map(String::as_str),
+        };
+
+        if let Some(value) = value {
+            rendered.push_str(&response[last..range.start]);
+            rendered.push_str(value);
+            last = range.end;
+        }
+    }
+
+    rendered.push_str(&response[last..]);
+    rendered
+}
+
+/// Find each `{name}` in `response`, returning the byte range of the whole
+/// placeholder along with the name inside it.
+fn placeholders(response: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+    response.match_indices('{').filter_map(move |(start, _)| {
+        let len = response[start..].find('}')?;
+        let name = &response[(start + 1)..(start + len)];
+
+        if name.is_empty() || name.contains('{') {
+            None
+        } else {
+            Some((start..(start + len + 1), name))
+        }
+    })
 }
 
 #[derive(Debug, Error)]
@@ -123,6 +247,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn render_arguments() {
+        let response = "{sender} gives {1} a hug! ({args})";
+        let args = vec!["@friend".to_owned(), "tightly".to_owned()];
+
+        assert_eq!(required_args(response), 1);
+        assert_eq!(
+            render_response(response, "nerosnm", &args, "@friend tightly"),
+            "nerosnm gives @friend a hug! (@friend tightly)"
+        );
+    }
+
+    #[test]
+    fn render_leaves_unknown_placeholders() {
+        let response = "{nothing} to see {2} here {}";
+
+        assert_eq!(required_args(response), 2);
+        assert_eq!(
+            render_response(response, "nerosnm", &[], ""),
+            "{nothing} to see {2} here {}"
+        );
+    }
+
     #[test]
     fn update_command() {
         let (_db_dir, commands) = storage();
@@ -149,4 +296,60 @@ mod tests {
             "response should have been updated"
         );
     }
+
+    #[test]
+    fn case_insensitive_trigger() {
+        let (_db_dir, commands) = storage();
+
+        commands
+            .set_command("asdf", "First_Time", "welcome!")
+            .expect("setting the command should succeed");
+
+        let response = commands
+            .get_command("asdf", "first_TIME")
+            .expect("attempting to get the command should succeed");
+
+        assert_eq!(response.as_deref(), Some("welcome!"));
+    }
+
+    #[test]
+    fn migration_normalises_triggers() {
+        let db_dir = tempdir().expect("creating a temporary directory should succeed");
+        let manager = SqliteConnectionManager::file(db_dir.path().join("db.sqlite3"));
+        let conn_pool = Pool::new(manager).expect("creating a connection pool should succeed");
+
+        let mut conn = conn_pool
+            .get()
+            .expect("getting a connection from the pool should succeed");
+
+        crate::db::migrations::runner()
+            .set_target(refinery::Target::Version(3))
+            .run(conn.deref_mut())
+            .expect("running migrations up to V3 should succeed");
+
+        conn.execute_batch(
+            r#"
+            INSERT INTO commands (channel, trigger, response) VALUES ('asdf', 'Hug', 'old hug');
+            INSERT INTO commands (channel, trigger, response) VALUES ('asdf', 'hug', 'new hug');
+            INSERT INTO commands (channel, trigger, response) VALUES ('asdf', 'Dance', 'dance');
+            "#,
+        )
+        .expect("inserting mixed-case triggers should succeed");
+
+        crate::db::migrations::runner()
+            .run(conn.deref_mut())
+            .expect("running the remaining migrations should succeed");
+
+        let commands = CommandsStore::new(conn_pool.clone());
+
+        let get = |trigger| {
+            commands
+                .get_command("asdf", trigger)
+                .expect("attempting to get the command should succeed")
+        };
+
+        assert_eq!(get("hug").as_deref(), Some("new hug"));
+        assert_eq!(get("hug_1").as_deref(), Some("old hug"));
+        assert_eq!(get("dance").as_deref(), Some("dance"));
+    }
 }
@@ -0,0 +1,413 @@
+//! Named counters in each channel, such as a death counter, which chat can
+//! show and change with their own commands.
+//!
+//! Counters share their names with custom commands, so they're kept alongside
+//! them in the database, and a custom command that only holds a number can be
+//! turned into a counter.
+
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, OptionalExtension, Row, TransactionBehavior};
+use thiserror::Error;
+
+use crate::msg::Role;
+
+/// How a counter is shown if it hasn't been given a format.
+pub const DEFAULT_FORMAT: &str = "{name}: {count}";
+
+/// The role needed to change a counter, if it hasn't been given one.
+pub const DEFAULT_ROLE: Role = Role::Moderator;
+
+/// A counter in a channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counter {
+    pub name: String,
+    pub value: i64,
+    /// How the counter is shown, where `{name}` and `{count}` are replaced by
+    /// its name and value.
+    pub format: String,
+    /// The lowest role that can change the counter.
+    pub role: Role,
+}
+
+impl Counter {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let role = row.get::<_, String>(3)?;
+
+        Ok(Self {
+            name: row.get(0)?,
+            value: row.get(1)?,
+            format: row.get(2)?,
+            role: role.parse().unwrap_or(DEFAULT_ROLE),
+        })
+    }
+
+    /// Show the counter using its format.
+    pub fn render(&self) -> String {
+        self.format
+            .replace("{name}", &self.name)
+            .replace("{count}", &self.value.to_string())
+    }
+}
+
+/// How a counter was added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Added {
+    /// The counter is new, and starts at 0.
+    New,
+    /// The counter replaced a custom command with the same name whose
+    /// response was this number, which the counter starts at.
+    Converted(i64),
+}
+
+/// Storage of counters in an SQLite3 database.
+#[derive(Debug, Clone)]
+pub struct CountersStore {
+    conn_pool: Pool<SqliteConnectionManager>,
+}
+
+impl CountersStore {
+    /// Create a `CountersStore` with a connection to a database.
+    pub fn new(conn_pool: Pool<SqliteConnectionManager>) -> Self {
+        Self { conn_pool }
+    }
+
+    /// Add a counter called `name` in `channel`.
+    ///
+    /// If there's a custom command with the same name whose response is just a
+    /// number, it's replaced by the counter, which starts at that number.
+    pub fn add(&self, channel: &str, name: &str) -> Result<Added, CountersError> {
+        let mut conn = self.conn_pool.get()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let name = name.to_lowercase();
+
+        let exists = tx
+            .query_row(
+                "SELECT 1 FROM counters WHERE channel = ?1 AND name = ?2;",
+                params![channel, name],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+
+        if exists {
+            return Err(CountersError::Exists(name));
+        }
+
+        let command = tx
+            .query_row(
+                "SELECT response FROM commands WHERE channel = ?1 AND trigger = ?2;",
+                params![channel, name],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+
+        let added = match command {
+            None => Added::New,
+            Some(response) => match response.trim().parse::<i64>() {
+                Ok(value) => Added::Converted(value),
+                Err(_) => return Err(CountersError::Command(name)),
+            },
+        };
+
+        let value = match added {
+            Added::New => 0,
+            Added::Converted(value) => value,
+        };
+
+        tx.execute(
+            r#"
+            INSERT INTO counters (channel, name, value, format, role)
+            VALUES (?1, ?2, ?3, ?4, ?5);
+            "#,
+            params![
+                channel,
+                name,
+                value,
+                DEFAULT_FORMAT,
+                DEFAULT_ROLE.to_string()
+            ],
+        )?;
+
+        tx.execute(
+            "DELETE FROM commands WHERE channel = ?1 AND trigger = ?2;",
+            params![channel, name],
+        )?;
+
+        tx.commit()?;
+
+        Ok(added)
+    }
+
+    /// Remove the counter called `name` in `channel`, returning `false` if
+    /// there wasn't one.
+    pub fn remove(&self, channel: &str, name: &str) -> Result<bool, CountersError> {
+        let conn = self.conn_pool.get()?;
+
+        let removed = conn.execute(
+            "DELETE FROM counters WHERE channel = ?1 AND name = ?2;",
+            params![channel, name.to_lowercase()],
+        )?;
+
+        Ok(removed > 0)
+    }
+
+    /// Get the counter called `name` in `channel`, if there is one.
+    pub fn get(&self, channel: &str, name: &str) -> Result<Option<Counter>, CountersError> {
+        let conn = self.conn_pool.get()?;
+
+        let counter = conn
+            .query_row(
+                r#"
+                SELECT name, value, format, role
+                FROM counters
+                WHERE channel = ?1 AND name = ?2;
+                "#,
+                params![channel, name.to_lowercase()],
+                Counter::from_row,
+            )
+            .optional()?;
+
+        Ok(counter)
+    }
+
+    /// Add `change` to the counter called `name` in `channel`, returning the
+    /// counter as it is afterwards, or `None` if there's no such counter.
+    pub fn change(
+        &self,
+        channel: &str,
+        name: &str,
+        change: i64,
+    ) -> Result<Option<Counter>, CountersError> {
+        self.update(channel, name, |value| {
+            value
+                .checked_add(change)
+                .ok_or_else(|| CountersError::Overflow(name.to_lowercase()))
+        })
+    }
+
+    /// Set the counter called `name` in `channel` to `value`, returning the
+    /// counter as it is afterwards, or `None` if there's no such counter.
+    pub fn set(
+        &self,
+        channel: &str,
+        name: &str,
+        value: i64,
+    ) -> Result<Option<Counter>, CountersError> {
+        self.update(channel, name, |_| Ok(value))
+    }
+
+    /// Set the format used to show the counter called `name` in `channel`,
+    /// returning `false` if there's no such counter.
+    pub fn set_format(
+        &self,
+        channel: &str,
+        name: &str,
+        format: &str,
+    ) -> Result<bool, CountersError> {
+        let conn = self.conn_pool.get()?;
+
+        let updated = conn.execute(
+            "UPDATE counters SET format = ?3 WHERE channel = ?1 AND name = ?2;",
+            params![channel, name.to_lowercase(), format],
+        )?;
+
+        Ok(updated > 0)
+    }
+
+    /// Set the lowest role that can change the counter called `name` in
+    /// `channel`, returning `false` if there's no such counter.
+    pub fn set_role(&self, channel: &str, name: &str, role: Role) -> Result<bool, CountersError> {
+        let conn = self.conn_pool.get()?;
+
+        let updated = conn.execute(
+            "UPDATE counters SET role = ?3 WHERE channel = ?1 AND name = ?2;",
+            params![channel, name.to_lowercase(), role.to_string()],
+        )?;
+
+        Ok(updated > 0)
+    }
+
+    /// List the names of every counter in `channel`, in alphabetical order.
+    pub fn list(&self, channel: &str) -> Result<Vec<String>, CountersError> {
+        let conn = self.conn_pool.get()?;
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT name
+            FROM counters
+            WHERE channel = ?1
+            ORDER BY name;
+            "#,
+        )?;
+
+        let names = stmt
+            .query_map(params![channel], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(names)
+    }
+
+    /// List every counter in every channel, as pairs of channel and name.
+    pub fn all(&self) -> Result<Vec<(String, String)>, CountersError> {
+        let conn = self.conn_pool.get()?;
+
+        let mut stmt = conn.prepare("SELECT channel, name FROM counters;")?;
+
+        let counters = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(counters)
+    }
+
+    /// Replace the value of a counter with the result of `update`, in a single
+    /// transaction so that changes made at the same time can't be lost.
+    fn update<F>(
+        &self,
+        channel: &str,
+        name: &str,
+        update: F,
+    ) -> Result<Option<Counter>, CountersError>
+    where
+        F: FnOnce(i64) -> Result<i64, CountersError>,
+    {
+        let mut conn = self.conn_pool.get()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let name = name.to_lowercase();
+
+        let counter = tx
+            .query_row(
+                r#"
+                SELECT name, value, format, role
+                FROM counters
+                WHERE channel = ?1 AND name = ?2;
+                "#,
+                params![channel, name],
+                Counter::from_row,
+            )
+            .optional()?;
+
+        let mut counter = match counter {
+            Some(counter) => counter,
+            None => return Ok(None),
+        };
+
+        counter.value = update(counter.value)?;
+
+        tx.execute(
+            "UPDATE counters SET value = ?3 WHERE channel = ?1 AND name = ?2;",
+            params![channel, name, counter.value],
+        )?;
+
+        tx.commit()?;
+
+        Ok(Some(counter))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CountersError {
+    #[error("there's already a counter called {0}")]
+    Exists(String),
+
+    #[error("there's already a custom command called {0}")]
+    Command(String),
+
+    #[error("counter {0} can't go any further")]
+    Overflow(String),
+
+    #[error("rusqlite error: {0}")]
+    Rusqlite(#[from] rusqlite::Error),
+
+    #[error("r2d2 error: {0}")]
+    R2d2(#[from] r2d2::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::DerefMut;
+
+    use tempfile::{tempdir, TempDir};
+
+    use super::*;
+    use crate::commands::CommandsStore;
+
+    fn storage() -> (TempDir, Pool<SqliteConnectionManager>) {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let manager = SqliteConnectionManager::file(dir.path().join("db.sqlite3"));
+        let conn_pool = Pool::new(manager).expect("creating a connection pool should succeed");
+
+        let mut conn = conn_pool
+            .get()
+            .expect("getting a connection should succeed");
+        crate::db::migrations::runner()
+            .run(conn.deref_mut())
+            .expect("migrations should succeed");
+
+        (dir, conn_pool)
+    }
+
+    #[test]
+    fn change_and_render() {
+        let (_dir, conn_pool) = storage();
+        let store = CountersStore::new(conn_pool);
+
+        assert_eq!(store.add("channel", "Deaths").unwrap(), Added::New);
+        assert!(matches!(
+            store.add("channel", "deaths"),
+            Err(CountersError::Exists(_))
+        ));
+
+        assert_eq!(
+            store.change("channel", "deaths", 2).unwrap().unwrap().value,
+            2
+        );
+        assert_eq!(
+            store
+                .change("channel", "deaths", -3)
+                .unwrap()
+                .unwrap()
+                .value,
+            -1
+        );
+        assert_eq!(store.change("other", "deaths", 1).unwrap(), None);
+
+        assert!(store
+            .set_format("channel", "deaths", "Died {count} times")
+            .unwrap());
+        let counter = store.set("channel", "deaths", 10).unwrap().unwrap();
+        assert_eq!(counter.render(), "Died 10 times");
+
+        assert!(matches!(
+            store.change("channel", "deaths", i64::MAX),
+            Err(CountersError::Overflow(_))
+        ));
+        assert_eq!(store.get("channel", "deaths").unwrap().unwrap().value, 10);
+    }
+
+    #[test]
+    fn converts_number_commands() {
+        let (_dir, conn_pool) = storage();
+        let commands = CommandsStore::new(conn_pool.clone());
+        let store = CountersStore::new(conn_pool);
+
+        commands.set_command("channel", "deaths", " 42 ").unwrap();
+        commands
+            .set_command("channel", "hug", "{sender} hugs {1}")
+            .unwrap();
+
+        assert_eq!(
+            store.add("channel", "deaths").unwrap(),
+            Added::Converted(42)
+        );
+        assert_eq!(commands.get_command("channel", "deaths").unwrap(), None);
+        assert_eq!(store.get("channel", "deaths").unwrap().unwrap().value, 42);
+
+        assert!(matches!(
+            store.add("channel", "hug"),
+            Err(CountersError::Command(_))
+        ));
+        assert!(commands.get_command("channel", "hug").unwrap().is_some());
+    }
+}
@@ -2,9 +2,20 @@
 
 pub mod auth;
 pub mod bot;
+pub mod chat;
 pub mod commands;
+pub mod counters;
 pub mod db;
+pub mod messagelog;
+pub mod module;
 pub mod msg;
 pub mod parse;
+pub mod points;
+pub mod poll;
 pub mod quotes;
+pub mod raffle;
+pub mod registry;
+pub mod scores;
+pub mod trivia;
+pub mod wordgame;
 pub mod wordsearch;
@@ -1,29 +1,133 @@
+use std::time::Duration;
+
 use clap::Parser;
-use eyre::Result;
+use eyre::{eyre, Result};
 use opts::Opts;
-use oxbow::bot::Bot;
+use oxbow::{bot::Bot, chat::console, wordsearch::auto::AutoPlay};
 
 mod opts;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
-
     dotenv::dotenv().ok();
 
     let opts: Opts = Opts::parse();
 
+    if opts.console {
+        // Responses are printed to stdout, so logs need to go somewhere else.
+        tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .init();
+    } else {
+        tracing_subscriber::fmt::init();
+    }
+
     let mut bot_the_builder = Bot::the_builder()
-        .twitch_credentials(opts.client_id, opts.client_secret)
-        .twitch_name(opts.twitch_name)
-        .extend_channels(opts.channels)
-        .prefix(opts.prefix);
+        .twitch_name(&opts.twitch_name)
+        .prefix(opts.prefix)
+        .owner(&opts.owner)
+        .help_url(opts.help_url);
+
+    if opts.console {
+        bot_the_builder = bot_the_builder.add_channel(&opts.console_channel);
+    } else {
+        bot_the_builder = bot_the_builder.extend_channels(opts.channels);
+    }
+
+    if let (Some(client_id), Some(client_secret)) = (opts.client_id, opts.client_secret) {
+        bot_the_builder = bot_the_builder.twitch_credentials(client_id, client_secret);
+    }
+
+    for disable in opts.disable {
+        let (channel, module) = disable
+            .split_once(':')
+            .ok_or_else(|| eyre!("expected channel:module, found {}", disable))?;
+
+        bot_the_builder = bot_the_builder.disable_module(channel, module);
+    }
+
+    for word_list in opts.word_lists {
+        let (channel, source) = word_list
+            .split_once(':')
+            .ok_or_else(|| eyre!("expected channel:source, found {}", word_list))?;
+
+        bot_the_builder = bot_the_builder.word_list(channel, source.parse()?);
+    }
+
+    for import in opts.word_list_imports {
+        let (name, path) = import
+            .split_once(':')
+            .ok_or_else(|| eyre!("expected name:path, found {}", import))?;
+
+        bot_the_builder = bot_the_builder.import_word_list(name, path);
+    }
+
+    if let Some(game_bot) = opts.game_bot {
+        let mut auto_play =
+            AutoPlay::new(game_bot).guess_interval(Duration::from_secs(opts.guess_interval));
+
+        if let Some(pattern) = opts.lower_pattern {
+            auto_play = auto_play.lower_pattern(&pattern)?;
+        }
+
+        if let Some(pattern) = opts.upper_pattern {
+            auto_play = auto_play.upper_pattern(&pattern)?;
+        }
+
+        if let Some(pattern) = opts.found_pattern {
+            auto_play = auto_play.found_pattern(&pattern)?;
+        }
+
+        bot_the_builder = bot_the_builder.auto_play(auto_play);
+    }
+
+    bot_the_builder =
+        bot_the_builder.word_game_cooldown(Duration::from_secs(opts.word_game_cooldown));
+
+    for import in opts.trivia_imports {
+        let (bank, path) = import
+            .split_once(':')
+            .ok_or_else(|| eyre!("expected bank:path, found {}", import))?;
+
+        bot_the_builder = bot_the_builder.import_trivia(bank, path);
+    }
+
+    bot_the_builder = bot_the_builder.trivia_round_time(Duration::from_secs(opts.trivia_time));
+    bot_the_builder = bot_the_builder.chat_points(
+        opts.chat_points,
+        Duration::from_secs(opts.chat_points_interval),
+    );
+    bot_the_builder = bot_the_builder.poll_updates(Duration::from_secs(opts.poll_updates));
+
+    for channel in opts.logged_channels {
+        bot_the_builder = bot_the_builder.log_messages(channel);
+    }
+
+    let retention = match opts.message_log_retention {
+        0 => None,
+        days => Some(
+            days.checked_mul(24 * 60 * 60)
+                .map(Duration::from_secs)
+                .ok_or_else(|| eyre!("message log retention of {} days is too long", days))?,
+        ),
+    };
+    bot_the_builder = bot_the_builder.message_log_retention(retention);
 
     if let Some(db_path) = opts.database {
         bot_the_builder = bot_the_builder.db_path(db_path);
     }
 
-    bot_the_builder.build()?.run().await?;
+    let mut bot = bot_the_builder.build()?;
+
+    if opts.console {
+        let user = opts.console_user.as_deref().unwrap_or(&opts.owner);
+        let (msg_rx, chat) = console::connect(&opts.console_channel, user, &opts.twitch_name);
+
+        bot.migrate()?;
+        bot.run_with(msg_rx, chat).await?;
+    } else {
+        bot.run().await?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,518 @@
+//! An opt-in log of the messages sent in each channel, used to look up when
+//! chatters were first and last seen.
+//!
+//! Messages are queued by the receive loop without waiting, and written to the
+//! database in batches by a separate task, so a busy chat never has to wait
+//! for the database. Users who opt out aren't logged, and any messages of
+//! theirs that were already logged are deleted.
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, TransactionBehavior};
+use thiserror::Error;
+use tokio::{
+    sync::mpsc::{self, error::TrySendError},
+    task::JoinHandle,
+    time::{self, MissedTickBehavior},
+};
+use tracing::{debug, error, info, warn};
+
+/// The number of messages that can be waiting to be written before any more
+/// are dropped.
+const QUEUE_CAPACITY: usize = 4096;
+
+/// The most messages written to the database at once.
+const BATCH_SIZE: usize = 100;
+
+/// How long a message can wait to be written to the database, by default.
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often messages older than the retention period are deleted.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How long messages are kept for, by default.
+pub const DEFAULT_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// A message sent in a channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoggedMessage {
+    pub channel: String,
+    pub username: String,
+    pub text: String,
+    pub time: DateTime<Utc>,
+}
+
+/// The users who have opted out of the message log, shared between everything
+/// that needs to check.
+#[derive(Debug, Clone, Default)]
+pub struct OptOuts {
+    users: Arc<RwLock<HashSet<String>>>,
+}
+
+impl OptOuts {
+    pub fn contains(&self, username: &str) -> bool {
+        self.users
+            .read()
+            .expect("opt outs lock poisoned")
+            .contains(username)
+    }
+
+    pub fn insert(&self, username: &str) {
+        self.users
+            .write()
+            .expect("opt outs lock poisoned")
+            .insert(username.to_owned());
+    }
+
+    pub fn remove(&self, username: &str) {
+        self.users
+            .write()
+            .expect("opt outs lock poisoned")
+            .remove(username);
+    }
+}
+
+/// Which channels are logged and for how long, and the task that writes the
+/// log.
+#[derive(Debug, Clone)]
+pub struct MessageLog {
+    store: MessageLogStore,
+    channels: HashSet<String>,
+    opt_outs: OptOuts,
+    retention: Option<Duration>,
+    flush_interval: Duration,
+}
+
+impl MessageLog {
+    /// Create a `MessageLog` that writes to `store`, skipping the users in
+    /// `opt_outs`, but doesn't log any channels yet.
+    pub fn new(store: MessageLogStore, opt_outs: OptOuts) -> Self {
+        Self {
+            store,
+            channels: HashSet::new(),
+            opt_outs,
+            retention: Some(DEFAULT_RETENTION),
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+        }
+    }
+
+    /// Log the messages sent in `channel`.
+    pub fn channel(mut self, channel: &str) -> Self {
+        self.channels.insert(channel.to_owned());
+        self
+    }
+
+    /// Delete messages once they're older than `retention`, or never if it's
+    /// `None`.
+    pub fn retention(mut self, retention: Option<Duration>) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Let messages wait up to `flush_interval` to be written, so they can be
+    /// written in batches. If it's zero, each message is written as soon as
+    /// it's queued.
+    pub fn flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    /// Whether messages sent in `channel` are logged.
+    pub fn is_logged(&self, channel: &str) -> bool {
+        self.channels.contains(channel)
+    }
+
+    /// Spawn the task that writes queued messages to the database, returning a
+    /// [`MessageLogger`] to queue them with.
+    ///
+    /// The task stops once every `MessageLogger` has been dropped and the
+    /// messages they queued have been written.
+    pub fn spawn(&self) -> (MessageLogger, JoinHandle<()>) {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+
+        let writer = tokio::spawn(write_loop(
+            rx,
+            self.store.clone(),
+            self.retention,
+            self.flush_interval,
+        ));
+
+        let logger = MessageLogger {
+            tx,
+            channels: Arc::new(self.channels.clone()),
+            opt_outs: self.opt_outs.clone(),
+        };
+
+        (logger, writer)
+    }
+}
+
+/// Queues messages to be written to the log.
+#[derive(Debug, Clone)]
+pub struct MessageLogger {
+    tx: mpsc::Sender<LoggedMessage>,
+    channels: Arc<HashSet<String>>,
+    opt_outs: OptOuts,
+}
+
+impl MessageLogger {
+    /// Queue a message to be logged, if its channel is logged and its sender
+    /// hasn't opted out.
+    ///
+    /// This never waits: if the queue is full, the message is dropped.
+    pub fn log(&self, channel: &str, username: &str, text: &str, time: DateTime<Utc>) {
+        if !self.channels.contains(channel) || self.opt_outs.contains(username) {
+            return;
+        }
+
+        let message = LoggedMessage {
+            channel: channel.to_owned(),
+            username: username.to_owned(),
+            text: text.to_owned(),
+            time,
+        };
+
+        match self.tx.try_send(message) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                warn!(%channel, "message log queue full, dropping message")
+            }
+            Err(TrySendError::Closed(_)) => error!(%channel, "message log writer has stopped"),
+        }
+    }
+}
+
+/// Write messages from `rx` to `store` in batches, at least every
+/// `flush_interval`, and delete old messages every so often, until `rx` is
+/// closed.
+async fn write_loop(
+    mut rx: mpsc::Receiver<LoggedMessage>,
+    store: MessageLogStore,
+    retention: Option<Duration>,
+    flush_interval: Duration,
+) {
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+
+    // Intervals can't be zero, but without a flush interval every message is
+    // written straight away, so there's never anything left to flush.
+    let mut flush = time::interval(if flush_interval.is_zero() {
+        DEFAULT_FLUSH_INTERVAL
+    } else {
+        flush_interval
+    });
+    flush.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut expire = time::interval(RETENTION_INTERVAL);
+    expire.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            message = rx.recv() => match message {
+                Some(message) => {
+                    batch.push(message);
+
+                    if batch.len() >= BATCH_SIZE || flush_interval.is_zero() {
+                        write_batch(&store, &mut batch);
+                    }
+                }
+                None => break,
+            },
+            _ = flush.tick() => write_batch(&store, &mut batch),
+            _ = expire.tick() => {
+                // A retention too long to represent might as well be forever.
+                let cutoff = retention
+                    .and_then(|retention| chrono::Duration::from_std(retention).ok())
+                    .and_then(|retention| Utc::now().checked_sub_signed(retention));
+
+                if let Some(cutoff) = cutoff {
+                    match store.delete_before(cutoff) {
+                        Ok(deleted) => info!(deleted, "deleted old messages from the log"),
+                        Err(err) => error!(%err, "failed to delete old messages from the log"),
+                    }
+                }
+            }
+        }
+    }
+
+    write_batch(&store, &mut batch);
+    debug!("message log writer stopped");
+}
+
+fn write_batch(store: &MessageLogStore, batch: &mut Vec<LoggedMessage>) {
+    if batch.is_empty() {
+        return;
+    }
+
+    match store.insert(batch) {
+        Ok(()) => debug!(messages = batch.len(), "wrote messages to the log"),
+        Err(err) => error!(%err, messages = batch.len(), "failed to write messages to the log"),
+    }
+
+    batch.clear();
+}
+
+/// Storage of the message log in an SQLite3 database.
+#[derive(Debug, Clone)]
+pub struct MessageLogStore {
+    conn_pool: Pool<SqliteConnectionManager>,
+}
+
+impl MessageLogStore {
+    /// Create a `MessageLogStore` with a connection to a database.
+    pub fn new(conn_pool: Pool<SqliteConnectionManager>) -> Self {
+        Self { conn_pool }
+    }
+
+    /// Write `messages` to the log in a single transaction, skipping any from
+    /// users who have opted out.
+    pub fn insert(&self, messages: &[LoggedMessage]) -> Result<(), MessageLogError> {
+        let mut conn = self.conn_pool.get()?;
+        let tx = conn.transaction()?;
+
+        {
+            let mut stmt = tx.prepare(
+                r#"
+                INSERT INTO message_log (channel, username, message, time)
+                SELECT ?1, ?2, ?3, ?4
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM message_log_opt_outs WHERE username = ?2
+                );
+                "#,
+            )?;
+
+            for message in messages {
+                stmt.execute(params![
+                    message.channel,
+                    message.username,
+                    message.text,
+                    message.time,
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// When `username` first sent a message in `channel` that's still in the
+    /// log.
+    pub fn first_seen(
+        &self,
+        channel: &str,
+        username: &str,
+    ) -> Result<Option<DateTime<Utc>>, MessageLogError> {
+        self.seen("MIN", channel, username)
+    }
+
+    /// When `username` last sent a message in `channel`.
+    pub fn last_seen(
+        &self,
+        channel: &str,
+        username: &str,
+    ) -> Result<Option<DateTime<Utc>>, MessageLogError> {
+        self.seen("MAX", channel, username)
+    }
+
+    fn seen(
+        &self,
+        aggregate: &str,
+        channel: &str,
+        username: &str,
+    ) -> Result<Option<DateTime<Utc>>, MessageLogError> {
+        let conn = self.conn_pool.get()?;
+
+        let time = conn.query_row(
+            &format!(
+                r#"
+                SELECT {}(time)
+                FROM message_log
+                WHERE channel = ?1 AND username = ?2;
+                "#,
+                aggregate
+            ),
+            params![channel, username],
+            |row| row.get(0),
+        )?;
+
+        Ok(time)
+    }
+
+    /// Delete every message sent before `cutoff`, returning how many were
+    /// deleted.
+    pub fn delete_before(&self, cutoff: DateTime<Utc>) -> Result<usize, MessageLogError> {
+        let conn = self.conn_pool.get()?;
+
+        let deleted = conn.execute("DELETE FROM message_log WHERE time < ?1;", params![cutoff])?;
+
+        Ok(deleted)
+    }
+
+    /// Stop logging messages from `username`, and delete any that were already
+    /// logged, returning how many were deleted.
+    pub fn opt_out(&self, username: &str, now: DateTime<Utc>) -> Result<usize, MessageLogError> {
+        let mut conn = self.conn_pool.get()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        tx.execute(
+            r#"
+            INSERT OR IGNORE INTO message_log_opt_outs (username, time)
+            VALUES (?1, ?2);
+            "#,
+            params![username, now],
+        )?;
+
+        let deleted = tx.execute(
+            "DELETE FROM message_log WHERE username = ?1;",
+            params![username],
+        )?;
+
+        tx.commit()?;
+
+        Ok(deleted)
+    }
+
+    /// Start logging messages from `username` again, returning `false` if they
+    /// hadn't opted out.
+    pub fn opt_in(&self, username: &str) -> Result<bool, MessageLogError> {
+        let conn = self.conn_pool.get()?;
+
+        let removed = conn.execute(
+            "DELETE FROM message_log_opt_outs WHERE username = ?1;",
+            params![username],
+        )?;
+
+        Ok(removed > 0)
+    }
+
+    /// Every user who has opted out.
+    pub fn opt_outs(&self) -> Result<Vec<String>, MessageLogError> {
+        let conn = self.conn_pool.get()?;
+
+        let mut stmt = conn.prepare("SELECT username FROM message_log_opt_outs;")?;
+
+        let users = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(users)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum MessageLogError {
+    #[error("rusqlite error: {0}")]
+    Rusqlite(#[from] rusqlite::Error),
+
+    #[error("r2d2 error: {0}")]
+    R2d2(#[from] r2d2::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::DerefMut;
+
+    use chrono::Duration as ChronoDuration;
+    use tempfile::{tempdir, TempDir};
+
+    use super::*;
+
+    fn storage() -> (TempDir, MessageLogStore) {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let manager = SqliteConnectionManager::file(dir.path().join("db.sqlite3"));
+        let conn_pool = Pool::new(manager).expect("creating a connection pool should succeed");
+
+        let mut conn = conn_pool
+            .get()
+            .expect("getting a connection should succeed");
+        crate::db::migrations::runner()
+            .run(conn.deref_mut())
+            .expect("migrations should succeed");
+
+        (dir, MessageLogStore::new(conn_pool))
+    }
+
+    fn message(username: &str, time: DateTime<Utc>) -> LoggedMessage {
+        LoggedMessage {
+            channel: "channel".to_owned(),
+            username: username.to_owned(),
+            text: "hello".to_owned(),
+            time,
+        }
+    }
+
+    #[test]
+    fn seen_and_retention() {
+        let (_dir, store) = storage();
+        let now = Utc::now();
+        let earlier = now - ChronoDuration::days(2);
+
+        store
+            .insert(&[message("someone", earlier), message("someone", now)])
+            .expect("inserting messages should succeed");
+
+        assert_eq!(
+            store.first_seen("channel", "someone").unwrap(),
+            Some(earlier)
+        );
+        assert_eq!(store.last_seen("channel", "someone").unwrap(), Some(now));
+        assert_eq!(store.last_seen("other", "someone").unwrap(), None);
+
+        assert_eq!(
+            store.delete_before(now - ChronoDuration::days(1)).unwrap(),
+            1
+        );
+        assert_eq!(store.first_seen("channel", "someone").unwrap(), Some(now));
+    }
+
+    #[test]
+    fn opt_outs_are_honoured() {
+        let (_dir, store) = storage();
+        let now = Utc::now();
+
+        store.insert(&[message("someone", now)]).unwrap();
+
+        assert_eq!(store.opt_out("someone", now).unwrap(), 1);
+        assert_eq!(store.opt_outs().unwrap(), vec!["someone".to_owned()]);
+
+        // Messages queued before opting out aren't written either.
+        store
+            .insert(&[message("someone", now), message("other", now)])
+            .unwrap();
+        assert_eq!(store.last_seen("channel", "someone").unwrap(), None);
+        assert!(store.last_seen("channel", "other").unwrap().is_some());
+
+        assert!(store.opt_in("someone").unwrap());
+        assert!(!store.opt_in("someone").unwrap());
+    }
+
+    #[tokio::test]
+    async fn logger_skips_unlogged_channels() {
+        let (_dir, store) = storage();
+        let opt_outs = OptOuts::default();
+        opt_outs.insert("quiet");
+
+        let log = MessageLog::new(store.clone(), opt_outs).channel("channel");
+        let (logger, writer) = log.spawn();
+
+        let now = Utc::now();
+        logger.log("channel", "someone", "hi", now);
+        logger.log("channel", "quiet", "hi", now);
+        logger.log("other", "someone", "hi", now);
+
+        // Dropping the logger makes the writer write what's left and stop.
+        drop(logger);
+        writer.await.expect("the writer should not panic");
+
+        assert!(store.last_seen("channel", "someone").unwrap().is_some());
+        assert_eq!(store.last_seen("channel", "quiet").unwrap(), None);
+        assert_eq!(store.last_seen("other", "someone").unwrap(), None);
+    }
+}
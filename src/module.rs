@@ -0,0 +1,638 @@
+//! Features of the bot, each of which claims its own commands, parses their
+//! arguments and handles the resulting tasks with its own state.
+
+mod commands;
+mod counters;
+mod greet;
+mod messagelog;
+mod points;
+mod poll;
+mod quotes;
+mod raffle;
+mod trivia;
+mod wordgame;
+mod wordsearch;
+
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    fmt::{self, Debug, Display},
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use lalrpop_util::{lexer::Token, ParseError};
+use thiserror::Error;
+use tracing::error;
+
+pub use self::{
+    commands::{CustomCommand, CustomCommandsModule},
+    counters::{CounterTask, CountersModule},
+    greet::{Greet, GreetModule},
+    messagelog::MessageLogModule,
+    points::{PointsModule, PointsTask, DEFAULT_CHAT_INTERVAL, DEFAULT_CHAT_POINTS},
+    poll::{PollModule, PollTask, DEFAULT_POLL_UPDATES},
+    quotes::QuotesModule,
+    raffle::{RaffleModule, RaffleTask},
+    trivia::{TriviaModule, TriviaTask, DEFAULT_ROUND_TIME},
+    wordgame::{WordGameModule, DEFAULT_GUESS_COOLDOWN},
+    wordsearch::{WordSearchModule, WordSearchTask},
+};
+use crate::{
+    commands::CommandsError,
+    counters::CountersError,
+    messagelog::MessageLogError,
+    msg::{Metadata, Response, Role},
+    parse::usage::usage_hint,
+    points::PointsError,
+    quotes::QuotesError,
+    raffle::RaffleError,
+    registry::{BuiltIn, CORE_COMMANDS},
+    scores::{ScoresError, ScoresStore},
+    trivia::TriviaError,
+    wordsearch::{list::WordListError, session::SessionError},
+};
+
+/// How often [`Module::tick`] is called.
+pub const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The number of users shown on a game's leaderboard.
+const LEADERBOARD_SIZE: usize = 5;
+
+/// A feature of the bot, such as quotes or custom commands.
+///
+/// Each module claims the keywords of the built-in commands it lists in
+/// [`Module::commands`]. When a message starting with one of those keywords is
+/// received, the module parses it into its own [`Module::Task`], which is then
+/// passed back to the same module to be handled.
+#[async_trait]
+pub trait Module: Send + Sync + 'static {
+    /// The task this module produces from messages, and then handles.
+    type Task: Debug + Send + 'static;
+
+    /// The name used to refer to this module, such as when disabling it in a
+    /// channel.
+    fn name(&self) -> &'static str;
+
+    /// The built-in commands provided by this module, whose keywords it claims.
+    fn commands(&self) -> &'static [BuiltIn];
+
+    /// Whether this module should be given the chance to parse commands that
+    /// weren't claimed by any other module. This is how custom commands, which
+    /// can have any trigger, are handled.
+    fn fallback(&self) -> bool {
+        false
+    }
+
+    /// Prepare the module to run, once the database is ready.
+    ///
+    /// Any problem that would stop the module from working properly, such as
+    /// invalid configuration, should be returned here so that the bot can
+    /// refuse to start.
+    fn start(&self) -> Result<(), ModuleError> {
+        Ok(())
+    }
+
+    /// Parse the text of a command (without the prefix) into a task.
+    ///
+    /// Returns `Ok(None)` if there's nothing to do, and a [`UsageError`] if
+    /// the command was meant for this module but was written incorrectly.
+    fn parse(&self, input: &str, meta: &Metadata) -> Result<Option<Self::Task>, UsageError>;
+
+    /// Look at a message that isn't a command, and return a task if this
+    /// module should do something in response to it anyway.
+    fn observe(&self, _text: &str, _meta: &Metadata) -> Option<Self::Task> {
+        None
+    }
+
+    /// Act on a task produced by this module, returning any responses that
+    /// should be sent in the channel the task came from.
+    async fn handle(&self, task: Self::Task, meta: &Metadata)
+        -> Result<Vec<Response>, ModuleError>;
+
+    /// Check on anything this module is waiting for, such as a round of a
+    /// game running out of time, returning any responses that should be sent
+    /// along with the metadata of the channel each belongs to.
+    ///
+    /// This is called every [`TICK_INTERVAL`] while the bot is running.
+    async fn tick(&self) -> Result<Vec<(Response, Metadata)>, ModuleError> {
+        Ok(vec![])
+    }
+
+    /// Describe a command in `channel` that isn't one of the built-in
+    /// commands in [`Module::commands`], if this module provides it.
+    fn describe(&self, _channel: &str, _name: &str) -> Result<Option<String>, ModuleError> {
+        Ok(None)
+    }
+
+    /// List the names of the commands in `channel` that aren't one of the
+    /// built-in commands in [`Module::commands`].
+    fn list(&self, _channel: &str) -> Result<Vec<String>, ModuleError> {
+        Ok(vec![])
+    }
+}
+
+/// A task produced by a module, with its type erased so that it can be sent
+/// from the receive loop to the process loop.
+pub struct ModuleTask {
+    task: Box<dyn Any + Send>,
+    description: String,
+}
+
+impl ModuleTask {
+    fn new<T: Debug + Send + 'static>(task: T) -> Self {
+        Self {
+            description: format!("{:?}", task),
+            task: Box::new(task),
+        }
+    }
+}
+
+impl Debug for ModuleTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.description)
+    }
+}
+
+/// An object-safe version of [`Module`], implemented for every module.
+#[async_trait]
+trait DynModule: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn commands(&self) -> &'static [BuiltIn];
+    fn fallback(&self) -> bool;
+    fn start(&self) -> Result<(), ModuleError>;
+    fn parse(&self, input: &str, meta: &Metadata) -> Result<Option<ModuleTask>, UsageError>;
+    fn observe(&self, text: &str, meta: &Metadata) -> Option<ModuleTask>;
+    async fn handle(&self, task: ModuleTask, meta: &Metadata)
+        -> Result<Vec<Response>, ModuleError>;
+    async fn tick(&self) -> Result<Vec<(Response, Metadata)>, ModuleError>;
+    fn describe(&self, channel: &str, name: &str) -> Result<Option<String>, ModuleError>;
+    fn list(&self, channel: &str) -> Result<Vec<String>, ModuleError>;
+}
+
+#[async_trait]
+impl<M: Module> DynModule for M {
+    fn name(&self) -> &'static str {
+        Module::name(self)
+    }
+
+    fn commands(&self) -> &'static [BuiltIn] {
+        Module::commands(self)
+    }
+
+    fn fallback(&self) -> bool {
+        Module::fallback(self)
+    }
+
+    fn start(&self) -> Result<(), ModuleError> {
+        Module::start(self)
+    }
+
+    fn parse(&self, input: &str, meta: &Metadata) -> Result<Option<ModuleTask>, UsageError> {
+        Module::parse(self, input, meta).map(|task| task.map(ModuleTask::new))
+    }
+
+    fn observe(&self, text: &str, meta: &Metadata) -> Option<ModuleTask> {
+        Module::observe(self, text, meta).map(ModuleTask::new)
+    }
+
+    async fn handle(
+        &self,
+        task: ModuleTask,
+        meta: &Metadata,
+    ) -> Result<Vec<Response>, ModuleError> {
+        let task = task
+            .task
+            .downcast::<M::Task>()
+            .map_err(|_| ModuleError::WrongTask(Module::name(self)))?;
+
+        Module::handle(self, *task, meta).await
+    }
+
+    async fn tick(&self) -> Result<Vec<(Response, Metadata)>, ModuleError> {
+        Module::tick(self).await
+    }
+
+    fn describe(&self, channel: &str, name: &str) -> Result<Option<String>, ModuleError> {
+        Module::describe(self, channel, name)
+    }
+
+    fn list(&self, channel: &str) -> Result<Vec<String>, ModuleError> {
+        Module::list(self, channel)
+    }
+}
+
+/// The set of modules the bot is running, and the channels each one is
+/// disabled in.
+#[derive(Clone, Default)]
+pub struct ModuleRegistry {
+    modules: Vec<Arc<dyn DynModule>>,
+    disabled: HashMap<String, HashSet<String>>,
+}
+
+impl ModuleRegistry {
+    /// Create an empty `ModuleRegistry`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a module to the registry.
+    ///
+    /// Modules are asked to parse unclaimed commands in the order they're
+    /// registered, so any [`Module::fallback`] modules that accept any command
+    /// should be registered last.
+    pub fn register<M: Module>(&mut self, module: M) {
+        self.modules.push(Arc::new(module));
+    }
+
+    /// Disable the module called `module` in `channel`.
+    pub fn disable(&mut self, channel: &str, module: &str) {
+        self.disabled
+            .entry(channel.to_owned())
+            .or_default()
+            .insert(module.to_owned());
+    }
+
+    /// Check whether the module called `module` is enabled in `channel`.
+    pub fn is_enabled(&self, channel: &str, module: &str) -> bool {
+        !matches!(self.disabled.get(channel), Some(disabled) if disabled.contains(module))
+    }
+
+    /// The names of every registered module.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.modules.iter().map(|module| module.name()).collect()
+    }
+
+    fn enabled_in<'a>(&'a self, channel: &'a str) -> impl Iterator<Item = &'a Arc<dyn DynModule>> {
+        self.modules
+            .iter()
+            .filter(move |module| self.is_enabled(channel, module.name()))
+    }
+
+    fn get(&self, module: &str) -> Option<&Arc<dyn DynModule>> {
+        self.modules.iter().find(|ea| ea.name() == module)
+    }
+
+    /// Prepare every module to run, stopping at the first one that can't.
+    pub fn start(&self) -> Result<(), ModuleError> {
+        for module in self.modules.iter() {
+            module.start()?;
+        }
+
+        Ok(())
+    }
+
+    /// Parse the text of a command (without the prefix) using the module that
+    /// claims its keyword, or failing that, the first fallback module that
+    /// accepts it.
+    ///
+    /// Commands that the sender doesn't have the role to run are ignored.
+    pub fn parse(
+        &self,
+        input: &str,
+        meta: &Metadata,
+    ) -> Result<Option<(&'static str, ModuleTask)>, UsageError> {
+        let keyword = match keyword(input) {
+            Some(keyword) => keyword,
+            None => return Ok(None),
+        };
+
+        for module in self.enabled_in(&meta.channel) {
+            if let Some(built_in) = module.commands().iter().find(|ea| ea.name == keyword) {
+                if meta.role < built_in.role {
+                    return Ok(None);
+                }
+
+                return Ok(module.parse(input, meta)?.map(|task| (module.name(), task)));
+            }
+        }
+
+        for module in self
+            .enabled_in(&meta.channel)
+            .filter(|module| module.fallback())
+        {
+            if let Some(task) = module.parse(input, meta)? {
+                return Ok(Some((module.name(), task)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Give every module enabled in the channel a chance to act on a message
+    /// that isn't a command.
+    pub fn observe(&self, text: &str, meta: &Metadata) -> Vec<(&'static str, ModuleTask)> {
+        self.enabled_in(&meta.channel)
+            .filter_map(|module| module.observe(text, meta).map(|task| (module.name(), task)))
+            .collect()
+    }
+
+    /// Pass a task back to the module that produced it.
+    pub async fn handle(
+        &self,
+        module: &str,
+        task: ModuleTask,
+        meta: &Metadata,
+    ) -> Result<Vec<Response>, ModuleError> {
+        self.get(module)
+            .ok_or_else(|| ModuleError::UnknownModule(module.to_owned()))?
+            .handle(task, meta)
+            .await
+    }
+
+    /// Let every module check on anything it's waiting for, returning the
+    /// responses they want to send.
+    ///
+    /// A module that fails is logged and skipped, so that it can't stop the
+    /// others from running.
+    pub async fn tick(&self) -> Vec<(Response, Metadata)> {
+        let mut responses = vec![];
+
+        for module in self.modules.iter() {
+            match module.tick().await {
+                Ok(module_responses) => responses.extend(module_responses),
+                Err(err) => error!(module = module.name(), %err, "module tick failed"),
+            }
+        }
+
+        responses
+    }
+
+    /// The keywords of every built-in command, which are always claimed before
+    /// any other commands are considered.
+    pub fn command_names(&self) -> Vec<&'static str> {
+        CORE_COMMANDS
+            .iter()
+            .chain(self.modules.iter().flat_map(|module| module.commands()))
+            .map(|built_in| built_in.name)
+            .collect()
+    }
+
+    /// Find the built-in command with the keyword `name`, in any case.
+    pub fn find_command(&self, name: &str) -> Option<&'static BuiltIn> {
+        let name = name.to_lowercase();
+
+        CORE_COMMANDS
+            .iter()
+            .chain(self.modules.iter().flat_map(|module| module.commands()))
+            .find(|built_in| built_in.name == name)
+    }
+
+    /// The built-in commands a user with `role` is allowed to run in
+    /// `channel`.
+    pub fn available_to(&self, channel: &str, role: Role) -> Vec<&'static BuiltIn> {
+        CORE_COMMANDS
+            .iter()
+            .chain(
+                self.enabled_in(channel)
+                    .flat_map(|module| module.commands()),
+            )
+            .filter(|built_in| role >= built_in.role)
+            .collect()
+    }
+
+    /// Describe a command in `channel` that isn't a built-in command, if any
+    /// module provides one called `name`.
+    pub fn describe(&self, channel: &str, name: &str) -> Result<Option<String>, ModuleError> {
+        for module in self.enabled_in(channel) {
+            if let Some(description) = module.describe(channel, name)? {
+                return Ok(Some(description));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// List the names of the commands in `channel` that aren't built-in
+    /// commands.
+    pub fn list(&self, channel: &str) -> Result<Vec<String>, ModuleError> {
+        let mut names = vec![];
+
+        for module in self.enabled_in(channel) {
+            names.extend(module.list(channel)?);
+        }
+
+        Ok(names)
+    }
+}
+
+impl Debug for ModuleRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ModuleRegistry")
+            .field("modules", &self.names())
+            .field("disabled", &self.disabled)
+            .finish()
+    }
+}
+
+/// The first word of `input`, in lowercase.
+pub fn keyword(input: &str) -> Option<String> {
+    input.split_whitespace().next().map(str::to_lowercase)
+}
+
+/// The users with the most points in `channel` in a game, after `title`, or
+/// `empty` if nobody has any yet.
+fn leaderboard(
+    scores: &ScoresStore,
+    channel: &str,
+    title: &str,
+    empty: &str,
+) -> Result<String, ModuleError> {
+    let leaders = scores.leaderboard(channel, LEADERBOARD_SIZE)?;
+
+    if leaders.is_empty() {
+        return Ok(empty.to_owned());
+    }
+
+    let leaders = leaders
+        .iter()
+        .enumerate()
+        .map(|(index, (username, score))| format!("{}. @{} ({})", index + 1, username, score))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Ok(format!("{}: {}", title, leaders))
+}
+
+/// Describe `n` things, e.g. "1 game" or "2 games".
+fn count<N: Display + PartialEq + From<u8>>(n: N, singular: &str, plural: &str) -> String {
+    let noun = if n == N::from(1) { singular } else { plural };
+    format!("{} {}", n, noun)
+}
+
+/// A command that was meant for a module, but couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageError {
+    /// The keyword of the command.
+    pub keyword: String,
+    /// What was wrong with the command.
+    pub hint: String,
+}
+
+impl UsageError {
+    /// Create a `UsageError` for the command with the keyword `keyword`, from
+    /// the error encountered while parsing `input`.
+    pub fn new(keyword: &str, input: &str, err: &ParseError<usize, Token<'_>, &str>) -> Self {
+        Self {
+            keyword: keyword.to_owned(),
+            hint: usage_hint(input, err),
+        }
+    }
+}
+
+/// Errors that could be encountered while a module is handling a task.
+#[derive(Debug, Error)]
+pub enum ModuleError {
+    /// Something the user did wrong, with a message explaining it to them.
+    #[error("{0}")]
+    User(String),
+
+    #[error("command error: {0}")]
+    Commands(#[from] CommandsError),
+
+    #[error("counter error: {0}")]
+    Counters(#[from] CountersError),
+
+    #[error("message log error: {0}")]
+    MessageLog(#[from] MessageLogError),
+
+    #[error("points error: {0}")]
+    Points(#[from] PointsError),
+
+    #[error("quote error: {0}")]
+    Quotes(#[from] QuotesError),
+
+    #[error("word list {list}: {error}")]
+    WordList {
+        list: String,
+        #[source]
+        error: WordListError,
+    },
+
+    #[error("raffle error: {0}")]
+    Raffle(#[from] RaffleError),
+
+    #[error("scores error: {0}")]
+    Scores(#[from] ScoresError),
+
+    #[error("trivia error: {0}")]
+    Trivia(#[from] TriviaError),
+
+    #[error("trivia bank {bank}: {error}")]
+    TriviaBank {
+        bank: String,
+        #[source]
+        error: TriviaError,
+    },
+
+    #[error("word search session error: {0}")]
+    Session(#[from] SessionError),
+
+    #[error("no module called {0}")]
+    UnknownModule(String),
+
+    #[error("task passed to the wrong module: {0}")]
+    WrongTask(&'static str),
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2::Pool;
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::wordsearch::{list::WordListStore, session::SessionStore};
+
+    fn meta(role: Role) -> Metadata {
+        Metadata {
+            id: "id".into(),
+            channel: "channel".into(),
+            sender: "sender".into(),
+            role,
+        }
+    }
+
+    fn registry() -> ModuleRegistry {
+        let mut modules = ModuleRegistry::new();
+        modules.register(GreetModule::new("oxbow"));
+        let conn_pool = Pool::new(SqliteConnectionManager::memory()).unwrap();
+        modules.register(WordSearchModule::new(
+            '!',
+            WordListStore::new(conn_pool.clone()),
+            SessionStore::new(conn_pool),
+        ));
+        modules
+    }
+
+    #[test]
+    fn available_by_role() {
+        let modules = registry();
+
+        let everyone = modules
+            .available_to("channel", Role::Everyone)
+            .into_iter()
+            .map(|built_in| built_in.name)
+            .collect::<Vec<_>>();
+
+        assert!(everyone.contains(&"help"));
+        assert!(!everyone.contains(&"search"));
+
+        assert!(modules
+            .available_to("channel", Role::Owner)
+            .iter()
+            .any(|built_in| built_in.name == "search"));
+    }
+
+    #[test]
+    fn parse_checks_role() {
+        let modules = registry();
+
+        assert!(matches!(
+            modules.parse("search", &meta(Role::Everyone)),
+            Ok(None)
+        ));
+        assert!(matches!(
+            modules.parse("Search", &meta(Role::Owner)),
+            Ok(Some(("wordsearch", _)))
+        ));
+    }
+
+    #[test]
+    fn usage_error_from_claiming_module() {
+        let modules = registry();
+
+        let err = modules
+            .parse("lower", &meta(Role::Owner))
+            .expect_err("lower without a word should fail to parse");
+
+        assert_eq!(err.keyword, "lower");
+    }
+
+    #[test]
+    fn disabled_module_ignored() {
+        let mut modules = registry();
+        modules.disable("channel", "wordsearch");
+
+        assert!(matches!(
+            modules.parse("search", &meta(Role::Owner)),
+            Ok(None)
+        ));
+        assert!(!modules
+            .available_to("channel", Role::Owner)
+            .iter()
+            .any(|built_in| built_in.name == "search"));
+
+        assert!(modules.is_enabled("other", "wordsearch"));
+    }
+
+    #[test]
+    fn observe_greeting() {
+        let modules = registry();
+
+        let tasks = modules.observe("hi @Oxbow", &meta(Role::Everyone));
+
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].0, "greet");
+    }
+}
@@ -0,0 +1,260 @@
+use std::convert::TryFrom;
+
+use async_trait::async_trait;
+use tap::TapOptional;
+use tracing::{info, instrument, warn};
+
+use crate::{
+    commands::{render_response, required_args, CommandsStore},
+    module::{count, keyword, Module, ModuleError, UsageError},
+    msg::{Metadata, Response, Role},
+    parse::{
+        ast::{CommandCost, MetaCommand, PotentialUser},
+        oxbow::{CommandCostParser, MetaCommandParser, PotentialUserParser},
+    },
+    points::{PointsError, PointsStore},
+    registry::BuiltIn,
+};
+
+const COMMANDS: &[BuiltIn] = &[
+    BuiltIn {
+        name: "command",
+        syntax: &[r#"command <trigger> "response""#],
+        description: "Add or update a custom command",
+        role: Role::Everyone,
+        anchor: "custom-commands",
+    },
+    BuiltIn {
+        name: "cost",
+        syntax: &["cost <trigger> <points>"],
+        description: "Set how many points it costs to run a custom command",
+        role: Role::Moderator,
+        anchor: "points",
+    },
+];
+
+/// Custom commands stored in the database, which respond to a trigger with
+/// some text.
+pub struct CustomCommandsModule {
+    commands: CommandsStore,
+    /// Where points are spent to run commands that cost them, if points are
+    /// enabled.
+    points: Option<PointsStore>,
+    prefix: char,
+    meta_parser: MetaCommandParser,
+    cost_parser: CommandCostParser,
+    trigger_parser: PotentialUserParser,
+}
+
+/// Tasks for the custom commands module.
+#[derive(Debug, Clone)]
+pub enum CustomCommand {
+    /// Add or update a custom command.
+    Set(MetaCommand),
+    /// Set how many points it costs to run a custom command.
+    Cost(CommandCost),
+    /// Run a custom command, if one exists with the given trigger.
+    Run(PotentialUser),
+}
+
+impl CustomCommandsModule {
+    /// Create a `CustomCommandsModule` that stores commands in `commands`, and
+    /// refers to them using `prefix`.
+    pub fn new(commands: CommandsStore, prefix: char) -> Self {
+        Self {
+            commands,
+            points: None,
+            prefix,
+            meta_parser: MetaCommandParser::new(),
+            cost_parser: CommandCostParser::new(),
+            trigger_parser: PotentialUserParser::new(),
+        }
+    }
+
+    /// Make users pay for commands that cost points out of their balances in
+    /// `points`.
+    pub fn points(mut self, points: PointsStore) -> Self {
+        self.points = Some(points);
+        self
+    }
+
+    /// Take the cost of running `trigger` from the sender's balance, returning
+    /// a message to send instead of the command's response if they can't
+    /// afford it.
+    fn pay(&self, trigger: &str, meta: &Metadata) -> Result<Option<String>, ModuleError> {
+        let points = match &self.points {
+            Some(points) => points,
+            None => return Ok(None),
+        };
+
+        let cost = self.commands.get_cost(&meta.channel, trigger)?;
+
+        if cost == 0 {
+            return Ok(None);
+        }
+
+        let reason = format!("ran {}{}", self.prefix, trigger);
+
+        match points.spend(&meta.channel, &meta.sender, cost, &reason) {
+            Ok(_) => Ok(None),
+            Err(PointsError::Insufficient { balance, needed }) => Ok(Some(format!(
+                "@{} {}{} costs {}, but you only have {}",
+                meta.sender,
+                self.prefix,
+                trigger,
+                count(needed, "point", "points"),
+                count(balance, "point", "points"),
+            ))),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl Module for CustomCommandsModule {
+    type Task = CustomCommand;
+
+    fn name(&self) -> &'static str {
+        "commands"
+    }
+
+    fn commands(&self) -> &'static [BuiltIn] {
+        COMMANDS
+    }
+
+    fn fallback(&self) -> bool {
+        true
+    }
+
+    fn parse(&self, input: &str, _meta: &Metadata) -> Result<Option<CustomCommand>, UsageError> {
+        match keyword(input).as_deref() {
+            Some("command") => self
+                .meta_parser
+                .parse(input)
+                .map(|meta_command| Some(CustomCommand::Set(meta_command)))
+                .map_err(|err| UsageError::new("command", input, &err)),
+            Some("cost") => self
+                .cost_parser
+                .parse(input)
+                .map(|cost| Some(CustomCommand::Cost(cost)))
+                .map_err(|err| UsageError::new("cost", input, &err)),
+            _ => {
+                // Anything that doesn't look like a trigger just isn't a custom
+                // command, so there's no need to tell anyone about it.
+                Ok(self
+                    .trigger_parser
+                    .parse(input)
+                    .ok()
+                    .map(CustomCommand::Run))
+            }
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn handle(
+        &self,
+        task: CustomCommand,
+        meta: &Metadata,
+    ) -> Result<Vec<Response>, ModuleError> {
+        match task {
+            CustomCommand::Set(MetaCommand { trigger, response }) => {
+                info!(?meta, ?trigger, ?response, "add command task");
+
+                let already_exists = self
+                    .commands
+                    .get_command(&meta.channel, &trigger)?
+                    .is_some();
+
+                self.commands
+                    .set_command(&meta.channel, &trigger, &response)?;
+
+                let verb = if already_exists { "Updated" } else { "Added" };
+
+                Ok(vec![Response::say(format!(
+                    "{} {}{}",
+                    verb, self.prefix, trigger
+                ))])
+            }
+            CustomCommand::Cost(CommandCost { trigger, cost }) => {
+                info!(?meta, ?trigger, ?cost, "set command cost task");
+
+                let cost = u32::try_from(cost)
+                    .map_err(|_| ModuleError::User("That's too many points!".to_owned()))?;
+
+                let message = if self.commands.set_cost(&meta.channel, &trigger, cost)? {
+                    format!(
+                        "{}{} now costs {}",
+                        self.prefix,
+                        trigger,
+                        count(cost, "point", "points")
+                    )
+                } else {
+                    format!(
+                        "@{} There's no {}{} command",
+                        meta.sender, self.prefix, trigger
+                    )
+                };
+
+                Ok(vec![Response::say(message)])
+            }
+            CustomCommand::Run(PotentialUser {
+                trigger,
+                args,
+                raw_args,
+            }) => {
+                info!(?meta, ?trigger, ?args, "user-defined command task");
+
+                let response = self
+                    .commands
+                    .get_command(&meta.channel, &trigger)?
+                    .tap_none(|| warn!(?meta, ?trigger, "command not found"));
+
+                let response = match response {
+                    Some(response) => response,
+                    None => return Ok(vec![]),
+                };
+
+                let required = required_args(&response);
+
+                let message = if args.len() < required {
+                    format!(
+                        "@{} {}{} needs {}",
+                        meta.sender,
+                        self.prefix,
+                        trigger,
+                        count(required, "argument", "arguments"),
+                    )
+                } else if let Some(refusal) = self.pay(&trigger, meta)? {
+                    refusal
+                } else {
+                    render_response(&response, &meta.sender, &args, &raw_args)
+                };
+
+                Ok(vec![Response::say(message)])
+            }
+        }
+    }
+
+    fn describe(&self, channel: &str, name: &str) -> Result<Option<String>, ModuleError> {
+        let description = self.commands.get_command(channel, name)?.map(|response| {
+            let required = required_args(&response);
+
+            if required > 0 {
+                format!(
+                    "{}{} is a custom command that needs {}",
+                    self.prefix,
+                    name,
+                    count(required, "argument", "arguments"),
+                )
+            } else {
+                format!("{}{} is a custom command", self.prefix, name)
+            }
+        });
+
+        Ok(description)
+    }
+
+    fn list(&self, channel: &str) -> Result<Vec<String>, ModuleError> {
+        Ok(self.commands.list_triggers(channel)?)
+    }
+}
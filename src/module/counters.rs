@@ -0,0 +1,293 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use async_trait::async_trait;
+use tracing::{debug, info, instrument};
+
+use crate::{
+    counters::{Added, CountersError, CountersStore},
+    module::{keyword, Module, ModuleError, UsageError},
+    msg::{Metadata, Response, Role},
+    parse::{
+        ast::{Counter, CounterChange, CounterUse},
+        oxbow::{CounterParser, CounterUseParser},
+    },
+    registry::BuiltIn,
+};
+
+const COMMANDS: &[BuiltIn] = &[BuiltIn {
+    name: "counter",
+    syntax: &[
+        "counter add <name>",
+        "counter remove <name>",
+        r#"counter format <name> "format""#,
+        "counter role <name> <role>",
+    ],
+    description: "Add, remove or change how a counter works",
+    role: Role::Moderator,
+    anchor: "counters",
+}];
+
+/// Tasks for the counters module.
+#[derive(Debug, Clone)]
+pub enum CounterTask {
+    Command(Counter),
+    /// Show or change a counter.
+    Use(CounterUse),
+}
+
+/// Named counters stored in the database, each of which is shown and changed
+/// using its name as a command.
+pub struct CountersModule {
+    prefix: char,
+    store: CountersStore,
+    parser: CounterParser,
+    use_parser: CounterUseParser,
+    /// The channel and name of every counter, so that commands can be checked
+    /// without going to the database.
+    names: Mutex<HashSet<(String, String)>>,
+    /// The names of built-in commands, which would always run instead of a
+    /// counter with the same name.
+    reserved: HashSet<&'static str>,
+}
+
+impl CountersModule {
+    /// Create a `CountersModule` that stores counters in `store`, and refers
+    /// to them using `prefix`.
+    pub fn new(prefix: char, store: CountersStore) -> Self {
+        Self {
+            prefix,
+            store,
+            parser: CounterParser::new(),
+            use_parser: CounterUseParser::new(),
+            names: Mutex::new(HashSet::new()),
+            reserved: COMMANDS.iter().map(|built_in| built_in.name).collect(),
+        }
+    }
+
+    /// Stop counters from being added with any of the names in `names`, such
+    /// as those of other modules' built-in commands.
+    pub fn reserve<I: IntoIterator<Item = &'static str>>(mut self, names: I) -> Self {
+        self.reserved.extend(names);
+        self
+    }
+
+    fn exists(&self, channel: &str, name: &str) -> bool {
+        self.names
+            .lock()
+            .expect("counter names lock poisoned")
+            .contains(&(channel.to_owned(), name.to_owned()))
+    }
+
+    fn configure(&self, command: Counter, meta: &Metadata) -> Result<String, ModuleError> {
+        let channel = &*meta.channel;
+
+        let message = match command {
+            Counter::Add { name } => {
+                if self.reserved.contains(name.as_str()) {
+                    return Err(ModuleError::User(format!(
+                        "There's already a {}{} built-in command",
+                        self.prefix, name
+                    )));
+                }
+
+                let added = self.store.add(channel, &name).map_err(|err| match err {
+                    CountersError::Exists(name) => ModuleError::User(format!(
+                        "There's already a {}{} counter",
+                        self.prefix, name
+                    )),
+                    CountersError::Command(name) => ModuleError::User(format!(
+                        "There's already a {}{} custom command",
+                        self.prefix, name
+                    )),
+                    err => err.into(),
+                })?;
+
+                self.names
+                    .lock()
+                    .expect("counter names lock poisoned")
+                    .insert((channel.to_owned(), name.clone()));
+
+                match added {
+                    Added::New => format!(
+                        "Added counter {}{}, change it with {}{} +1",
+                        self.prefix, name, self.prefix, name
+                    ),
+                    Added::Converted(value) => format!(
+                        "Turned {}{} into a counter, starting at {}",
+                        self.prefix, name, value
+                    ),
+                }
+            }
+            Counter::Remove { name } => {
+                if !self.store.remove(channel, &name)? {
+                    return Err(self.no_counter(&name));
+                }
+
+                self.names
+                    .lock()
+                    .expect("counter names lock poisoned")
+                    .remove(&(channel.to_owned(), name.clone()));
+
+                format!("Removed counter {}{}", self.prefix, name)
+            }
+            Counter::Format { name, format } => {
+                if !self.store.set_format(channel, &name, &format)? {
+                    return Err(self.no_counter(&name));
+                }
+
+                let counter = self
+                    .store
+                    .get(channel, &name)?
+                    .ok_or_else(|| self.no_counter(&name))?;
+
+                format!(
+                    "Changed the format of {}{}, it now looks like: {}",
+                    self.prefix,
+                    name,
+                    counter.render()
+                )
+            }
+            Counter::Role { name, role } => {
+                let role = role.parse::<Role>().map_err(|_| {
+                    ModuleError::User(format!(
+                        "{} isn't a role! Try everyone, subscriber, vip, moderator or broadcaster",
+                        role
+                    ))
+                })?;
+
+                if !self.store.set_role(channel, &name, role)? {
+                    return Err(self.no_counter(&name));
+                }
+
+                match role {
+                    Role::Everyone => format!("Now everyone can change {}{}", self.prefix, name),
+                    role => format!(
+                        "Now anyone who's a {} or higher can change {}{}",
+                        role, self.prefix, name
+                    ),
+                }
+            }
+        };
+
+        Ok(message)
+    }
+
+    fn no_counter(&self, name: &str) -> ModuleError {
+        ModuleError::User(format!("There's no {}{} counter", self.prefix, name))
+    }
+}
+
+#[async_trait]
+impl Module for CountersModule {
+    type Task = CounterTask;
+
+    fn name(&self) -> &'static str {
+        "counters"
+    }
+
+    fn commands(&self) -> &'static [BuiltIn] {
+        COMMANDS
+    }
+
+    fn fallback(&self) -> bool {
+        true
+    }
+
+    /// Load the names of every counter.
+    fn start(&self) -> Result<(), ModuleError> {
+        let counters = self.store.all()?;
+        info!(counters = counters.len(), "loaded counters");
+
+        self.names
+            .lock()
+            .expect("counter names lock poisoned")
+            .extend(counters);
+
+        Ok(())
+    }
+
+    fn parse(&self, input: &str, meta: &Metadata) -> Result<Option<CounterTask>, UsageError> {
+        let keyword = keyword(input).unwrap_or_default();
+
+        if keyword == "counter" {
+            self.parser
+                .parse(input)
+                .map(|command| Some(CounterTask::Command(command)))
+                .map_err(|err| UsageError::new(&keyword, input, &err))
+        } else if self.exists(&meta.channel, &keyword) {
+            self.use_parser
+                .parse(input)
+                .map(|counter_use| Some(CounterTask::Use(counter_use)))
+                .map_err(|err| UsageError::new(&keyword, input, &err))
+        } else {
+            // Anything else might be a custom command.
+            Ok(None)
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn handle(
+        &self,
+        task: CounterTask,
+        meta: &Metadata,
+    ) -> Result<Vec<Response>, ModuleError> {
+        let message = match task {
+            CounterTask::Command(command) => {
+                info!(?meta, ?command, "counter command task");
+
+                self.configure(command, meta)?
+            }
+            CounterTask::Use(CounterUse { name, change }) => {
+                info!(?meta, ?name, ?change, "counter task");
+
+                let counter = match self.store.get(&meta.channel, &name)? {
+                    Some(counter) => counter,
+                    None => return Ok(vec![]),
+                };
+
+                let change = match change {
+                    Some(change) if meta.role >= counter.role => change,
+                    Some(_) => {
+                        debug!(?meta, role = ?counter.role, "not allowed to change counter");
+                        return Ok(vec![]);
+                    }
+                    None => return Ok(vec![Response::say(counter.render())]),
+                };
+
+                let changed = match change {
+                    CounterChange::Add(change) => self.store.change(&meta.channel, &name, change),
+                    CounterChange::Set(value) => self.store.set(&meta.channel, &name, value),
+                };
+
+                match changed {
+                    Ok(Some(counter)) => counter.render(),
+                    Ok(None) => return Ok(vec![]),
+                    Err(CountersError::Overflow(_)) => {
+                        return Err(ModuleError::User(format!(
+                            "{}{} can't go any further!",
+                            self.prefix, name
+                        )))
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+            }
+        };
+
+        Ok(vec![Response::say(message)])
+    }
+
+    fn describe(&self, channel: &str, name: &str) -> Result<Option<String>, ModuleError> {
+        let description = self.store.get(channel, name)?.map(|counter| {
+            format!(
+                "{}{} is a counter, currently at {}",
+                self.prefix, counter.name, counter.value
+            )
+        });
+
+        Ok(description)
+    }
+
+    fn list(&self, channel: &str) -> Result<Vec<String>, ModuleError> {
+        Ok(self.store.list(channel)?)
+    }
+}
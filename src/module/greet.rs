@@ -0,0 +1,65 @@
+use async_trait::async_trait;
+use tracing::{info, instrument};
+
+use crate::{
+    module::{Module, ModuleError, UsageError},
+    msg::{Metadata, Response},
+    registry::BuiltIn,
+};
+
+/// Greeting users who say hi to the bot.
+pub struct GreetModule {
+    mention: String,
+}
+
+/// The task of greeting the sender of a message.
+#[derive(Debug, Clone, Copy)]
+pub struct Greet;
+
+impl GreetModule {
+    /// Create a `GreetModule` that responds to users saying hi to
+    /// `@twitch_name`.
+    pub fn new(twitch_name: &str) -> Self {
+        Self {
+            mention: format!("@{}", twitch_name.to_lowercase()),
+        }
+    }
+}
+
+#[async_trait]
+impl Module for GreetModule {
+    type Task = Greet;
+
+    fn name(&self) -> &'static str {
+        "greet"
+    }
+
+    fn commands(&self) -> &'static [BuiltIn] {
+        &[]
+    }
+
+    fn parse(&self, _input: &str, _meta: &Metadata) -> Result<Option<Greet>, UsageError> {
+        Ok(None)
+    }
+
+    fn observe(&self, text: &str, meta: &Metadata) -> Option<Greet> {
+        let text = text.to_lowercase();
+
+        if text.split_whitespace().any(|ea| ea == "hi") && text.contains(&self.mention) {
+            info!(?meta, ?text, "implicit greeting identified");
+            Some(Greet)
+        } else {
+            None
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn handle(&self, _task: Greet, meta: &Metadata) -> Result<Vec<Response>, ModuleError> {
+        info!(?meta, "implicit greet task");
+
+        Ok(vec![Response::say(format!(
+            "uwu *nuzzles @{}*",
+            meta.sender
+        ))])
+    }
+}
@@ -0,0 +1,189 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tracing::{info, instrument};
+
+use crate::{
+    messagelog::{MessageLog, MessageLogStore, OptOuts},
+    module::{count, keyword, Module, ModuleError, UsageError},
+    msg::{Metadata, Response, Role},
+    parse::{ast::MessageLog as MessageLogCommand, oxbow::MessageLogParser},
+    registry::BuiltIn,
+};
+
+const COMMANDS: &[BuiltIn] = &[
+    BuiltIn {
+        name: "lastseen",
+        syntax: &["lastseen @user"],
+        description: "Show when someone last chatted",
+        role: Role::Everyone,
+        anchor: "message-log",
+    },
+    BuiltIn {
+        name: "firstseen",
+        syntax: &["firstseen @user"],
+        description: "Show when someone first chatted",
+        role: Role::Everyone,
+        anchor: "message-log",
+    },
+    BuiltIn {
+        name: "optout",
+        syntax: &["optout"],
+        description: "Stop your messages being logged, and delete any that were",
+        role: Role::Everyone,
+        anchor: "message-log",
+    },
+    BuiltIn {
+        name: "optin",
+        syntax: &["optin"],
+        description: "Let your messages be logged again",
+        role: Role::Everyone,
+        anchor: "message-log",
+    },
+];
+
+/// Lookups of when chatters were first and last seen in the message log, and
+/// opting out of it.
+pub struct MessageLogModule {
+    parser: MessageLogParser,
+    store: MessageLogStore,
+    log: MessageLog,
+    opt_outs: OptOuts,
+}
+
+impl MessageLogModule {
+    /// Create a `MessageLogModule` that looks up messages in `store` for the
+    /// channels `log` logs, and keeps `opt_outs` up to date.
+    pub fn new(store: MessageLogStore, log: MessageLog, opt_outs: OptOuts) -> Self {
+        Self {
+            parser: MessageLogParser::new(),
+            store,
+            log,
+            opt_outs,
+        }
+    }
+}
+
+#[async_trait]
+impl Module for MessageLogModule {
+    type Task = MessageLogCommand;
+
+    fn name(&self) -> &'static str {
+        "messagelog"
+    }
+
+    fn commands(&self) -> &'static [BuiltIn] {
+        COMMANDS
+    }
+
+    /// Load every user who has opted out, so that their messages aren't
+    /// logged.
+    fn start(&self) -> Result<(), ModuleError> {
+        let opt_outs = self.store.opt_outs()?;
+        info!(opt_outs = opt_outs.len(), "loaded message log opt outs");
+
+        for username in opt_outs {
+            self.opt_outs.insert(&username);
+        }
+
+        Ok(())
+    }
+
+    fn parse(
+        &self,
+        input: &str,
+        _meta: &Metadata,
+    ) -> Result<Option<MessageLogCommand>, UsageError> {
+        self.parser
+            .parse(input)
+            .map(Some)
+            .map_err(|err| UsageError::new(&keyword(input).unwrap_or_default(), input, &err))
+    }
+
+    #[instrument(skip(self))]
+    async fn handle(
+        &self,
+        task: MessageLogCommand,
+        meta: &Metadata,
+    ) -> Result<Vec<Response>, ModuleError> {
+        let message = match task {
+            MessageLogCommand::LastSeen { user } | MessageLogCommand::FirstSeen { user }
+                if !self.log.is_logged(&meta.channel) =>
+            {
+                info!(?meta, ?user, "seen task in unlogged channel");
+
+                return Err(ModuleError::User(
+                    "I don't keep track of who chats in this channel".to_owned(),
+                ));
+            }
+            MessageLogCommand::LastSeen { user } => {
+                info!(?meta, ?user, "last seen task");
+
+                let user = user.to_lowercase();
+                let time = self.store.last_seen(&meta.channel, &user)?;
+
+                seen(&user, time, "last")
+            }
+            MessageLogCommand::FirstSeen { user } => {
+                info!(?meta, ?user, "first seen task");
+
+                let user = user.to_lowercase();
+                let time = self.store.first_seen(&meta.channel, &user)?;
+
+                seen(&user, time, "first")
+            }
+            MessageLogCommand::OptOut => {
+                info!(?meta, "message log opt out task");
+
+                // Stop queueing their messages first, so none can be written
+                // after the old ones are deleted.
+                self.opt_outs.insert(&meta.sender);
+                let deleted = self.store.opt_out(&meta.sender, Utc::now())?;
+
+                format!(
+                    "@{} Your messages won't be logged any more, and {} deleted",
+                    meta.sender,
+                    match deleted {
+                        1 => "1 logged message was".to_owned(),
+                        n => format!("{} logged messages were", n),
+                    },
+                )
+            }
+            MessageLogCommand::OptIn => {
+                info!(?meta, "message log opt in task");
+
+                if !self.store.opt_in(&meta.sender)? {
+                    return Err(ModuleError::User(
+                        "You haven't opted out of the message log".to_owned(),
+                    ));
+                }
+
+                self.opt_outs.remove(&meta.sender);
+
+                format!("@{} Your messages will be logged again", meta.sender)
+            }
+        };
+
+        Ok(vec![Response::say(message)])
+    }
+}
+
+/// Describe when `user` was first or last seen, if they have been.
+fn seen(user: &str, time: Option<DateTime<Utc>>, which: &str) -> String {
+    match time {
+        Some(time) => format!("@{} was {} seen {}", user, which, ago(Utc::now() - time)),
+        None => format!("I haven't seen @{} here", user),
+    }
+}
+
+/// Describe how long ago something happened, e.g. "just now" or "3 hours ago".
+fn ago(elapsed: chrono::Duration) -> String {
+    if elapsed.num_days() > 0 {
+        format!("{} ago", count(elapsed.num_days(), "day", "days"))
+    } else if elapsed.num_hours() > 0 {
+        format!("{} ago", count(elapsed.num_hours(), "hour", "hours"))
+    } else if elapsed.num_minutes() > 0 {
+        format!("{} ago", count(elapsed.num_minutes(), "minute", "minutes"))
+    } else {
+        "just now".to_owned()
+    }
+}
@@ -0,0 +1,224 @@
+use std::{convert::TryFrom, time::Duration};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use tracing::{debug, info, instrument};
+
+use crate::{
+    module::{count, keyword, Module, ModuleError, UsageError},
+    msg::{cooldown::Cooldowns, Metadata, Response, Role},
+    parse::{ast::Points, oxbow::PointsParser},
+    points::{PointsError, PointsStore},
+    registry::BuiltIn,
+};
+
+/// The number of points chatters earn for chatting, by default.
+pub const DEFAULT_CHAT_POINTS: u32 = 10;
+
+/// How long chatters have to wait after earning points for chatting before
+/// they can earn any more, by default.
+pub const DEFAULT_CHAT_INTERVAL: Duration = Duration::from_secs(300);
+
+const COMMANDS: &[BuiltIn] = &[
+    BuiltIn {
+        name: "points",
+        syntax: &["points [@user]"],
+        description: "Show how many points you or someone else has",
+        role: Role::Everyone,
+        anchor: "points",
+    },
+    BuiltIn {
+        name: "give",
+        syntax: &["give @user <amount>"],
+        description: "Give some of your points to someone else",
+        role: Role::Everyone,
+        anchor: "points",
+    },
+    BuiltIn {
+        name: "addpoints",
+        syntax: &["addpoints @user <amount>"],
+        description: "Give someone extra points",
+        role: Role::Moderator,
+        anchor: "points",
+    },
+];
+
+/// Tasks for the points module.
+#[derive(Debug, Clone)]
+pub enum PointsTask {
+    Command(Points),
+    /// Give the sender points for chatting, if they haven't earned any
+    /// recently.
+    Earn,
+}
+
+/// Points that chatters earn by chatting, and can spend on custom commands or
+/// give to each other.
+pub struct PointsModule {
+    parser: PointsParser,
+    store: PointsStore,
+    chat_points: u32,
+    chat_interval: Duration,
+    /// How long each user in each channel has to wait before they might earn
+    /// points for chatting again.
+    ///
+    /// This only saves a trip to the database for most messages; the database
+    /// decides whether points are actually earned.
+    earn_cooldowns: Cooldowns,
+}
+
+impl PointsModule {
+    /// Create a `PointsModule` that stores balances in `store`.
+    pub fn new(store: PointsStore) -> Self {
+        Self {
+            parser: PointsParser::new(),
+            store,
+            chat_points: DEFAULT_CHAT_POINTS,
+            chat_interval: DEFAULT_CHAT_INTERVAL,
+            earn_cooldowns: Cooldowns::new(DEFAULT_CHAT_INTERVAL),
+        }
+    }
+
+    /// Give chatters `amount` points for chatting, at most once every
+    /// `interval`.
+    pub fn earn(mut self, amount: u32, interval: Duration) -> Self {
+        self.chat_points = amount;
+        self.chat_interval = interval;
+        self.earn_cooldowns = Cooldowns::new(interval);
+        self
+    }
+}
+
+#[async_trait]
+impl Module for PointsModule {
+    type Task = PointsTask;
+
+    fn name(&self) -> &'static str {
+        "points"
+    }
+
+    fn commands(&self) -> &'static [BuiltIn] {
+        COMMANDS
+    }
+
+    fn parse(&self, input: &str, _meta: &Metadata) -> Result<Option<PointsTask>, UsageError> {
+        self.parser
+            .parse(input)
+            .map(|points| Some(PointsTask::Command(points)))
+            .map_err(|err| UsageError::new(&keyword(input).unwrap_or_default(), input, &err))
+    }
+
+    fn observe(&self, _text: &str, meta: &Metadata) -> Option<PointsTask> {
+        if self.chat_points > 0 && self.earn_cooldowns.start(meta) {
+            Some(PointsTask::Earn)
+        } else {
+            None
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn handle(
+        &self,
+        task: PointsTask,
+        meta: &Metadata,
+    ) -> Result<Vec<Response>, ModuleError> {
+        let message = match task {
+            PointsTask::Earn => {
+                let earned = self.store.earn(
+                    &meta.channel,
+                    &meta.sender,
+                    self.chat_points,
+                    self.chat_interval,
+                    Utc::now(),
+                )?;
+
+                debug!(?meta, earned, "earn points task");
+
+                return Ok(vec![]);
+            }
+            PointsTask::Command(Points::Balance { user: None }) => {
+                info!(?meta, "points balance task");
+
+                let balance = self.store.balance(&meta.channel, &meta.sender)?;
+
+                format!(
+                    "@{} You have {}",
+                    meta.sender,
+                    count(balance, "point", "points")
+                )
+            }
+            PointsTask::Command(Points::Balance { user: Some(user) }) => {
+                info!(?meta, ?user, "points balance task");
+
+                let user = user.to_lowercase();
+                let balance = self.store.balance(&meta.channel, &user)?;
+
+                format!("@{} has {}", user, count(balance, "point", "points"))
+            }
+            PointsTask::Command(Points::Give { user, amount }) => {
+                info!(?meta, ?user, ?amount, "give points task");
+
+                let user = user.to_lowercase();
+                let amount = points_amount(amount)?;
+
+                if user == *meta.sender {
+                    return Err(ModuleError::User(
+                        "You can't give points to yourself!".to_owned(),
+                    ));
+                }
+
+                match self.store.give(&meta.channel, &meta.sender, &user, amount) {
+                    Ok(balance) => format!(
+                        "@{} gave @{} {}, and has {} left",
+                        meta.sender,
+                        user,
+                        count(amount, "point", "points"),
+                        count(balance, "point", "points"),
+                    ),
+                    Err(PointsError::Insufficient { balance, .. }) => {
+                        return Err(ModuleError::User(format!(
+                            "You can't give away {}, you only have {}",
+                            count(amount, "point", "points"),
+                            count(balance, "point", "points"),
+                        )))
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            PointsTask::Command(Points::Add { user, amount }) => {
+                info!(?meta, ?user, ?amount, "add points task");
+
+                let user = user.to_lowercase();
+                let amount = points_amount(amount)?;
+
+                let balance = self.store.add(
+                    &meta.channel,
+                    &user,
+                    amount,
+                    &format!("added by {}", meta.sender),
+                )?;
+
+                format!(
+                    "Gave @{} {}, they now have {}",
+                    user,
+                    count(amount, "point", "points"),
+                    count(balance, "point", "points")
+                )
+            }
+        };
+
+        Ok(vec![Response::say(message)])
+    }
+}
+
+/// Check that an amount of points given in a command is one that can be moved
+/// between balances.
+fn points_amount(amount: usize) -> Result<u32, ModuleError> {
+    match u32::try_from(amount) {
+        Ok(0) => Err(ModuleError::User(
+            "The amount of points has to be more than 0".to_owned(),
+        )),
+        Ok(amount) => Ok(amount),
+        Err(_) => Err(ModuleError::User("That's too many points!".to_owned())),
+    }
+}
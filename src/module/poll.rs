@@ -0,0 +1,357 @@
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use tracing::{debug, info, instrument};
+
+use crate::{
+    module::{count, keyword, Module, ModuleError, UsageError},
+    msg::{Metadata, Response, Role},
+    parse::{ast::Poll as PollCommand, oxbow::PollParser},
+    poll::Poll,
+    registry::BuiltIn,
+};
+
+/// How often the running totals of a poll are announced, by default.
+pub const DEFAULT_POLL_UPDATES: Duration = Duration::from_secs(30);
+
+const COMMANDS: &[BuiltIn] = &[
+    BuiltIn {
+        name: "poll",
+        syntax: &[
+            r#"poll "question" "option" "option"... [seconds]"#,
+            "poll end",
+        ],
+        description: "Start a poll for chat to vote in, or end the current one",
+        role: Role::Moderator,
+        anchor: "polls",
+    },
+    BuiltIn {
+        name: "vote",
+        syntax: &["vote <number>"],
+        description: "Vote for an option in a poll, which can also be done by typing the option",
+        role: Role::Everyone,
+        anchor: "polls",
+    },
+];
+
+/// Tasks for the poll module.
+#[derive(Debug, Clone)]
+pub enum PollTask {
+    Command(PollCommand),
+    /// A vote for the option at `option` in the poll with the ID `poll`.
+    Vote {
+        poll: u64,
+        option: usize,
+    },
+}
+
+/// Polls that chat votes in, with the running totals announced every so often
+/// until the poll closes.
+pub struct PollModule {
+    prefix: char,
+    parser: PollParser,
+    updates: Duration,
+    next_poll: AtomicU64,
+    polls: Mutex<HashMap<String, RunningPoll>>,
+}
+
+impl PollModule {
+    /// Create a `PollModule`, which refers to its commands using `prefix`.
+    pub fn new(prefix: char) -> Self {
+        Self {
+            prefix,
+            parser: PollParser::new(),
+            updates: DEFAULT_POLL_UPDATES,
+            next_poll: AtomicU64::new(0),
+            polls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Announce the running totals of each poll at most once every `updates`.
+    pub fn updates(mut self, updates: Duration) -> Self {
+        self.updates = updates;
+        self
+    }
+
+    fn start_poll(
+        &self,
+        question: String,
+        options: Vec<String>,
+        seconds: Option<usize>,
+        meta: &Metadata,
+    ) -> Result<String, ModuleError> {
+        let poll = Poll::new(question, options);
+
+        // Votes can be made by typing an option, so no two options can have
+        // the same name.
+        if poll
+            .options()
+            .iter()
+            .enumerate()
+            .any(|(index, option)| poll.option_named(option) != Some(index))
+        {
+            return Err(ModuleError::User(
+                "Every option in a poll has to be different".to_owned(),
+            ));
+        }
+
+        if seconds == Some(0) {
+            return Err(ModuleError::User(
+                "A poll has to last at least 1 second".to_owned(),
+            ));
+        }
+
+        let now = Instant::now();
+        let deadline = seconds
+            .map(|seconds| {
+                u64::try_from(seconds)
+                    .ok()
+                    .filter(|seconds| *seconds <= u64::from(u32::MAX))
+                    .and_then(|seconds| now.checked_add(Duration::from_secs(seconds)))
+                    .ok_or_else(|| {
+                        ModuleError::User("That's not a length of time I can use!".to_owned())
+                    })
+            })
+            .transpose()?;
+
+        let options = poll
+            .options()
+            .iter()
+            .enumerate()
+            .map(|(index, option)| format!("{}. {}", index + 1, option))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let closes = match seconds {
+            Some(seconds) => format!(" (closes in {})", count(seconds, "second", "seconds")),
+            None => String::new(),
+        };
+
+        let message = format!(
+            "Poll time! {} Vote with {}vote <number> or by typing an option: {}{}",
+            poll.question(),
+            self.prefix,
+            options,
+            closes,
+        );
+
+        let mut polls = self.polls.lock().expect("polls lock poisoned");
+
+        if let Some(running) = polls.get(&*meta.channel) {
+            return Ok(format!(
+                "There's already a poll going: {}",
+                running.poll.question()
+            ));
+        }
+
+        polls.insert(
+            meta.channel.to_string(),
+            RunningPoll {
+                id: self.next_poll.fetch_add(1, Ordering::Relaxed),
+                poll,
+                meta: meta.clone(),
+                deadline,
+                next_update: now + self.updates,
+                changed: false,
+            },
+        );
+
+        Ok(message)
+    }
+}
+
+#[async_trait]
+impl Module for PollModule {
+    type Task = PollTask;
+
+    fn name(&self) -> &'static str {
+        "poll"
+    }
+
+    fn commands(&self) -> &'static [BuiltIn] {
+        COMMANDS
+    }
+
+    fn parse(&self, input: &str, _meta: &Metadata) -> Result<Option<PollTask>, UsageError> {
+        self.parser
+            .parse(input)
+            .map(|poll| Some(PollTask::Command(poll)))
+            .map_err(|err| UsageError::new(&keyword(input).unwrap_or_default(), input, &err))
+    }
+
+    fn observe(&self, text: &str, meta: &Metadata) -> Option<PollTask> {
+        let polls = self.polls.lock().expect("polls lock poisoned");
+        let running = polls.get(&*meta.channel)?;
+
+        running
+            .poll
+            .option_named(text)
+            .map(|option| PollTask::Vote {
+                poll: running.id,
+                option,
+            })
+    }
+
+    #[instrument(skip(self))]
+    async fn handle(&self, task: PollTask, meta: &Metadata) -> Result<Vec<Response>, ModuleError> {
+        let message = match task {
+            PollTask::Command(PollCommand::Start {
+                question,
+                options,
+                seconds,
+            }) => {
+                info!(?meta, ?question, ?options, ?seconds, "start poll task");
+
+                self.start_poll(question, options, seconds, meta)?
+            }
+            PollTask::Command(PollCommand::End) => {
+                info!(?meta, "end poll task");
+
+                let running = self
+                    .polls
+                    .lock()
+                    .expect("polls lock poisoned")
+                    .remove(&*meta.channel)
+                    .ok_or_else(|| ModuleError::User("There's no poll going!".to_owned()))?;
+
+                result(&running.poll)
+            }
+            PollTask::Command(PollCommand::Vote(number)) => {
+                let mut polls = self.polls.lock().expect("polls lock poisoned");
+
+                let running = polls
+                    .get_mut(&*meta.channel)
+                    .ok_or_else(|| ModuleError::User("There's no poll to vote in!".to_owned()))?;
+
+                debug!(?meta, number, "vote task");
+
+                let options = running.poll.options().len();
+
+                if number == 0 || !running.poll.vote(&meta.sender, number - 1) {
+                    return Err(ModuleError::User(format!(
+                        "Pick an option from 1 to {}",
+                        options
+                    )));
+                }
+
+                running.changed = true;
+
+                return Ok(vec![]);
+            }
+            PollTask::Vote { poll, option } => {
+                let mut polls = self.polls.lock().expect("polls lock poisoned");
+
+                // The poll might have closed since the vote was made.
+                if let Some(running) = polls
+                    .get_mut(&*meta.channel)
+                    .filter(|running| running.id == poll)
+                {
+                    debug!(?meta, option, "vote task");
+
+                    running.changed |= running.poll.vote(&meta.sender, option);
+                }
+
+                return Ok(vec![]);
+            }
+        };
+
+        Ok(vec![Response::say(message)])
+    }
+
+    async fn tick(&self) -> Result<Vec<(Response, Metadata)>, ModuleError> {
+        let mut polls = self.polls.lock().expect("polls lock poisoned");
+        let now = Instant::now();
+
+        let closed = polls
+            .iter()
+            .filter(|(_, running)| matches!(running.deadline, Some(deadline) if deadline <= now))
+            .map(|(channel, _)| channel.to_owned())
+            .collect::<Vec<_>>();
+
+        let mut responses = closed
+            .into_iter()
+            .filter_map(|channel| polls.remove(&channel))
+            .map(|running| {
+                info!(meta = ?running.meta, "poll closed");
+
+                (Response::say(result(&running.poll)), running.meta)
+            })
+            .collect::<Vec<_>>();
+
+        for running in polls.values_mut() {
+            if running.next_update > now {
+                continue;
+            }
+
+            running.next_update = now + self.updates;
+
+            // There's no need to repeat the same totals.
+            if running.changed {
+                running.changed = false;
+
+                responses.push((
+                    Response::say(format!(
+                        "Poll update: {} {}",
+                        running.poll.question(),
+                        running.poll.totals()
+                    )),
+                    running.meta.clone(),
+                ));
+            }
+        }
+
+        Ok(responses)
+    }
+}
+
+/// A poll that's being voted in.
+struct RunningPoll {
+    id: u64,
+    poll: Poll,
+    /// The metadata of the command that started the poll, used to send updates
+    /// and the result to the same channel.
+    meta: Metadata,
+    deadline: Option<Instant>,
+    next_update: Instant,
+    /// Whether any votes have been made since the totals were last announced.
+    changed: bool,
+}
+
+/// Describe the final result of `poll`.
+fn result(poll: &Poll) -> String {
+    let voters = poll.voters();
+    let leaders = poll.leaders();
+
+    let verdict = match leaders.as_slice() {
+        [] => return format!("Poll closed! {} Nobody voted", poll.question()),
+        [winner] => format!(
+            "{} wins with {} of {}",
+            poll.options()[*winner],
+            poll.tally()[*winner],
+            count(voters, "vote", "votes"),
+        ),
+        [init @ .., last] => format!(
+            "It's a tie between {} and {}",
+            init.iter()
+                .map(|index| poll.options()[*index].as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            poll.options()[*last],
+        ),
+    };
+
+    format!(
+        "Poll closed! {} {}. Final votes: {}",
+        poll.question(),
+        verdict,
+        poll.totals()
+    )
+}
@@ -0,0 +1,130 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use tracing::{info, instrument};
+
+use crate::{
+    module::{Module, ModuleError, UsageError},
+    msg::{Metadata, Response, Role},
+    parse::{ast::Quote, oxbow::QuoteParser},
+    quotes::{QuotesError, QuotesStore},
+    registry::BuiltIn,
+};
+
+/// The maximum number of messages a single quote is allowed to be split into.
+const QUOTE_MAX_PARTS: usize = 2;
+
+const COMMANDS: &[BuiltIn] = &[BuiltIn {
+    name: "quote",
+    syntax: &[r#"quote @user [#key] "text""#, "quote #key", "quote"],
+    description: "Add a quote, or get one by its key or at random",
+    role: Role::Everyone,
+    anchor: "quotes",
+}];
+
+/// Saving and retrieving quotes from users in a channel.
+pub struct QuotesModule {
+    quotes: QuotesStore,
+    parser: QuoteParser,
+}
+
+impl QuotesModule {
+    /// Create a `QuotesModule` that stores quotes in `quotes`.
+    pub fn new(quotes: QuotesStore) -> Self {
+        Self {
+            quotes,
+            parser: QuoteParser::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Module for QuotesModule {
+    type Task = Quote;
+
+    fn name(&self) -> &'static str {
+        "quotes"
+    }
+
+    fn commands(&self) -> &'static [BuiltIn] {
+        COMMANDS
+    }
+
+    fn parse(&self, input: &str, _meta: &Metadata) -> Result<Option<Quote>, UsageError> {
+        self.parser
+            .parse(input)
+            .map(Some)
+            .map_err(|err| UsageError::new("quote", input, &err))
+    }
+
+    #[instrument(skip(self))]
+    async fn handle(&self, task: Quote, meta: &Metadata) -> Result<Vec<Response>, ModuleError> {
+        let message = match task {
+            Quote::Add {
+                username,
+                key,
+                text,
+            } => {
+                info!(?meta, ?username, ?key, ?text, "add quote task");
+
+                let when = Utc::now();
+                let date_str = when.format("%d %b %Y");
+                let time_str = when.format("%H:%M");
+
+                if let Some(key) = key {
+                    self.quotes
+                        .add_quote_keyed(&meta.channel, &username, &key, &text, when)
+                        .map_err(user_error)?;
+
+                    format!("Quote #{key} added from @{username} on {date_str} at {time_str} UTC")
+                } else {
+                    self.quotes
+                        .add_quote_unkeyed(&meta.channel, &username, &text, when)
+                        .map_err(user_error)?;
+
+                    format!("Quote added from @{username} on {date_str} at {time_str} UTC")
+                }
+            }
+            Quote::Get { key } => {
+                info!(?meta, ?key, "get quote by key task");
+
+                if let Some(quote) = self.quotes.get_quote_keyed(&meta.channel, &key)? {
+                    return Ok(vec![Response::Say {
+                        message: format!("{}", quote),
+                        max_parts: Some(QUOTE_MAX_PARTS),
+                    }]);
+                } else {
+                    format!("@{} There's no quote with the key #{}", meta.sender, key)
+                }
+            }
+            Quote::Random => {
+                info!(?meta, "get random quote task");
+
+                if let Some(quote) = self.quotes.get_quote_random(&meta.channel)? {
+                    return Ok(vec![Response::Say {
+                        message: format!("{}", quote),
+                        max_parts: Some(QUOTE_MAX_PARTS),
+                    }]);
+                } else {
+                    format!("@{} There are no quotes here yet", meta.sender)
+                }
+            }
+        };
+
+        Ok(vec![Response::say(message)])
+    }
+}
+
+/// Explain errors that were caused by the user to them, rather than treating
+/// them as internal errors.
+fn user_error(err: QuotesError) -> ModuleError {
+    match err {
+        QuotesError::DuplicateKey { key, .. } => {
+            ModuleError::User(format!("#{} is already used by another quote", key))
+        }
+        QuotesError::DuplicateQuote { username, .. } => ModuleError::User(format!(
+            "That quote from @{} has already been added",
+            username
+        )),
+        err => err.into(),
+    }
+}
@@ -0,0 +1,359 @@
+use std::{collections::HashMap, convert::TryFrom, sync::Mutex};
+
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use tracing::{debug, info, instrument, warn};
+
+use crate::{
+    module::{count, keyword, Module, ModuleError, UsageError},
+    msg::{Metadata, Response, Role},
+    parse::{ast::Raffle as RaffleCommand, oxbow::RaffleParser},
+    raffle::{Raffle, RaffleStore, Winner},
+    registry::BuiltIn,
+};
+
+/// The most times an entry can count for through `!raffle luck`.
+const MAX_LUCK: u32 = 100;
+
+const COMMANDS: &[BuiltIn] = &[BuiltIn {
+    name: "raffle",
+    syntax: &[
+        "raffle start <keyword> [seconds] [exclude]",
+        "raffle draw",
+        "raffle reroll",
+        "raffle luck <role> <weight>",
+    ],
+    description: "Run a raffle that chatters enter by typing a keyword, and draw its winners",
+    role: Role::Moderator,
+    anchor: "raffles",
+}];
+
+/// Tasks for the raffle module.
+#[derive(Debug, Clone)]
+pub enum RaffleTask {
+    Command(RaffleCommand),
+    /// Enter the sender into the raffle with the ID `raffle`.
+    Enter {
+        raffle: i64,
+    },
+}
+
+/// Raffles that chatters enter by typing a keyword, with winners drawn at
+/// random.
+pub struct RaffleModule {
+    prefix: char,
+    parser: RaffleParser,
+    store: RaffleStore,
+    /// The raffle that can be entered in each channel, if there is one.
+    open: Mutex<HashMap<String, OpenRaffle>>,
+}
+
+/// A raffle that can still be entered.
+struct OpenRaffle {
+    raffle: Raffle,
+    /// The metadata of the command that started the raffle, used to announce
+    /// when entries close, or `None` if the raffle was restored at startup.
+    meta: Option<Metadata>,
+}
+
+impl RaffleModule {
+    /// Create a `RaffleModule`, which refers to its commands using `prefix`
+    /// and saves raffles in `store`.
+    pub fn new(prefix: char, store: RaffleStore) -> Self {
+        Self {
+            prefix,
+            parser: RaffleParser::new(),
+            store,
+            open: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn start_raffle(
+        &self,
+        keyword: String,
+        seconds: Option<usize>,
+        exclude_winners: bool,
+        meta: &Metadata,
+    ) -> Result<String, ModuleError> {
+        let mut open = self.open.lock().expect("raffles lock poisoned");
+
+        if let Some(OpenRaffle { raffle, .. }) = open.get(&*meta.channel) {
+            return Ok(format!(
+                "There's already a raffle going! Type {} to enter",
+                raffle.keyword
+            ));
+        }
+
+        let duration = seconds
+            .map(|seconds| {
+                i64::try_from(seconds)
+                    .ok()
+                    .filter(|seconds| *seconds > 0 && *seconds <= i64::from(u32::MAX))
+                    .map(Duration::seconds)
+                    .ok_or_else(|| {
+                        ModuleError::User("That's not a length of time I can use!".to_owned())
+                    })
+            })
+            .transpose()?;
+
+        let now = Utc::now();
+        let raffle = self.store.start(
+            &meta.channel,
+            &keyword,
+            duration.map(|duration| now + duration),
+            exclude_winners,
+            now,
+        )?;
+
+        let mut message = format!("Raffle time! Type {} to enter", raffle.keyword);
+
+        if let Some(seconds) = seconds {
+            message.push_str(&format!(
+                ", entries close in {}",
+                count(seconds, "second", "seconds")
+            ));
+        }
+
+        if exclude_winners {
+            message.push_str(". Anyone who has won a raffle here before can't win this one");
+        }
+
+        open.insert(
+            meta.channel.to_string(),
+            OpenRaffle {
+                raffle,
+                meta: Some(meta.clone()),
+            },
+        );
+
+        Ok(message)
+    }
+
+    /// The most recent raffle in `channel`, closed to any more entries.
+    fn close_current(&self, channel: &str) -> Result<Option<Raffle>, ModuleError> {
+        let mut raffle = match self.store.current(channel)? {
+            Some(raffle) => raffle,
+            None => return Ok(None),
+        };
+
+        if !raffle.closed {
+            self.store.close(raffle.id)?;
+            raffle.closed = true;
+        }
+
+        self.open
+            .lock()
+            .expect("raffles lock poisoned")
+            .remove(channel);
+
+        Ok(Some(raffle))
+    }
+
+    fn no_raffle(&self) -> String {
+        format!(
+            "There hasn't been a raffle here yet! Start one with {}raffle start <keyword>",
+            self.prefix
+        )
+    }
+}
+
+#[async_trait]
+impl Module for RaffleModule {
+    type Task = RaffleTask;
+
+    fn name(&self) -> &'static str {
+        "raffle"
+    }
+
+    fn commands(&self) -> &'static [BuiltIn] {
+        COMMANDS
+    }
+
+    /// Restore raffles that could still be entered when the bot stopped.
+    fn start(&self) -> Result<(), ModuleError> {
+        let raffles = self.store.open()?;
+        let mut open = self.open.lock().expect("raffles lock poisoned");
+
+        for raffle in raffles {
+            info!(channel = %raffle.channel, keyword = %raffle.keyword, "restored raffle");
+
+            open.insert(raffle.channel.clone(), OpenRaffle { raffle, meta: None });
+        }
+
+        Ok(())
+    }
+
+    fn parse(&self, input: &str, _meta: &Metadata) -> Result<Option<RaffleTask>, UsageError> {
+        self.parser
+            .parse(input)
+            .map(|raffle| Some(RaffleTask::Command(raffle)))
+            .map_err(|err| UsageError::new(&keyword(input).unwrap_or_default(), input, &err))
+    }
+
+    fn observe(&self, text: &str, meta: &Metadata) -> Option<RaffleTask> {
+        let open = self.open.lock().expect("raffles lock poisoned");
+        let OpenRaffle { raffle, .. } = open.get(&*meta.channel)?;
+
+        if raffle.is_entry(text) {
+            Some(RaffleTask::Enter { raffle: raffle.id })
+        } else {
+            None
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn handle(
+        &self,
+        task: RaffleTask,
+        meta: &Metadata,
+    ) -> Result<Vec<Response>, ModuleError> {
+        let message = match task {
+            RaffleTask::Enter { raffle } => {
+                let weight = self.store.luck(&meta.channel, meta.role)?;
+                let entered = self.store.enter(raffle, &meta.sender, weight, Utc::now())?;
+
+                debug!(?meta, raffle, weight, entered, "enter raffle task");
+
+                return Ok(vec![]);
+            }
+            RaffleTask::Command(RaffleCommand::Start {
+                keyword,
+                seconds,
+                exclude_winners,
+            }) => {
+                info!(
+                    ?meta,
+                    ?keyword,
+                    ?seconds,
+                    exclude_winners,
+                    "start raffle task"
+                );
+
+                self.start_raffle(keyword, seconds, exclude_winners, meta)?
+            }
+            RaffleTask::Command(RaffleCommand::Draw) => {
+                info!(?meta, "draw raffle task");
+
+                match self.close_current(&meta.channel)? {
+                    None => self.no_raffle(),
+                    Some(raffle) => {
+                        let winner =
+                            self.store
+                                .draw(&raffle, &mut rand::thread_rng(), Utc::now())?;
+
+                        match winner {
+                            Some(winner) => announce(&winner),
+                            None if self.store.entries(raffle.id)? == 0 => {
+                                "Nobody entered the raffle!".to_owned()
+                            }
+                            None => "There's nobody left who can win the raffle!".to_owned(),
+                        }
+                    }
+                }
+            }
+            RaffleTask::Command(RaffleCommand::Reroll) => {
+                info!(?meta, "reroll raffle task");
+
+                match self.close_current(&meta.channel)? {
+                    None => self.no_raffle(),
+                    Some(raffle) => {
+                        let rerolled =
+                            self.store
+                                .reroll(&raffle, &mut rand::thread_rng(), Utc::now())?;
+
+                        match rerolled {
+                            None => "There's no winner to re-roll!".to_owned(),
+                            Some((rerolled, Some(winner))) => {
+                                format!("Re-rolled @{}. {}", rerolled, announce(&winner))
+                            }
+                            Some((rerolled, None)) => format!(
+                                "Re-rolled @{}, but there's nobody left who can win the raffle!",
+                                rerolled
+                            ),
+                        }
+                    }
+                }
+            }
+            RaffleTask::Command(RaffleCommand::Luck { role, weight }) => {
+                info!(?meta, ?role, ?weight, "raffle luck task");
+
+                let role = role.parse::<Role>().map_err(|_| {
+                    ModuleError::User(format!(
+                        "{} isn't a role! Try subscriber, vip, moderator or broadcaster",
+                        role
+                    ))
+                })?;
+
+                let weight = u32::try_from(weight)
+                    .ok()
+                    .filter(|weight| (1..=MAX_LUCK).contains(weight))
+                    .ok_or_else(|| {
+                        ModuleError::User(format!(
+                            "The weight has to be a number from 1 to {}",
+                            MAX_LUCK
+                        ))
+                    })?;
+
+                self.store.set_luck(&meta.channel, role, weight)?;
+
+                format!(
+                    "Raffle entries from a {} now count {}",
+                    role,
+                    count(weight, "time", "times")
+                )
+            }
+        };
+
+        Ok(vec![Response::say(message)])
+    }
+
+    async fn tick(&self) -> Result<Vec<(Response, Metadata)>, ModuleError> {
+        let now = Utc::now();
+
+        let expired = {
+            let mut open = self.open.lock().expect("raffles lock poisoned");
+
+            let channels = open
+                .iter()
+                .filter(|(_, open)| matches!(open.raffle.closes, Some(closes) if closes <= now))
+                .map(|(channel, _)| channel.to_owned())
+                .collect::<Vec<_>>();
+
+            channels
+                .into_iter()
+                .filter_map(|channel| open.remove(&channel))
+                .collect::<Vec<_>>()
+        };
+
+        let mut responses = vec![];
+
+        for OpenRaffle { raffle, meta } in expired {
+            self.store.close(raffle.id)?;
+
+            let entries = self.store.entries(raffle.id)?;
+            info!(channel = %raffle.channel, entries, "raffle entries closed");
+
+            match meta {
+                Some(meta) => responses.push((
+                    Response::say(format!(
+                        "Entries for the raffle are closed, with {}! Draw a winner with {}raffle draw",
+                        count(entries, "entry", "entries"),
+                        self.prefix,
+                    )),
+                    meta,
+                )),
+                None => warn!(channel = %raffle.channel, "closed a restored raffle without announcing it"),
+            }
+        }
+
+        Ok(responses)
+    }
+}
+
+fn announce(winner: &Winner) -> String {
+    format!(
+        "@{} won the raffle, out of {}!",
+        winner.username,
+        count(winner.entries, "entry", "entries")
+    )
+}
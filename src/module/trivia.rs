@@ -0,0 +1,265 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use tracing::{info, instrument};
+
+use crate::{
+    module::{count, keyword, leaderboard, Module, ModuleError, UsageError},
+    msg::{Metadata, Response, Role},
+    parse::{ast::Trivia, oxbow::TriviaParser},
+    registry::BuiltIn,
+    scores::ScoresStore,
+    trivia::{read_bank, Question, TriviaStore},
+};
+
+/// How long chatters have to answer a question, by default.
+pub const DEFAULT_ROUND_TIME: Duration = Duration::from_secs(60);
+
+const COMMANDS: &[BuiltIn] = &[
+    BuiltIn {
+        name: "trivia",
+        syntax: &["trivia [bank]"],
+        description: "Ask a trivia question, from any bank or a particular one",
+        role: Role::Moderator,
+        anchor: "trivia",
+    },
+    BuiltIn {
+        name: "triviascores",
+        syntax: &["triviascores"],
+        description: "Show who has answered the most trivia questions",
+        role: Role::Everyone,
+        anchor: "trivia",
+    },
+];
+
+/// Tasks for the trivia module.
+#[derive(Debug, Clone)]
+pub enum TriviaTask {
+    Command(Trivia),
+    /// A correct answer to the round with the ID `round`.
+    Answer {
+        round: u64,
+    },
+}
+
+/// Asking trivia questions, and giving a point to whoever answers each one
+/// first.
+pub struct TriviaModule {
+    parser: TriviaParser,
+    store: TriviaStore,
+    scores: ScoresStore,
+    /// Question banks to save to the database at startup, by name.
+    imports: Vec<(String, PathBuf)>,
+    round_time: Duration,
+    next_round: AtomicU64,
+    rounds: Mutex<HashMap<String, Round>>,
+}
+
+impl TriviaModule {
+    /// Create a `TriviaModule` that stores questions in `store` and the
+    /// number of questions each user has answered in `scores`.
+    pub fn new(store: TriviaStore, scores: ScoresStore) -> Self {
+        Self {
+            parser: TriviaParser::new(),
+            store,
+            scores,
+            imports: vec![],
+            round_time: DEFAULT_ROUND_TIME,
+            next_round: AtomicU64::new(0),
+            rounds: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Save the questions in the file at `path` to the database at startup,
+    /// in the bank called `bank`.
+    pub fn import_bank<P: AsRef<Path>>(mut self, bank: &str, path: P) -> Self {
+        self.imports
+            .push((bank.to_lowercase(), path.as_ref().to_owned()));
+        self
+    }
+
+    /// Give chatters `round_time` to answer each question.
+    pub fn round_time(mut self, round_time: Duration) -> Self {
+        self.round_time = round_time;
+        self
+    }
+}
+
+#[async_trait]
+impl Module for TriviaModule {
+    type Task = TriviaTask;
+
+    fn name(&self) -> &'static str {
+        "trivia"
+    }
+
+    fn commands(&self) -> &'static [BuiltIn] {
+        COMMANDS
+    }
+
+    /// Import question banks into the database.
+    fn start(&self) -> Result<(), ModuleError> {
+        for (bank, path) in self.imports.iter() {
+            let questions = read_bank(path)
+                .and_then(|questions| {
+                    self.store.set_bank(bank, &questions)?;
+                    Ok(questions)
+                })
+                .map_err(|error| ModuleError::TriviaBank {
+                    bank: bank.to_owned(),
+                    error,
+                })?;
+
+            info!(%bank, questions = questions.len(), "imported trivia questions");
+        }
+
+        Ok(())
+    }
+
+    fn parse(&self, input: &str, _meta: &Metadata) -> Result<Option<TriviaTask>, UsageError> {
+        self.parser
+            .parse(input)
+            .map(|trivia| Some(TriviaTask::Command(trivia)))
+            .map_err(|err| UsageError::new(&keyword(input).unwrap_or_default(), input, &err))
+    }
+
+    fn observe(&self, text: &str, meta: &Metadata) -> Option<TriviaTask> {
+        let rounds = self.rounds.lock().expect("trivia rounds lock poisoned");
+        let round = rounds.get(&*meta.channel)?;
+
+        if round.deadline > Instant::now() && round.question.accepts(text) {
+            Some(TriviaTask::Answer { round: round.id })
+        } else {
+            None
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn handle(
+        &self,
+        task: TriviaTask,
+        meta: &Metadata,
+    ) -> Result<Vec<Response>, ModuleError> {
+        let mut rounds = self.rounds.lock().expect("trivia rounds lock poisoned");
+
+        let message = match task {
+            TriviaTask::Command(Trivia::Start { bank }) => {
+                info!(?meta, ?bank, "start trivia round task");
+
+                if let Some(round) = rounds.get(&*meta.channel) {
+                    format!("There's already a question: {}", round.question.question)
+                } else {
+                    match self.store.random_question(bank.as_deref())? {
+                        Some(question) => {
+                            let seconds = self.round_time.as_secs();
+                            let message = format!(
+                                "Trivia time! {} (you have {})",
+                                question.question,
+                                count(seconds, "second", "seconds"),
+                            );
+
+                            rounds.insert(
+                                meta.channel.to_string(),
+                                Round {
+                                    id: self.next_round.fetch_add(1, Ordering::Relaxed),
+                                    question,
+                                    meta: meta.clone(),
+                                    deadline: Instant::now() + self.round_time,
+                                },
+                            );
+
+                            message
+                        }
+                        None => match bank {
+                            Some(bank) => format!("There are no questions in the {} bank", bank),
+                            None => "There are no trivia questions yet".to_owned(),
+                        },
+                    }
+                }
+            }
+            TriviaTask::Answer { round } => {
+                // Anyone who answered after the first correct answer was too
+                // late.
+                let question = match rounds.get(&*meta.channel) {
+                    Some(current) if current.id == round => {
+                        rounds
+                            .remove(&*meta.channel)
+                            .expect("the round should still be there")
+                            .question
+                    }
+                    _ => return Ok(vec![]),
+                };
+
+                info!(?meta, round, "correct trivia answer task");
+
+                let points = self.scores.add_point(&meta.channel, &meta.sender)?;
+
+                format!(
+                    "@{} got it, the answer was {}! @{} has {}",
+                    meta.sender,
+                    question.answer,
+                    meta.sender,
+                    count(points, "point", "points"),
+                )
+            }
+            TriviaTask::Command(Trivia::Scores) => {
+                info!(?meta, "trivia leaderboard task");
+
+                leaderboard(
+                    &self.scores,
+                    &meta.channel,
+                    "Trivia leaderboard",
+                    "Nobody has answered a trivia question here yet!",
+                )?
+            }
+        };
+
+        Ok(vec![Response::say(message)])
+    }
+
+    /// End any rounds that have run out of time, revealing their answers.
+    async fn tick(&self) -> Result<Vec<(Response, Metadata)>, ModuleError> {
+        let mut rounds = self.rounds.lock().expect("trivia rounds lock poisoned");
+        let now = Instant::now();
+
+        let expired = rounds
+            .iter()
+            .filter(|(_, round)| round.deadline <= now)
+            .map(|(channel, _)| channel.to_owned())
+            .collect::<Vec<_>>();
+
+        Ok(expired
+            .into_iter()
+            .filter_map(|channel| rounds.remove(&channel))
+            .map(|round| {
+                info!(meta = ?round.meta, "trivia round timed out");
+
+                (
+                    Response::say(format!(
+                        "Time's up! The answer was {}",
+                        round.question.answer
+                    )),
+                    round.meta,
+                )
+            })
+            .collect())
+    }
+}
+
+/// A question waiting to be answered in a channel.
+struct Round {
+    id: u64,
+    question: Question,
+    /// The metadata of the command that asked the question, used to send the
+    /// answer to the same channel if nobody gets it.
+    meta: Metadata,
+    deadline: Instant,
+}
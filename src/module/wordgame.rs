@@ -0,0 +1,196 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use async_trait::async_trait;
+use tracing::{debug, info, instrument, trace};
+
+use crate::{
+    module::{count, keyword, leaderboard, Module, ModuleError, UsageError},
+    msg::{cooldown::Cooldowns, Metadata, Response, Role},
+    parse::{ast::WordGame, oxbow::WordGameParser},
+    registry::BuiltIn,
+    scores::ScoresStore,
+    wordgame::{Game, Outcome},
+    wordsearch::list::ChannelLists,
+};
+
+/// How long a user has to wait after guessing before they can guess again, by
+/// default.
+pub const DEFAULT_GUESS_COOLDOWN: Duration = Duration::from_secs(10);
+
+const COMMANDS: &[BuiltIn] = &[
+    BuiltIn {
+        name: "wordgame",
+        syntax: &["wordgame"],
+        description: "Start a word game, where chat has to guess a secret word",
+        role: Role::Moderator,
+        anchor: "word-game",
+    },
+    BuiltIn {
+        name: "guess",
+        syntax: &["guess <word>"],
+        description: "Guess the secret word in a word game",
+        role: Role::Everyone,
+        anchor: "word-game",
+    },
+    BuiltIn {
+        name: "wordscores",
+        syntax: &["wordscores"],
+        description: "Show who has won the most word games",
+        role: Role::Everyone,
+        anchor: "word-game",
+    },
+];
+
+/// Running a word guessing game in each channel, with a leaderboard of the
+/// users who have won the most games.
+pub struct WordGameModule {
+    prefix: char,
+    parser: WordGameParser,
+    scores: ScoresStore,
+    /// The word list used in each channel, which the secret word is picked
+    /// from.
+    lists: ChannelLists,
+    games: Mutex<HashMap<String, Game>>,
+    /// How long each user in each channel has to wait before guessing again.
+    guess_cooldowns: Cooldowns,
+}
+
+impl WordGameModule {
+    /// Create a `WordGameModule`, which refers to its commands using `prefix`,
+    /// picks words from `lists` and saves the number of games each user has
+    /// won in `scores`.
+    pub fn new(prefix: char, lists: ChannelLists, scores: ScoresStore) -> Self {
+        Self {
+            prefix,
+            parser: WordGameParser::new(),
+            scores,
+            lists,
+            games: Mutex::new(HashMap::new()),
+            guess_cooldowns: Cooldowns::new(DEFAULT_GUESS_COOLDOWN),
+        }
+    }
+
+    /// Make users wait for `cooldown` after guessing before they can guess
+    /// again.
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.guess_cooldowns = Cooldowns::new(cooldown);
+        self
+    }
+}
+
+#[async_trait]
+impl Module for WordGameModule {
+    type Task = WordGame;
+
+    fn name(&self) -> &'static str {
+        "wordgame"
+    }
+
+    fn commands(&self) -> &'static [BuiltIn] {
+        COMMANDS
+    }
+
+    fn parse(&self, input: &str, _meta: &Metadata) -> Result<Option<WordGame>, UsageError> {
+        self.parser
+            .parse(input)
+            .map(Some)
+            .map_err(|err| UsageError::new(&keyword(input).unwrap_or_default(), input, &err))
+    }
+
+    #[instrument(skip(self))]
+    async fn handle(&self, task: WordGame, meta: &Metadata) -> Result<Vec<Response>, ModuleError> {
+        let message = match task {
+            WordGame::Start => {
+                info!(?meta, "start word game task");
+
+                let mut games = self.games.lock().expect("games lock poisoned");
+
+                if games.contains_key(&*meta.channel) {
+                    format!(
+                        "There's already a word game going! Guess the word with {}guess <word>",
+                        self.prefix
+                    )
+                } else {
+                    let game = Game::new(self.lists.get(&meta.channel));
+                    trace!(secret = game.secret(), "picked secret word");
+
+                    games.insert(meta.channel.to_string(), game);
+
+                    format!(
+                        "I'm thinking of a word! Guess it with {}guess <word>",
+                        self.prefix
+                    )
+                }
+            }
+            WordGame::Guess(word) => {
+                let mut games = self.games.lock().expect("games lock poisoned");
+
+                let game = match games.get_mut(&*meta.channel) {
+                    Some(game) => game,
+                    None => {
+                        return Ok(vec![Response::say(format!(
+                            "@{} No word game in progress!",
+                            meta.sender
+                        ))])
+                    }
+                };
+
+                // Guesses made too soon are ignored, so that nobody can win by
+                // spamming guesses.
+                if !self.guess_cooldowns.is_ready(meta) {
+                    debug!(?meta, ?word, "guess on cooldown");
+                    return Ok(vec![]);
+                }
+
+                info!(?meta, ?word, "word game guess task");
+
+                let outcome = game.guess(&word);
+
+                // A word that isn't in the list doesn't count as a guess, so a
+                // typo doesn't lock anyone out.
+                if outcome.is_some() {
+                    self.guess_cooldowns.start(meta);
+                }
+
+                match outcome {
+                    None => format!("@{} {} isn't in my word list", meta.sender, word),
+                    Some(Outcome::Higher { distance }) => format!(
+                        "@{} Higher than {}! Distance: {}",
+                        meta.sender, word, distance
+                    ),
+                    Some(Outcome::Lower { distance }) => format!(
+                        "@{} Lower than {}! Distance: {}",
+                        meta.sender, word, distance
+                    ),
+                    Some(Outcome::Correct) => {
+                        let guesses = game.guesses();
+                        games.remove(&*meta.channel);
+
+                        let wins = self.scores.add_point(&meta.channel, &meta.sender)?;
+
+                        format!(
+                            "@{} got it, the word was {}! Found in {}. @{} has won {}",
+                            meta.sender,
+                            word,
+                            count(guesses, "guess", "guesses"),
+                            meta.sender,
+                            count(wins, "game", "games"),
+                        )
+                    }
+                }
+            }
+            WordGame::Scores => {
+                info!(?meta, "word game leaderboard task");
+
+                leaderboard(
+                    &self.scores,
+                    &meta.channel,
+                    "Word game leaderboard",
+                    "Nobody has won a word game here yet!",
+                )?
+            }
+        };
+
+        Ok(vec![Response::say(message)])
+    }
+}
@@ -0,0 +1,534 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Instant,
+};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use tracing::{info, instrument};
+
+use crate::{
+    module::{count, keyword, Module, ModuleError, UsageError},
+    msg::{Metadata, Response, Role},
+    parse::{ast::Search, oxbow::SearchParser},
+    registry::BuiltIn,
+    wordsearch::{
+        auto::{AutoPlay, Feedback},
+        list::{ChannelLists, WordList, WordListSource, WordListStore},
+        session::SessionStore,
+        Bound, Constraint, ConstraintError, Strategy, WordSearch,
+    },
+};
+
+const COMMANDS: &[BuiltIn] = &[
+    BuiltIn {
+        name: "search",
+        syntax: &["search", "search resume", "search status", "search undo"],
+        description: "Start, resume, check on or correct a word search",
+        role: Role::Owner,
+        anchor: "word-search",
+    },
+    BuiltIn {
+        name: "lower",
+        syntax: &["lower <word> [distance]"],
+        description: "Tell the word search the answer comes after a word",
+        role: Role::Owner,
+        anchor: "word-search",
+    },
+    BuiltIn {
+        name: "upper",
+        syntax: &["upper <word> [distance]"],
+        description: "Tell the word search the answer comes before a word",
+        role: Role::Owner,
+        anchor: "word-search",
+    },
+    BuiltIn {
+        name: "found",
+        syntax: &["found"],
+        description: "Stop the word search",
+        role: Role::Owner,
+        anchor: "word-search",
+    },
+];
+
+/// Tasks for the word search module.
+#[derive(Debug, Clone)]
+pub enum WordSearchTask {
+    /// A command to start, resume, stop or steer a search.
+    Command(Search),
+    /// A reply from the game bot to a guess.
+    Feedback(Feedback),
+}
+
+/// Solving the word guessing game run by another bot, with one search per
+/// channel.
+pub struct WordSearchModule {
+    prefix: char,
+    parser: SearchParser,
+    store: WordListStore,
+    sessions: SessionStore,
+    /// Word lists to save to the database at startup, by name.
+    imports: Vec<(String, PathBuf)>,
+    /// Where the word list for each channel comes from, if it isn't the
+    /// built-in list.
+    sources: HashMap<String, WordListSource>,
+    /// The word lists loaded from `sources` at startup.
+    lists: ChannelLists,
+    /// How to play automatically, if the game bot's replies should be read.
+    auto_play: Option<AutoPlay>,
+    word_searches: Mutex<HashMap<String, ChannelSearch>>,
+}
+
+impl WordSearchModule {
+    /// Create a `WordSearchModule`, which refers to its commands using
+    /// `prefix`, can load word lists from `store`, and saves searches in
+    /// `sessions`.
+    pub fn new(prefix: char, store: WordListStore, sessions: SessionStore) -> Self {
+        Self {
+            prefix,
+            parser: SearchParser::new(),
+            store,
+            sessions,
+            imports: vec![],
+            sources: HashMap::new(),
+            lists: ChannelLists::default(),
+            auto_play: None,
+            word_searches: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Use the word list from `source` in `channel`, instead of the built-in
+    /// list.
+    pub fn use_list(mut self, channel: &str, source: WordListSource) -> Self {
+        self.sources.insert(channel.to_owned(), source);
+        self
+    }
+
+    /// Save the word list in the file at `path` to the database at startup,
+    /// with the name `name`.
+    pub fn import_list<P: AsRef<Path>>(mut self, name: &str, path: P) -> Self {
+        self.imports
+            .push((name.to_owned(), path.as_ref().to_owned()));
+        self
+    }
+
+    /// Make guesses automatically after each of the game bot's replies, once
+    /// a search has been started.
+    pub fn auto_play(mut self, auto_play: AutoPlay) -> Self {
+        self.auto_play = Some(auto_play);
+        self
+    }
+
+    /// Make the next guess in a search now.
+    fn guess(&self, channel_search: &mut ChannelSearch) -> Response {
+        match channel_search.search.guess() {
+            Some(guess) => {
+                channel_search.last_guess = Some(Instant::now());
+                Response::say(format!("!wg {}", guess))
+            }
+            None => {
+                channel_search.in_progress = false;
+                Response::say("I've run out of words to guess!".to_owned())
+            }
+        }
+    }
+
+    /// Make the next guess in a search, or if the last guess was too recent,
+    /// leave it for [`Module::tick`] to make once enough time has passed.
+    fn next_guess(&self, channel_search: &mut ChannelSearch, meta: &Metadata) -> Option<Response> {
+        if channel_search.queued.is_some() {
+            return None;
+        }
+
+        match (&self.auto_play, channel_search.last_guess) {
+            (Some(auto_play), Some(last_guess)) if last_guess.elapsed() < auto_play.interval() => {
+                channel_search.queued = Some(meta.clone());
+                None
+            }
+            _ => Some(self.guess(channel_search)),
+        }
+    }
+
+    /// Narrow down the search in `channel`, saving what was learned so the
+    /// search can be resumed later.
+    ///
+    /// Constraints that can't be applied are explained in a
+    /// [`ModuleError::User`].
+    fn constrain(
+        &self,
+        channel: &str,
+        channel_search: &mut ChannelSearch,
+        bound: Bound,
+        word: String,
+        distance: Option<usize>,
+    ) -> Result<(), ModuleError> {
+        let constraint = Constraint {
+            bound,
+            word,
+            distance,
+        };
+
+        match channel_search.search.apply(constraint.clone()) {
+            Ok(()) => {}
+            Err(ConstraintError::UnknownWord(word)) => {
+                return Err(ModuleError::User(format!(
+                    "{} isn't in my word list, so I've ignored it",
+                    word
+                )))
+            }
+            Err(ConstraintError::Contradiction) => {
+                return Err(ModuleError::User(format!(
+                    "That doesn't leave any possible words, so I've ignored it. If an earlier \
+                     answer was wrong, use {}search undo",
+                    self.prefix
+                )))
+            }
+        }
+
+        self.sessions.add_constraint(channel, &constraint)?;
+
+        Ok(())
+    }
+
+    /// The word list used in each channel, once the module has started.
+    pub fn lists(&self) -> ChannelLists {
+        self.lists.clone()
+    }
+
+    /// Describe how far along the search in a channel is.
+    fn status(&self, channel_search: &ChannelSearch) -> String {
+        let search = &channel_search.search;
+
+        let state = if channel_search.in_progress {
+            "in progress".to_owned()
+        } else {
+            format!("paused, use {}search resume to carry on", self.prefix)
+        };
+
+        let bounds = match search.bounds() {
+            (Some(lower), Some(upper)) => format!(", after {} and before {}", lower, upper),
+            (Some(lower), None) => format!(", after {}", lower),
+            (None, Some(upper)) => format!(", before {}", upper),
+            (None, None) => String::new(),
+        };
+
+        format!(
+            "Word search {}: {}{}",
+            state,
+            count(search.remaining(), "possible word", "possible words"),
+            bounds
+        )
+    }
+
+    fn not_in_progress(&self) -> String {
+        format!(
+            "No word search in progress! Start one with {}search",
+            self.prefix
+        )
+    }
+}
+
+#[async_trait]
+impl Module for WordSearchModule {
+    type Task = WordSearchTask;
+
+    fn name(&self) -> &'static str {
+        "wordsearch"
+    }
+
+    fn commands(&self) -> &'static [BuiltIn] {
+        COMMANDS
+    }
+
+    /// Import word lists into the database, then load and check the list for
+    /// every channel that doesn't use the built-in one, and restore the
+    /// searches saved when the bot last stopped.
+    fn start(&self) -> Result<(), ModuleError> {
+        for (name, path) in self.imports.iter() {
+            let list = WordList::from_file(path).map_err(|error| ModuleError::WordList {
+                list: WordListSource::File(path.to_owned()).to_string(),
+                error,
+            })?;
+
+            self.store
+                .set_list(name, &list)
+                .map_err(|error| ModuleError::WordList {
+                    list: WordListSource::Database(name.to_owned()).to_string(),
+                    error,
+                })?;
+
+            info!(%name, words = list.words().len(), "imported word list");
+        }
+
+        for (channel, source) in self.sources.iter() {
+            let list = source
+                .load(&self.store)
+                .map_err(|error| ModuleError::WordList {
+                    list: source.to_string(),
+                    error,
+                })?;
+
+            info!(%channel, %source, words = list.words().len(), "loaded word list");
+
+            self.lists.set(channel, list);
+        }
+
+        let mut word_searches = self
+            .word_searches
+            .lock()
+            .expect("word searches lock poisoned");
+
+        for session in self.sessions.sessions()? {
+            let search = WordSearch::replay(
+                self.lists.get(&session.channel),
+                Strategy::default(),
+                session.history,
+            );
+
+            info!(channel = %session.channel, constraints = search.history().len(), "restored word search");
+
+            // Restored searches wait for `search resume`, since the game may
+            // have moved on while the bot was stopped.
+            word_searches.insert(
+                session.channel,
+                ChannelSearch {
+                    search,
+                    in_progress: false,
+                    last_guess: None,
+                    queued: None,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    fn parse(&self, input: &str, _meta: &Metadata) -> Result<Option<WordSearchTask>, UsageError> {
+        self.parser
+            .parse(input)
+            .map(|search| Some(WordSearchTask::Command(search)))
+            .map_err(|err| UsageError::new(&keyword(input).unwrap_or_default(), input, &err))
+    }
+
+    fn observe(&self, text: &str, meta: &Metadata) -> Option<WordSearchTask> {
+        let auto_play = self.auto_play.as_ref()?;
+
+        if *meta.sender == *auto_play.game_bot() {
+            auto_play.feedback(text).map(WordSearchTask::Feedback)
+        } else {
+            None
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn handle(
+        &self,
+        task: WordSearchTask,
+        meta: &Metadata,
+    ) -> Result<Vec<Response>, ModuleError> {
+        let mut word_searches = self
+            .word_searches
+            .lock()
+            .expect("word searches lock poisoned");
+
+        let response = match task {
+            WordSearchTask::Command(Search::Search) => {
+                info!(?meta, "word search task");
+
+                let channel_search = word_searches
+                    .entry(meta.channel.to_string())
+                    .and_modify(|cs| cs.search.reset())
+                    .or_insert_with(|| ChannelSearch {
+                        search: WordSearch::with_words(
+                            self.lists.get(&meta.channel),
+                            Strategy::default(),
+                        ),
+                        in_progress: false,
+                        last_guess: None,
+                        queued: None,
+                    });
+
+                self.sessions.start(&meta.channel, Utc::now())?;
+                channel_search.in_progress = true;
+
+                self.next_guess(channel_search, meta)
+            }
+            WordSearchTask::Command(Search::Resume) => {
+                info!(?meta, "word search resume task");
+
+                match word_searches.get_mut(&*meta.channel) {
+                    Some(channel_search) => {
+                        channel_search.in_progress = true;
+                        self.next_guess(channel_search, meta)
+                    }
+                    None => Some(Response::say(format!(
+                        "No word search to resume! Start one with {}search",
+                        self.prefix
+                    ))),
+                }
+            }
+            WordSearchTask::Command(Search::Status) => {
+                info!(?meta, "word search status task");
+
+                Some(match word_searches.get(&*meta.channel) {
+                    Some(channel_search) => Response::say(self.status(channel_search)),
+                    None => Response::say(self.not_in_progress()),
+                })
+            }
+            WordSearchTask::Command(Search::Undo) => {
+                info!(?meta, "word search undo task");
+
+                Some(match word_searches.get_mut(&*meta.channel) {
+                    Some(channel_search) => match channel_search.search.undo() {
+                        Some(constraint) => {
+                            self.sessions.undo(&meta.channel)?;
+
+                            let side = match constraint.bound {
+                                Bound::Lower => "after",
+                                Bound::Upper => "before",
+                            };
+
+                            Response::say(format!(
+                                "Forgot that the word is {} {}. {}",
+                                side,
+                                constraint.word,
+                                self.status(channel_search)
+                            ))
+                        }
+                        None => Response::say("There's nothing to undo!".to_owned()),
+                    },
+                    None => Response::say(self.not_in_progress()),
+                })
+            }
+            WordSearchTask::Command(Search::Lower { word, distance }) => {
+                info!(?meta, ?word, "word lower task");
+
+                match word_searches.get_mut(&*meta.channel) {
+                    Some(channel_search) => {
+                        self.constrain(
+                            &meta.channel,
+                            channel_search,
+                            Bound::Lower,
+                            word,
+                            distance,
+                        )?;
+                        self.next_guess(channel_search, meta)
+                    }
+                    None => Some(Response::say(self.not_in_progress())),
+                }
+            }
+            WordSearchTask::Command(Search::Upper { word, distance }) => {
+                info!(?meta, ?word, "word upper task");
+
+                match word_searches.get_mut(&*meta.channel) {
+                    Some(channel_search) => {
+                        self.constrain(
+                            &meta.channel,
+                            channel_search,
+                            Bound::Upper,
+                            word,
+                            distance,
+                        )?;
+                        self.next_guess(channel_search, meta)
+                    }
+                    None => Some(Response::say(self.not_in_progress())),
+                }
+            }
+            WordSearchTask::Command(Search::Found) => {
+                info!(?meta, "word found task");
+
+                Some(match word_searches.remove(&*meta.channel) {
+                    Some(_) => {
+                        self.sessions.end(&meta.channel)?;
+                        Response::say("Word search stopped".to_owned())
+                    }
+                    None => Response::say("No word search in progress!".to_owned()),
+                })
+            }
+            WordSearchTask::Feedback(feedback) => {
+                let channel_search = match word_searches.get_mut(&*meta.channel) {
+                    Some(channel_search) if channel_search.in_progress => channel_search,
+                    _ => return Ok(vec![]),
+                };
+
+                info!(?meta, ?feedback, "word search feedback task");
+
+                let constrained = match feedback {
+                    Feedback::Lower { word, distance } => {
+                        self.constrain(&meta.channel, channel_search, Bound::Lower, word, distance)
+                    }
+                    Feedback::Upper { word, distance } => {
+                        self.constrain(&meta.channel, channel_search, Bound::Upper, word, distance)
+                    }
+                    Feedback::Found => {
+                        word_searches.remove(&*meta.channel);
+                        self.sessions.end(&meta.channel)?;
+                        return Ok(vec![]);
+                    }
+                };
+
+                // The reply came from the game bot, so there's nobody to
+                // mention when it can't be used.
+                if let Err(ModuleError::User(message)) = constrained {
+                    return Ok(vec![Response::say(message)]);
+                }
+                constrained?;
+
+                // Replies to other people's guesses still narrow down the
+                // answer, but if a guess is already waiting to be made, there's
+                // no need for another one.
+                self.next_guess(channel_search, meta)
+            }
+        };
+
+        Ok(response.into_iter().collect())
+    }
+
+    /// Make any guesses that were waiting for the guess interval to pass.
+    async fn tick(&self) -> Result<Vec<(Response, Metadata)>, ModuleError> {
+        let interval = match &self.auto_play {
+            Some(auto_play) => auto_play.interval(),
+            None => return Ok(vec![]),
+        };
+
+        let mut word_searches = self
+            .word_searches
+            .lock()
+            .expect("word searches lock poisoned");
+
+        let mut responses = vec![];
+
+        for channel_search in word_searches.values_mut() {
+            let due = matches!(
+                channel_search.last_guess,
+                Some(last_guess) if last_guess.elapsed() >= interval
+            );
+
+            if !due {
+                continue;
+            }
+
+            if let Some(meta) = channel_search.queued.take() {
+                if channel_search.in_progress {
+                    responses.push((self.guess(channel_search), meta));
+                }
+            }
+        }
+
+        Ok(responses)
+    }
+}
+
+/// The state of the word search in a channel.
+struct ChannelSearch {
+    search: WordSearch,
+    /// Whether a search has been started and the answer hasn't been found yet.
+    in_progress: bool,
+    /// When the last guess was made.
+    last_guess: Option<Instant>,
+    /// The metadata of the task that asked for the next guess, if it's
+    /// waiting for the guess interval to pass.
+    queued: Option<Metadata>,
+}
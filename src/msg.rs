@@ -1,4 +1,15 @@
-use std::{fmt::Debug, sync::Arc};
+pub mod cooldown;
+pub mod split;
+
+use std::{
+    fmt::{self, Debug},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use self::split::{split_message, DEFAULT_MAX_PARTS, MAX_MESSAGE_LEN};
+use crate::module::ModuleTask;
 
 /// Metadata about a task (data that is common to all tasks and helps identify
 /// it through its whole lifecycle).
@@ -10,6 +21,74 @@ pub struct Metadata {
     pub channel: Arc<str>,
     /// The user who sent the command.
     pub sender: Arc<str>,
+    /// The role of the user who sent the command, in the channel it was sent
+    /// in.
+    pub role: Role,
+}
+
+/// The roles a user can have in a channel, in increasing order of privilege.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Role {
+    Everyone,
+    Subscriber,
+    Vip,
+    Moderator,
+    Broadcaster,
+    /// The owner of the bot itself.
+    Owner,
+}
+
+impl Role {
+    /// Determine the role of a user from the names of the badges they have in a
+    /// channel, such as `moderator` or `subscriber`.
+    pub fn from_badges<'a, I>(badges: I) -> Self
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        badges
+            .into_iter()
+            .map(|badge| match badge {
+                "broadcaster" => Role::Broadcaster,
+                "moderator" => Role::Moderator,
+                "vip" => Role::Vip,
+                "subscriber" | "founder" => Role::Subscriber,
+                _ => Role::Everyone,
+            })
+            .max()
+            .unwrap_or(Role::Everyone)
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "everyone" => Ok(Role::Everyone),
+            "subscriber" => Ok(Role::Subscriber),
+            "vip" => Ok(Role::Vip),
+            "moderator" => Ok(Role::Moderator),
+            "broadcaster" => Ok(Role::Broadcaster),
+            "owner" => Ok(Role::Owner),
+            other => Err(format!(
+                "invalid role {:?}, expected everyone, subscriber, vip, moderator, broadcaster or owner",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Everyone => write!(f, "everyone"),
+            Role::Subscriber => write!(f, "subscriber"),
+            Role::Vip => write!(f, "vip"),
+            Role::Moderator => write!(f, "moderator"),
+            Role::Broadcaster => write!(f, "broadcaster"),
+            Role::Owner => write!(f, "owner"),
+        }
+    }
 }
 
 pub trait WithMeta<M> {
@@ -30,77 +109,41 @@ pub trait WithMeta<M> {
 }
 
 /// Tasks to perform, which may or may not result in a [`Response`] being sent.
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub enum Task {
-    /// Respond to an arbitrary `command`, identified from a message beginning
-    /// with a preset prefix.
-    Command {
-        /// The command, not including the prefix.
-        command: String,
+    /// A task produced by one of the bot's modules, to be handled by the same
+    /// module.
+    Module {
+        /// The name of the module that produced the task.
+        module: &'static str,
+        /// The task itself.
+        task: ModuleTask,
     },
-    Implicit(ImplicitTask),
-    BuiltIn(BuiltInCommand),
     Help(Help),
 }
 
 impl WithMeta<Metadata> for Task {}
 
-/// Commands which are not triggered by an explicit message of the form
-/// `!command` by a user, but are instead triggered in response to a heuristic.
-#[derive(Debug, Clone)]
-pub enum ImplicitTask {
-    Greet,
-}
-
-/// Commands that are built in to the bot, rather than being arbitrary commands
-/// stored in the database.
-#[derive(Debug, Clone)]
-pub enum BuiltInCommand {
-    /// Add a new command to the database, with the given trigger and response.
-    AddCommand {
-        /// The string after the prefix that should cause this command to run.
-        trigger: String,
-        /// The response that should be sent in a message.
-        response: String,
-    },
-    /// Add a new quote to the database.
-    AddQuote {
-        /// The username of the user being quoted.
-        username: String,
-        /// The key, if provided, for retrieval of this quote.
-        key: Option<String>,
-        /// The quote itself.
-        text: String,
-    },
-    /// Get a quote by its key.
-    GetQuote {
-        /// The key of the quote to get.
-        key: String,
-    },
-    /// Get a random quote.
-    RandomQuote,
-    /// Start a word search run.
-    WordSearch,
-    /// Set the lower bound after a guess.
-    WordLower {
-        word: String,
-        distance: Option<usize>,
-    },
-    /// Set the upper bound after a guess.
-    WordUpper {
-        word: String,
-        distance: Option<usize>,
-    },
-    /// End a word search run.
-    WordFound,
-}
-
 #[derive(Debug, Clone)]
 pub enum Help {
     /// Respond with general help text.
     General,
-    /// Respond with help text for the quote command.
-    Quote,
+    /// Respond with help text for a built-in command, or a command provided by
+    /// a module.
+    Command {
+        /// The keyword or trigger of the command.
+        name: String,
+    },
+    /// Respond with a list of the commands the user is allowed to run.
+    ListCommands,
+    /// Respond with a hint about how to use a built-in command that failed to
+    /// parse.
+    Usage {
+        /// The keyword of the built-in command.
+        keyword: String,
+        /// What was wrong with the command.
+        hint: String,
+    },
 }
 
 /// Commands to respond in some way to an action, such as by replying with a
@@ -111,7 +154,33 @@ pub enum Response {
     Say {
         /// The message to send.
         message: String,
+        /// The maximum number of parts to split `message` into if it's too long
+        /// to send in one go, or `None` to use [`DEFAULT_MAX_PARTS`].
+        max_parts: Option<usize>,
     },
 }
 
+impl Response {
+    /// Create a response that sends `message`, split into at most
+    /// [`DEFAULT_MAX_PARTS`] parts if it's too long.
+    pub fn say(message: String) -> Self {
+        Response::Say {
+            message,
+            max_parts: None,
+        }
+    }
+
+    /// Split this response into the messages that should actually be sent, in
+    /// the order they should be sent in.
+    pub fn parts(&self) -> Vec<String> {
+        match self {
+            Response::Say { message, max_parts } => split_message(
+                message,
+                MAX_MESSAGE_LEN,
+                max_parts.unwrap_or(DEFAULT_MAX_PARTS),
+            ),
+        }
+    }
+}
+
 impl WithMeta<Metadata> for Response {}
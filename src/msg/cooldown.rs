@@ -0,0 +1,93 @@
+//! Cooldowns that stop each user in each channel from doing something too
+//! often.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::msg::Metadata;
+
+/// A channel and the name of a user in it.
+type ChannelUser = (Arc<str>, Arc<str>);
+
+/// When each user in each channel last started a cooldown of the same length.
+#[derive(Debug)]
+pub struct Cooldowns {
+    duration: Duration,
+    started: Mutex<HashMap<ChannelUser, Instant>>,
+}
+
+impl Cooldowns {
+    /// Create `Cooldowns` that each last for `duration`.
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            started: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check whether the sender of a message is off cooldown in its channel,
+    /// without starting their cooldown.
+    pub fn is_ready(&self, meta: &Metadata) -> bool {
+        let started = self.started.lock().expect("cooldowns lock poisoned");
+
+        match started.get(&(meta.channel.clone(), meta.sender.clone())) {
+            Some(start) => start.elapsed() >= self.duration,
+            None => true,
+        }
+    }
+
+    /// Check whether the sender of a message is off cooldown in its channel,
+    /// and if so, start their cooldown.
+    pub fn start(&self, meta: &Metadata) -> bool {
+        let now = Instant::now();
+        let key = (meta.channel.clone(), meta.sender.clone());
+
+        let mut started = self.started.lock().expect("cooldowns lock poisoned");
+
+        started.retain(|_, start| now.duration_since(*start) < self.duration);
+
+        match started.entry(key) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(now);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::Role;
+
+    fn meta(channel: &str, sender: &str) -> Metadata {
+        Metadata {
+            id: "id".into(),
+            channel: channel.into(),
+            sender: sender.into(),
+            role: Role::Everyone,
+        }
+    }
+
+    #[test]
+    fn per_user_per_channel() {
+        let cooldowns = Cooldowns::new(Duration::from_secs(60));
+
+        assert!(cooldowns.is_ready(&meta("channel", "someone")));
+        assert!(cooldowns.is_ready(&meta("channel", "someone")));
+        assert!(cooldowns.start(&meta("channel", "someone")));
+        assert!(!cooldowns.is_ready(&meta("channel", "someone")));
+        assert!(!cooldowns.start(&meta("channel", "someone")));
+        assert!(cooldowns.start(&meta("channel", "other")));
+        assert!(cooldowns.start(&meta("elsewhere", "someone")));
+
+        let none = Cooldowns::new(Duration::ZERO);
+
+        assert!(none.start(&meta("channel", "someone")));
+        assert!(none.start(&meta("channel", "someone")));
+    }
+}
@@ -0,0 +1,180 @@
+//! Splitting of over-long messages into parts that fit within Twitch's message
+//! length limit.
+
+/// The maximum number of characters Twitch will accept in a single chat
+/// message.
+pub const MAX_MESSAGE_LEN: usize = 500;
+
+/// The number of parts a message is split into if the command that produced it
+/// doesn't specify its own limit.
+pub const DEFAULT_MAX_PARTS: usize = 3;
+
+/// Marker appended to the last part when a message had to be cut short.
+const ELLIPSIS: char = '…';
+
+/// Split `message` into parts of at most `max_len` characters each, numbered
+/// like `(1/3)`, breaking only on whitespace where possible.
+///
+/// Messages that already fit are returned unchanged as a single part. Words are
+/// never split unless a single word is longer than a whole part on its own, and
+/// even then the split is made on a character boundary. If more than
+/// `max_parts` parts would be needed, the remainder is dropped and the last
+/// part ends with an ellipsis instead.
+pub fn split_message(message: &str, max_len: usize, max_parts: usize) -> Vec<String> {
+    if message.chars().count() <= max_len {
+        return vec![message.to_owned()];
+    }
+
+    let max_parts = max_parts.max(1);
+
+    // Reserve room for the widest possible `(n/n) ` prefix.
+    let prefix_len = 2 * max_parts.to_string().len() + 4;
+    let width = max_len.saturating_sub(prefix_len).max(1);
+
+    let mut chunks = chunk_words(message, width);
+
+    if chunks.len() > max_parts {
+        chunks.truncate(max_parts);
+
+        if let Some(last) = chunks.last_mut() {
+            *last = truncate_with_ellipsis(last, width);
+        }
+    }
+
+    let total = chunks.len();
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| format!("({}/{}) {}", i + 1, total, chunk))
+        .collect()
+}
+
+/// Greedily pack the whitespace-separated words of `message` into chunks of at
+/// most `width` characters.
+fn chunk_words(message: &str, width: usize) -> Vec<String> {
+    let mut chunks = vec![];
+    let mut current = String::new();
+    let mut current_len = 0;
+
+    for word in message.split_whitespace() {
+        let word_len = word.chars().count();
+
+        if current_len > 0 && current_len + 1 + word_len <= width {
+            current.push(' ');
+            current.push_str(word);
+            current_len += 1 + word_len;
+            continue;
+        }
+
+        if current_len > 0 {
+            chunks.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+
+        if word_len <= width {
+            current.push_str(word);
+            current_len = word_len;
+        } else {
+            // The word can't fit in a part on its own, so it has to be broken up,
+            // but only between characters.
+            let chars = word.chars().collect::<Vec<_>>();
+            let mut pieces = chars.chunks(width).map(|ea| ea.iter().collect::<String>());
+            let last = pieces.next_back();
+
+            chunks.extend(pieces);
+
+            if let Some(last) = last {
+                current_len = last.chars().count();
+                current = last;
+            }
+        }
+    }
+
+    if current_len > 0 {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+/// Shorten `chunk` by whole words until an ellipsis fits within `width`
+/// characters, then append one.
+fn truncate_with_ellipsis(chunk: &str, width: usize) -> String {
+    let mut words = chunk.split(' ').collect::<Vec<_>>();
+
+    while words.len() > 1 && words.join(" ").chars().count() + 2 > width {
+        words.pop();
+    }
+
+    let kept = words.join(" ");
+
+    if kept.chars().count() + 2 > width {
+        // A single word fills the whole part, so cut it at a character boundary.
+        let cut = kept
+            .chars()
+            .take(width.saturating_sub(1))
+            .collect::<String>();
+        format!("{}{}", cut, ELLIPSIS)
+    } else {
+        format!("{} {}", kept, ELLIPSIS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_message_unchanged() {
+        let parts = split_message("hello there", MAX_MESSAGE_LEN, DEFAULT_MAX_PARTS);
+
+        assert_eq!(parts, vec!["hello there".to_owned()]);
+    }
+
+    #[test]
+    fn splits_on_word_boundaries() {
+        let message = "Kappa PogChamp LUL Kappa PogChamp LUL";
+        let parts = split_message(message, 24, 10);
+
+        assert_eq!(
+            parts,
+            vec![
+                "(1/3) Kappa PogChamp".to_owned(),
+                "(2/3) LUL Kappa".to_owned(),
+                "(3/3) PogChamp LUL".to_owned(),
+            ]
+        );
+
+        for part in parts {
+            assert!(part.chars().count() <= 24, "part too long: {:?}", part);
+        }
+    }
+
+    #[test]
+    fn never_splits_mid_character() {
+        let message = "ø".repeat(30);
+        let parts = split_message(&message, 20, 10);
+
+        assert!(parts.len() > 1, "message should have been split");
+
+        for part in parts {
+            assert!(part.chars().count() <= 20, "part too long: {:?}", part);
+        }
+    }
+
+    #[test]
+    fn respects_max_parts() {
+        let message = "word ".repeat(100);
+        let parts = split_message(&message, 30, 2);
+
+        assert_eq!(parts.len(), 2, "there should be exactly max_parts parts");
+        assert!(parts[0].starts_with("(1/2) "));
+        assert!(parts[1].starts_with("(2/2) "));
+        assert!(parts[1].ends_with(ELLIPSIS));
+
+        for part in parts {
+            assert!(part.chars().count() <= 30, "part too long: {:?}", part);
+        }
+    }
+}
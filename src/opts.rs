@@ -8,12 +8,22 @@ pub struct Opts {
     pub database: Option<String>,
 
     /// The client ID to use for authentication with the Twitch API.
-    #[clap(long = "id", env = "CLIENT_ID", hide_env_values = true)]
-    pub client_id: String,
+    #[clap(
+        long = "id",
+        env = "CLIENT_ID",
+        hide_env_values = true,
+        required_unless_present = "console"
+    )]
+    pub client_id: Option<String>,
 
     /// The client secret to use for authentication with the Twitch API.
-    #[clap(long = "secret", env = "CLIENT_SECRET", hide_env_values = true)]
-    pub client_secret: String,
+    #[clap(
+        long = "secret",
+        env = "CLIENT_SECRET",
+        hide_env_values = true,
+        required_unless_present = "console"
+    )]
+    pub client_secret: Option<String>,
 
     /// The username of the account to post as in Twitch chat.
     #[clap(
@@ -28,6 +38,119 @@ pub struct Opts {
     #[clap(long, default_value = "!")]
     pub prefix: char,
 
+    /// The Twitch username of the bot's owner, who can run every command.
+    #[clap(long, env = "OWNER", default_value = "nerosnm")]
+    pub owner: String,
+
+    /// The URL of the page that help responses link to.
+    #[clap(long, env = "HELP_URL", default_value = "oxbow.cacti.dev/commands")]
+    pub help_url: String,
+
+    /// Disable a module in a channel, given as `channel:module`, such as
+    /// `nerosnm:wordsearch`. Can be given more than once.
+    #[clap(long = "disable", value_name = "CHANNEL:MODULE", number_of_values = 1)]
+    pub disable: Vec<String>,
+
+    /// Use a different word list for word searches in a channel, given as
+    /// `channel:source`. The source can be `builtin`, `file:PATH` or `db:NAME`,
+    /// such as `nerosnm:file:words.txt`. Can be given more than once.
+    #[clap(
+        long = "word-list",
+        value_name = "CHANNEL:SOURCE",
+        number_of_values = 1
+    )]
+    pub word_lists: Vec<String>,
+
+    /// Save the word list in a file to the database at startup, given as
+    /// `name:path`, so that channels can use it as `db:NAME`. Can be given more
+    /// than once.
+    #[clap(
+        long = "import-word-list",
+        value_name = "NAME:PATH",
+        number_of_values = 1
+    )]
+    pub word_list_imports: Vec<String>,
+
+    /// The Twitch username of the bot running the word search game. If this is
+    /// given, word searches are played automatically by reading its replies
+    /// to each guess.
+    #[clap(long, env = "GAME_BOT")]
+    pub game_bot: Option<String>,
+
+    /// The pattern for the game bot's reply when the answer comes after a
+    /// guess, with a `word` group for the guess and an optional `distance`
+    /// group.
+    #[clap(long)]
+    pub lower_pattern: Option<String>,
+
+    /// The pattern for the game bot's reply when the answer comes before a
+    /// guess, with a `word` group for the guess and an optional `distance`
+    /// group.
+    #[clap(long)]
+    pub upper_pattern: Option<String>,
+
+    /// The pattern for the game bot's reply when the answer has been found.
+    #[clap(long)]
+    pub found_pattern: Option<String>,
+
+    /// The shortest time between two automatic guesses in the same channel,
+    /// in seconds.
+    #[clap(long, default_value = "3")]
+    pub guess_interval: u64,
+
+    /// How long a chatter has to wait between guesses in a word game, in
+    /// seconds.
+    #[clap(long, default_value = "10")]
+    pub word_game_cooldown: u64,
+
+    /// Save the trivia questions in a JSON or CSV file to the database at
+    /// startup, given as `bank:path`. Can be given more than once.
+    #[clap(long = "import-trivia", value_name = "BANK:PATH", number_of_values = 1)]
+    pub trivia_imports: Vec<String>,
+
+    /// How long chatters have to answer each trivia question, in seconds.
+    #[clap(long, default_value = "60")]
+    pub trivia_time: u64,
+
+    /// How many points chatters earn for chatting. Set to 0 to stop chatters
+    /// earning points.
+    #[clap(long, default_value = "10")]
+    pub chat_points: u32,
+
+    /// How long a chatter has to wait after earning points for chatting before
+    /// they can earn any more, in seconds.
+    #[clap(long, default_value = "300")]
+    pub chat_points_interval: u64,
+
+    /// How often the running totals of a poll are announced while it's open,
+    /// in seconds.
+    #[clap(long, default_value = "30")]
+    pub poll_updates: u64,
+
+    /// Log the messages sent in a channel, so that `lastseen` and `firstseen`
+    /// can be used there. Can be given more than once.
+    #[clap(long = "log-messages", value_name = "CHANNEL", number_of_values = 1)]
+    pub logged_channels: Vec<String>,
+
+    /// How long logged messages are kept for, in days. Set to 0 to keep them
+    /// forever.
+    #[clap(long, default_value = "30")]
+    pub message_log_retention: u64,
+
+    /// Run the bot locally instead of connecting to Twitch, reading chat
+    /// messages from stdin and printing responses to stdout.
+    #[clap(long)]
+    pub console: bool,
+
+    /// The user that messages read from stdin are sent by in console mode.
+    /// Defaults to the owner.
+    #[clap(long, requires = "console")]
+    pub console_user: Option<String>,
+
+    /// The channel that messages read from stdin are sent in, in console mode.
+    #[clap(long, default_value = "console")]
+    pub console_channel: String,
+
     /// A space-separated list of channels to join.
     pub channels: Vec<String>,
 }
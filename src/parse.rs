@@ -1,4 +1,5 @@
 pub mod ast;
+pub mod usage;
 
 #[doc(hidden)]
 pub use generated::oxbow;
@@ -14,11 +15,35 @@ mod generated {
     lalrpop_mod!(pub oxbow);
 }
 
+/// Strip the opening and closing quotes from a `QUOTED` token, and replace
+/// each backslash escape with the character it escapes.
+pub(crate) fn unquote(quoted: &str) -> String {
+    let mut chars = quoted.chars();
+    chars.next();
+    chars.next_back();
+
+    let mut unquoted = String::with_capacity(quoted.len());
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+
+    unquoted
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::parse::ast::Quote;
+    use crate::parse::ast::{
+        CounterChange, CounterUse, Poll, PotentialUser, Quote, Raffle, WordGame,
+    };
 
-    use super::oxbow::QuoteParser;
+    use super::oxbow::{
+        CounterUseParser, PollParser, PotentialUserParser, QuoteParser, RaffleParser,
+        WordGameParser,
+    };
 
     #[test]
     fn quote_simple() {
@@ -52,6 +77,54 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn quote_escaped() {
+        let input = r#"quote @nerosnm "she said \"hi\" and left a \\ behind""#;
+        let expected = Quote::Add {
+            username: "nerosnm".into(),
+            key: None,
+            text: r#"she said "hi" and left a \ behind"#.into(),
+        };
+
+        let actual = QuoteParser::new()
+            .parse(input)
+            .expect("valid input parses successfully");
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn quote_smart_quotes() {
+        let input = "quote @nerosnm “it’s not a bug, it’s a feature” #feature";
+        let expected = Quote::Add {
+            username: "nerosnm".into(),
+            key: Some("feature".into()),
+            text: "it’s not a bug, it’s a feature".into(),
+        };
+
+        let actual = QuoteParser::new()
+            .parse(input)
+            .expect("valid input parses successfully");
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn quote_guillemets() {
+        let input = r#"quote @nerosnm «the "run" rames»"#;
+        let expected = Quote::Add {
+            username: "nerosnm".into(),
+            key: None,
+            text: r#"the "run" rames"#.into(),
+        };
+
+        let actual = QuoteParser::new()
+            .parse(input)
+            .expect("valid input parses successfully");
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn quote_keyword_start() {
         let input = r#"quote @nerosnm #test-quote "this is a test quote""#;
@@ -83,4 +156,128 @@ mod tests {
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn custom_command_arguments() {
+        let input = r#"hug @friend "really tightly" <3"#;
+        let expected = PotentialUser {
+            trigger: "hug".into(),
+            args: vec!["@friend".into(), "really tightly".into(), "<3".into()],
+            raw_args: r#"@friend "really tightly" <3"#.into(),
+        };
+
+        let actual = PotentialUserParser::new()
+            .parse(input)
+            .expect("valid input parses successfully");
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn keyword_any_case() {
+        let input = "Quote #run";
+        let expected = Quote::Get { key: "run".into() };
+
+        let actual = QuoteParser::new()
+            .parse(input)
+            .expect("valid input parses successfully");
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn trigger_digits_underscores() {
+        for (input, trigger) in [("dc2", "dc2"), ("First_Time", "first_time")] {
+            let actual = PotentialUserParser::new()
+                .parse(input)
+                .expect("valid input parses successfully");
+
+            assert_eq!(actual.trigger, trigger);
+        }
+    }
+
+    #[test]
+    fn sub_keyword_trigger() {
+        let actual = PotentialUserParser::new()
+            .parse("Resume")
+            .expect("valid input parses successfully");
+
+        assert_eq!(actual.trigger, "resume");
+    }
+
+    #[test]
+    fn raffle_start() {
+        let expected = Raffle::Start {
+            keyword: "join".into(),
+            seconds: Some(60),
+            exclude_winners: true,
+        };
+
+        let actual = RaffleParser::new()
+            .parse("raffle start Join 60 exclude")
+            .expect("valid input parses successfully");
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn poll_options() {
+        let expected = Poll::Start {
+            question: "Best snack?".into(),
+            options: vec!["crisps".into(), "fruit".into(), "cake".into()],
+            seconds: Some(90),
+        };
+
+        let actual = PollParser::new()
+            .parse(r#"poll "Best snack?" "crisps" “fruit” "cake" 90"#)
+            .expect("valid input parses successfully");
+
+        assert_eq!(actual, expected);
+
+        PollParser::new()
+            .parse(r#"poll "Best snack?" "crisps""#)
+            .expect_err("a poll with one option should fail to parse");
+    }
+
+    #[test]
+    fn counter_changes() {
+        for (input, change) in [
+            ("deaths", None),
+            ("deaths +1", Some(CounterChange::Add(1))),
+            ("deaths -12", Some(CounterChange::Add(-12))),
+            ("deaths set 10", Some(CounterChange::Set(10))),
+            ("deaths set -3", Some(CounterChange::Set(-3))),
+            ("Deaths reset", Some(CounterChange::Set(0))),
+        ] {
+            let actual = CounterUseParser::new()
+                .parse(input)
+                .expect("valid input parses successfully");
+
+            assert_eq!(
+                actual,
+                CounterUse {
+                    name: "deaths".into(),
+                    change,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn signed_number_argument() {
+        let actual = PotentialUserParser::new()
+            .parse("score -5")
+            .expect("valid input parses successfully");
+
+        assert_eq!(actual.args, vec!["-5".to_owned()]);
+    }
+
+    #[test]
+    fn keyword_guess() {
+        let actual = WordGameParser::new()
+            .parse("guess Draw")
+            .expect("valid input parses successfully");
+
+        assert_eq!(actual, WordGame::Guess("draw".into()));
+    }
 }
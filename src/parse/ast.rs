@@ -1,12 +1,3 @@
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum Command {
-    Quote(Quote),
-    Meta(MetaCommand),
-    Search(Search),
-    PotentialUser(PotentialUser),
-    Help(Help),
-}
-
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Quote {
     Add {
@@ -26,9 +17,18 @@ pub struct MetaCommand {
     pub response: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CommandCost {
+    pub trigger: String,
+    pub cost: usize,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Search {
     Search,
+    Resume,
+    Status,
+    Undo,
     Lower {
         word: String,
         distance: Option<usize>,
@@ -40,13 +40,97 @@ pub enum Search {
     Found,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum WordGame {
+    Start,
+    Guess(String),
+    Scores,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Trivia {
+    Start { bank: Option<String> },
+    Scores,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Points {
+    Balance { user: Option<String> },
+    Give { user: String, amount: usize },
+    Add { user: String, amount: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Raffle {
+    Start {
+        keyword: String,
+        seconds: Option<usize>,
+        /// Whether people who have won an earlier raffle in the channel can't
+        /// win this one.
+        exclude_winners: bool,
+    },
+    Draw,
+    Reroll,
+    Luck {
+        role: String,
+        weight: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Poll {
+    Start {
+        question: String,
+        options: Vec<String>,
+        seconds: Option<usize>,
+    },
+    End,
+    /// A vote for an option, numbered from 1.
+    Vote(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Counter {
+    Add { name: String },
+    Remove { name: String },
+    Format { name: String, format: String },
+    Role { name: String, role: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CounterUse {
+    pub name: String,
+    /// How to change the counter, or `None` to just show it.
+    pub change: Option<CounterChange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CounterChange {
+    Add(i64),
+    Set(i64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MessageLog {
+    LastSeen { user: String },
+    FirstSeen { user: String },
+    OptOut,
+    OptIn,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PotentialUser {
     pub trigger: String,
+    /// The arguments after the trigger, with any quotes around them removed.
+    pub args: Vec<String>,
+    /// The arguments after the trigger as they were typed, separated by single
+    /// spaces.
+    pub raw_args: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Help {
     General,
-    Quote,
+    Command(String),
+    ListCommands,
 }
@@ -0,0 +1,134 @@
+//! Usage hints for built-in commands that failed to parse.
+
+use lalrpop_util::{lexer::Token, ParseError};
+
+/// Build a one-line hint explaining why `input` (the command text, without the
+/// prefix) failed to parse, using the tokens the grammar expected at the point
+/// where it failed.
+///
+/// Positions are given in characters from the start of the whole message,
+/// counting from 1 and including the one-character prefix.
+pub fn usage_hint(input: &str, err: &ParseError<usize, Token<'_>, &str>) -> String {
+    let position = |location: usize| input[..location].chars().count() + 2;
+
+    match err {
+        ParseError::UnrecognizedToken {
+            token: (start, Token(_, found), _),
+            expected,
+        } => format!(
+            "expected {} at character {}, but found {}",
+            describe_expected(expected),
+            position(*start),
+            found,
+        ),
+        ParseError::UnrecognizedEOF { location, expected } => format!(
+            "expected {} at character {}, but the command ended",
+            describe_expected(expected),
+            position(*location),
+        ),
+        ParseError::ExtraToken {
+            token: (start, Token(_, found), _),
+        } => format!(
+            "unexpected {} at character {}, the command should end before it",
+            found,
+            position(*start),
+        ),
+        ParseError::InvalidToken { location } => format!(
+            "didn't understand the text at character {}",
+            position(*location),
+        ),
+        ParseError::User { error } => error.to_string(),
+    }
+}
+
+/// Describe a list of terminal names from the grammar in a way that makes
+/// sense to a user.
+fn describe_expected(expected: &[String]) -> String {
+    let mut described = Vec::<String>::new();
+
+    // Keywords can be used wherever a word can, so there's no need to list
+    // them all if any word will do.
+    let any_word = expected
+        .iter()
+        .any(|terminal| matches!(terminal.trim_matches('"'), "ALPHA" | "WORD"));
+
+    for terminal in expected {
+        if any_word && terminal.trim_matches('"').starts_with("CMD_") {
+            continue;
+        }
+
+        let description = describe_terminal(terminal);
+
+        if !described.contains(&description) {
+            described.push(description);
+        }
+    }
+
+    match described.as_slice() {
+        [] => "nothing".to_owned(),
+        [only] => only.clone(),
+        [init @ .., last] => format!("{} or {}", init.join(", "), last),
+    }
+}
+
+fn describe_terminal(terminal: &str) -> String {
+    match terminal.trim_matches('"') {
+        "ALPHA" => "a word".to_owned(),
+        "POS_NUM" => "a number".to_owned(),
+        "SIGNED_NUM" => "a number like +1 or -1".to_owned(),
+        "QUOTED" => "\"text in quotes\"".to_owned(),
+        "KEY" => "a #key".to_owned(),
+        "USERNAME" => "an @username".to_owned(),
+        "WORD" => "a word".to_owned(),
+        "TEXT" => "some text".to_owned(),
+        other => other
+            .strip_prefix("CMD_")
+            .map(str::to_lowercase)
+            .unwrap_or_else(|| other.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::oxbow::{PointsParser, QuoteParser, SearchParser};
+
+    #[test]
+    fn quote_missing_quotes() {
+        assert_eq!(
+            usage_hint(
+                "quote @nerosnm hi there",
+                &QuoteParser::new()
+                    .parse("quote @nerosnm hi there")
+                    .expect_err("quote without quotes should fail to parse"),
+            ),
+            "expected a #key or \"text in quotes\" at character 17, but found hi"
+        );
+    }
+
+    #[test]
+    fn lower_missing_word() {
+        assert_eq!(
+            usage_hint(
+                "lower",
+                &SearchParser::new()
+                    .parse("lower")
+                    .expect_err("lower without a word should fail to parse"),
+            ),
+            "expected a word at character 7, but the command ended"
+        );
+    }
+
+    #[test]
+    fn number_too_big() {
+        assert_eq!(
+            usage_hint(
+                "give @nerosnm 99999999999999999999999",
+                &PointsParser::new()
+                    .parse("give @nerosnm 99999999999999999999999")
+                    .expect_err("a number that doesn't fit should fail to parse"),
+            ),
+            "that number is too big"
+        );
+    }
+}
@@ -0,0 +1,422 @@
+//! Points that chatters earn by chatting in a channel, and can spend or give
+//! away.
+//!
+//! Every change to a balance is made in a single immediate transaction along
+//! with an entry in the transaction log, so balances can be audited and can't
+//! be corrupted by several changes happening at once.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, OptionalExtension, Transaction, TransactionBehavior};
+use thiserror::Error;
+
+/// An entry in the transaction log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PointsTransaction {
+    pub username: String,
+    /// The change in the user's balance, which is negative if they spent or
+    /// gave away points.
+    pub amount: i64,
+    pub reason: String,
+    pub time: DateTime<Utc>,
+}
+
+/// Storage of points balances and the transaction log in an SQLite3 database.
+#[derive(Debug, Clone)]
+pub struct PointsStore {
+    conn_pool: Pool<SqliteConnectionManager>,
+}
+
+impl PointsStore {
+    /// Create a `PointsStore` with a connection to a database.
+    pub fn new(conn_pool: Pool<SqliteConnectionManager>) -> Self {
+        Self { conn_pool }
+    }
+
+    /// The number of points `username` has in `channel`.
+    pub fn balance(&self, channel: &str, username: &str) -> Result<u32, PointsError> {
+        let conn = self.conn_pool.get()?;
+
+        let balance = conn
+            .query_row(
+                r#"
+                SELECT balance
+                FROM points_balances
+                WHERE channel = ?1 AND username = ?2;
+                "#,
+                params![channel, username],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(balance.unwrap_or(0))
+    }
+
+    /// Give `username` `amount` points for chatting in `channel`, unless they
+    /// were last given points for chatting less than `interval` before `now`.
+    ///
+    /// Returns whether they were given any points.
+    pub fn earn(
+        &self,
+        channel: &str,
+        username: &str,
+        amount: u32,
+        interval: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<bool, PointsError> {
+        let mut conn = self.conn_pool.get()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        ensure_balance(&tx, channel, username)?;
+
+        let earned = tx.execute(
+            r#"
+            UPDATE points_balances
+            SET balance = balance + ?3, last_earned = ?4
+            WHERE channel = ?1 AND username = ?2
+                AND (last_earned IS NULL OR last_earned <= ?4 - ?5);
+            "#,
+            params![
+                channel,
+                username,
+                amount,
+                now.timestamp(),
+                interval.as_secs() as i64
+            ],
+        )? > 0;
+
+        if earned {
+            log(&tx, channel, username, i64::from(amount), "chatting", now)?;
+        }
+
+        tx.commit()?;
+
+        Ok(earned)
+    }
+
+    /// Add `amount` points to the balance of `username` in `channel`, returning
+    /// their new balance.
+    pub fn add(
+        &self,
+        channel: &str,
+        username: &str,
+        amount: u32,
+        reason: &str,
+    ) -> Result<u32, PointsError> {
+        let mut conn = self.conn_pool.get()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let balance = credit(&tx, channel, username, amount, reason)?;
+
+        tx.commit()?;
+
+        Ok(balance)
+    }
+
+    /// Take `amount` points from the balance of `username` in `channel`,
+    /// returning their new balance, or [`PointsError::Insufficient`] if they
+    /// don't have enough.
+    pub fn spend(
+        &self,
+        channel: &str,
+        username: &str,
+        amount: u32,
+        reason: &str,
+    ) -> Result<u32, PointsError> {
+        let mut conn = self.conn_pool.get()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let balance = debit(&tx, channel, username, amount, reason)?;
+
+        tx.commit()?;
+
+        Ok(balance)
+    }
+
+    /// Move `amount` points from `from` to `to` in `channel`, returning the new
+    /// balance of `from`, or [`PointsError::Insufficient`] if they don't have
+    /// enough.
+    pub fn give(
+        &self,
+        channel: &str,
+        from: &str,
+        to: &str,
+        amount: u32,
+    ) -> Result<u32, PointsError> {
+        let mut conn = self.conn_pool.get()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let balance = debit(&tx, channel, from, amount, &format!("gave to {}", to))?;
+        credit(&tx, channel, to, amount, &format!("given by {}", from))?;
+
+        tx.commit()?;
+
+        Ok(balance)
+    }
+
+    /// The most recent `limit` entries in the transaction log for `username`
+    /// in `channel`, newest first.
+    pub fn history(
+        &self,
+        channel: &str,
+        username: &str,
+        limit: usize,
+    ) -> Result<Vec<PointsTransaction>, PointsError> {
+        let conn = self.conn_pool.get()?;
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT username, amount, reason, time
+            FROM points_transactions
+            WHERE channel = ?1 AND username = ?2
+            ORDER BY id DESC
+            LIMIT ?3;
+            "#,
+        )?;
+
+        let transactions = stmt
+            .query_map(params![channel, username, limit as i64], |row| {
+                Ok(PointsTransaction {
+                    username: row.get(0)?,
+                    amount: row.get(1)?,
+                    reason: row.get(2)?,
+                    time: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(transactions)
+    }
+}
+
+/// Make sure `username` has a balance in `channel`, starting at zero.
+fn ensure_balance(tx: &Transaction<'_>, channel: &str, username: &str) -> Result<(), PointsError> {
+    tx.execute(
+        r#"
+        INSERT OR IGNORE INTO points_balances (channel, username, balance)
+        VALUES (?1, ?2, 0);
+        "#,
+        params![channel, username],
+    )?;
+
+    Ok(())
+}
+
+fn credit(
+    tx: &Transaction<'_>,
+    channel: &str,
+    username: &str,
+    amount: u32,
+    reason: &str,
+) -> Result<u32, PointsError> {
+    ensure_balance(tx, channel, username)?;
+
+    tx.execute(
+        r#"
+        UPDATE points_balances
+        SET balance = balance + ?3
+        WHERE channel = ?1 AND username = ?2;
+        "#,
+        params![channel, username, amount],
+    )?;
+
+    log(tx, channel, username, i64::from(amount), reason, Utc::now())?;
+
+    current_balance(tx, channel, username)
+}
+
+/// Take `amount` points from `username`, as long as it doesn't leave them with
+/// a negative balance.
+fn debit(
+    tx: &Transaction<'_>,
+    channel: &str,
+    username: &str,
+    amount: u32,
+    reason: &str,
+) -> Result<u32, PointsError> {
+    ensure_balance(tx, channel, username)?;
+
+    let debited = tx.execute(
+        r#"
+        UPDATE points_balances
+        SET balance = balance - ?3
+        WHERE channel = ?1 AND username = ?2 AND balance >= ?3;
+        "#,
+        params![channel, username, amount],
+    )? > 0;
+
+    if !debited {
+        return Err(PointsError::Insufficient {
+            balance: current_balance(tx, channel, username)?,
+            needed: amount,
+        });
+    }
+
+    log(
+        tx,
+        channel,
+        username,
+        -i64::from(amount),
+        reason,
+        Utc::now(),
+    )?;
+
+    current_balance(tx, channel, username)
+}
+
+fn current_balance(
+    tx: &Transaction<'_>,
+    channel: &str,
+    username: &str,
+) -> Result<u32, PointsError> {
+    let balance = tx.query_row(
+        r#"
+        SELECT balance
+        FROM points_balances
+        WHERE channel = ?1 AND username = ?2;
+        "#,
+        params![channel, username],
+        |row| row.get(0),
+    )?;
+
+    Ok(balance)
+}
+
+fn log(
+    tx: &Transaction<'_>,
+    channel: &str,
+    username: &str,
+    amount: i64,
+    reason: &str,
+    time: DateTime<Utc>,
+) -> Result<(), PointsError> {
+    tx.execute(
+        r#"
+        INSERT INTO points_transactions (channel, username, amount, reason, time)
+        VALUES (?1, ?2, ?3, ?4, ?5);
+        "#,
+        params![channel, username, amount, reason, time],
+    )?;
+
+    Ok(())
+}
+
+#[derive(Debug, Error)]
+pub enum PointsError {
+    #[error("needed {needed} points, but only had {balance}")]
+    Insufficient { balance: u32, needed: u32 },
+
+    #[error("rusqlite error: {0}")]
+    Rusqlite(#[from] rusqlite::Error),
+
+    #[error("r2d2 error: {0}")]
+    R2d2(#[from] r2d2::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ops::DerefMut, thread};
+
+    use chrono::Duration as ChronoDuration;
+    use tempfile::{tempdir, TempDir};
+
+    use super::*;
+
+    fn storage() -> (TempDir, PointsStore) {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let manager = SqliteConnectionManager::file(dir.path().join("db.sqlite3"));
+        let conn_pool = Pool::new(manager).expect("creating a connection pool should succeed");
+
+        let mut conn = conn_pool
+            .get()
+            .expect("getting a connection should succeed");
+        crate::db::migrations::runner()
+            .run(conn.deref_mut())
+            .expect("migrations should succeed");
+
+        (dir, PointsStore::new(conn_pool))
+    }
+
+    #[test]
+    fn earns_once_per_interval() {
+        let (_dir, store) = storage();
+        let interval = Duration::from_secs(300);
+        let now = Utc::now();
+
+        assert!(store.earn("channel", "a", 10, interval, now).unwrap());
+        assert!(!store
+            .earn(
+                "channel",
+                "a",
+                10,
+                interval,
+                now + ChronoDuration::seconds(299)
+            )
+            .unwrap());
+        assert!(store
+            .earn(
+                "channel",
+                "a",
+                10,
+                interval,
+                now + ChronoDuration::seconds(300)
+            )
+            .unwrap());
+
+        assert_eq!(store.balance("channel", "a").unwrap(), 20);
+        assert_eq!(store.balance("other", "a").unwrap(), 0);
+    }
+
+    #[test]
+    fn give_and_spend_are_logged() {
+        let (_dir, store) = storage();
+
+        store.add("channel", "a", 10, "added by mod").unwrap();
+        assert_eq!(store.give("channel", "a", "b", 4).unwrap(), 6);
+        assert_eq!(store.spend("channel", "b", 3, "!hug").unwrap(), 1);
+
+        assert!(matches!(
+            store.give("channel", "b", "a", 2),
+            Err(PointsError::Insufficient {
+                balance: 1,
+                needed: 2
+            })
+        ));
+        assert_eq!(store.balance("channel", "a").unwrap(), 6);
+
+        let history = store
+            .history("channel", "b", 10)
+            .unwrap()
+            .into_iter()
+            .map(|transaction| (transaction.amount, transaction.reason))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            history,
+            vec![(-3, "!hug".to_owned()), (4, "given by a".to_owned())]
+        );
+    }
+
+    #[test]
+    fn concurrent_spending_never_overdraws() {
+        let (_dir, store) = storage();
+        store.add("channel", "a", 10, "added by mod").unwrap();
+
+        let spent = (0..8)
+            .map(|_| {
+                let store = store.clone();
+                thread::spawn(move || store.spend("channel", "a", 3, "!hug").is_ok())
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().expect("spending should not panic"))
+            .filter(|spent| *spent)
+            .count();
+
+        assert_eq!(spent, 3);
+        assert_eq!(store.balance("channel", "a").unwrap(), 1);
+    }
+}
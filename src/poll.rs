@@ -0,0 +1,160 @@
+//! Polls that chat votes in, with one vote per chatter.
+
+use std::collections::HashMap;
+
+/// A question with a list of options, and the option each chatter has voted
+/// for.
+#[derive(Debug, Clone)]
+pub struct Poll {
+    question: String,
+    options: Vec<String>,
+    /// The index of the option each user voted for.
+    votes: HashMap<String, usize>,
+}
+
+impl Poll {
+    /// Create a `Poll` asking `question`, with no votes yet.
+    pub fn new(question: String, options: Vec<String>) -> Self {
+        Self {
+            question,
+            options,
+            votes: HashMap::new(),
+        }
+    }
+
+    pub fn question(&self) -> &str {
+        &self.question
+    }
+
+    pub fn options(&self) -> &[String] {
+        &self.options
+    }
+
+    /// Find the index of the option `text` is, ignoring case and whitespace
+    /// around it.
+    pub fn option_named(&self, text: &str) -> Option<usize> {
+        let text = text.trim().to_lowercase();
+
+        self.options
+            .iter()
+            .position(|option| option.trim().to_lowercase() == text)
+    }
+
+    /// Record a vote by `username` for the option at `option`, replacing any
+    /// vote they made before.
+    ///
+    /// Returns `false` if there's no such option.
+    pub fn vote(&mut self, username: &str, option: usize) -> bool {
+        if option >= self.options.len() {
+            return false;
+        }
+
+        self.votes.insert(username.to_owned(), option);
+        true
+    }
+
+    /// The number of people who have voted.
+    pub fn voters(&self) -> usize {
+        self.votes.len()
+    }
+
+    /// The number of votes for each option.
+    pub fn tally(&self) -> Vec<usize> {
+        let mut tally = vec![0; self.options.len()];
+
+        for option in self.votes.values() {
+            tally[*option] += 1;
+        }
+
+        tally
+    }
+
+    /// The indices of the options with the most votes, which is every option
+    /// in a tie, or none if nobody has voted.
+    pub fn leaders(&self) -> Vec<usize> {
+        let tally = self.tally();
+        let most = tally.iter().copied().max().unwrap_or(0);
+
+        if most == 0 {
+            return vec![];
+        }
+
+        tally
+            .iter()
+            .enumerate()
+            .filter(|(_, votes)| **votes == most)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Describe the number of votes for every option, e.g. `1. yes: 2 (67%),
+    /// 2. no: 1 (33%)`.
+    pub fn totals(&self) -> String {
+        let voters = self.voters();
+
+        self.tally()
+            .iter()
+            .zip(self.options.iter())
+            .enumerate()
+            .map(|(index, (votes, option))| {
+                let percent = if voters == 0 {
+                    0.0
+                } else {
+                    (*votes as f64 * 100.0 / voters as f64).round()
+                };
+
+                format!("{}. {}: {} ({}%)", index + 1, option, votes, percent)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poll() -> Poll {
+        Poll::new(
+            "Best snack?".to_owned(),
+            vec!["Crisps".to_owned(), "fruit".to_owned(), "cake".to_owned()],
+        )
+    }
+
+    #[test]
+    fn votes_can_change() {
+        let mut poll = poll();
+
+        assert!(poll.vote("someone", 0));
+        assert!(poll.vote("other", 1));
+        assert!(poll.vote("someone", 1));
+        assert!(!poll.vote("someone", 3));
+
+        assert_eq!(poll.voters(), 2);
+        assert_eq!(poll.tally(), vec![0, 2, 0]);
+        assert_eq!(poll.leaders(), vec![1]);
+        assert_eq!(
+            poll.totals(),
+            "1. Crisps: 0 (0%), 2. fruit: 2 (100%), 3. cake: 0 (0%)"
+        );
+    }
+
+    #[test]
+    fn options_by_name() {
+        let poll = poll();
+
+        assert_eq!(poll.option_named(" crisps "), Some(0));
+        assert_eq!(poll.option_named("CAKE"), Some(2));
+        assert_eq!(poll.option_named("cake!"), None);
+    }
+
+    #[test]
+    fn ties_and_no_votes() {
+        let mut poll = poll();
+        assert_eq!(poll.leaders(), Vec::<usize>::new());
+
+        poll.vote("someone", 0);
+        poll.vote("other", 2);
+        assert_eq!(poll.leaders(), vec![0, 2]);
+    }
+}
@@ -0,0 +1,579 @@
+//! Raffles, where chatters enter by typing a keyword and winners are drawn at
+//! random.
+//!
+//! Every raffle, entry and winner is kept in the database, so that a draw can
+//! be checked afterwards. Each entry records the weight it was drawn with, and
+//! re-rolled winners are marked rather than removed.
+
+use chrono::{DateTime, Utc};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    Rng,
+};
+use rusqlite::{params, OptionalExtension, Row, Transaction, TransactionBehavior};
+use thiserror::Error;
+
+use crate::msg::Role;
+
+/// A raffle in a channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Raffle {
+    pub id: i64,
+    pub channel: String,
+    /// The word chatters type to enter, in lowercase.
+    pub keyword: String,
+    /// When entries close, if they don't stay open until a winner is drawn.
+    pub closes: Option<DateTime<Utc>>,
+    pub closed: bool,
+    /// Whether people who won an earlier raffle in the channel can't win this
+    /// one.
+    pub exclude_winners: bool,
+}
+
+impl Raffle {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            channel: row.get(1)?,
+            keyword: row.get(2)?,
+            closes: row.get(3)?,
+            closed: row.get(4)?,
+            exclude_winners: row.get(5)?,
+        })
+    }
+
+    /// Whether `text` is an entry to this raffle.
+    pub fn is_entry(&self, text: &str) -> bool {
+        !self.closed && text.trim().eq_ignore_ascii_case(&self.keyword)
+    }
+}
+
+/// A winner drawn from a raffle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Winner {
+    pub username: String,
+    /// How many entries were in the draw.
+    pub entries: usize,
+}
+
+/// Pick the index of one of `weights` at random, where each index is picked
+/// with a chance proportional to its weight.
+///
+/// Returns `None` if there are no weights, or they're all zero.
+pub fn pick<R: Rng + ?Sized>(weights: &[u32], rng: &mut R) -> Option<usize> {
+    // The weights are summed as `u64`, so that no number of entries with any
+    // weight can overflow the total.
+    WeightedIndex::new(weights.iter().map(|weight| u64::from(*weight)))
+        .ok()
+        .map(|distribution| distribution.sample(rng))
+}
+
+/// Storage of raffles in an SQLite3 database.
+#[derive(Debug, Clone)]
+pub struct RaffleStore {
+    conn_pool: Pool<SqliteConnectionManager>,
+}
+
+impl RaffleStore {
+    /// Create a `RaffleStore` with a connection to a database.
+    pub fn new(conn_pool: Pool<SqliteConnectionManager>) -> Self {
+        Self { conn_pool }
+    }
+
+    /// Start a raffle in `channel`, which chatters enter by typing `keyword`.
+    pub fn start(
+        &self,
+        channel: &str,
+        keyword: &str,
+        closes: Option<DateTime<Utc>>,
+        exclude_winners: bool,
+        now: DateTime<Utc>,
+    ) -> Result<Raffle, RaffleError> {
+        let conn = self.conn_pool.get()?;
+        let keyword = keyword.to_lowercase();
+
+        conn.execute(
+            r#"
+            INSERT INTO raffles (channel, keyword, started, closes, exclude_winners)
+            VALUES (?1, ?2, ?3, ?4, ?5);
+            "#,
+            params![channel, keyword, now, closes, exclude_winners],
+        )?;
+
+        Ok(Raffle {
+            id: conn.last_insert_rowid(),
+            channel: channel.to_owned(),
+            keyword,
+            closes,
+            closed: false,
+            exclude_winners,
+        })
+    }
+
+    /// The most recent raffle in `channel`, if there has been one.
+    pub fn current(&self, channel: &str) -> Result<Option<Raffle>, RaffleError> {
+        let conn = self.conn_pool.get()?;
+
+        let raffle = conn
+            .query_row(
+                r#"
+                SELECT id, channel, keyword, closes, closed, exclude_winners
+                FROM raffles
+                WHERE channel = ?1
+                ORDER BY id DESC
+                LIMIT 1;
+                "#,
+                params![channel],
+                Raffle::from_row,
+            )
+            .optional()?;
+
+        Ok(raffle)
+    }
+
+    /// Every raffle that can still be entered.
+    pub fn open(&self) -> Result<Vec<Raffle>, RaffleError> {
+        let conn = self.conn_pool.get()?;
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, channel, keyword, closes, closed, exclude_winners
+            FROM raffles
+            WHERE closed = 0
+            ORDER BY id;
+            "#,
+        )?;
+
+        let raffles = stmt
+            .query_map([], Raffle::from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(raffles)
+    }
+
+    /// Stop any more entries to the raffle with the ID `raffle`.
+    pub fn close(&self, raffle: i64) -> Result<(), RaffleError> {
+        let conn = self.conn_pool.get()?;
+
+        conn.execute(
+            r#"
+            UPDATE raffles
+            SET closed = 1
+            WHERE id = ?1;
+            "#,
+            params![raffle],
+        )?;
+
+        Ok(())
+    }
+
+    /// Enter `username` into the raffle with the ID `raffle`, with `weight`
+    /// chances to win.
+    ///
+    /// Returns `false` if they had already entered, in which case their entry
+    /// is left as it was, or if the raffle has closed, in which case nothing
+    /// is recorded.
+    pub fn enter(
+        &self,
+        raffle: i64,
+        username: &str,
+        weight: u32,
+        now: DateTime<Utc>,
+    ) -> Result<bool, RaffleError> {
+        let conn = self.conn_pool.get()?;
+
+        let entered = conn.execute(
+            r#"
+            INSERT OR IGNORE INTO raffle_entries (raffle, username, weight, entered)
+            SELECT ?1, ?2, ?3, ?4
+            WHERE EXISTS (SELECT 1 FROM raffles WHERE id = ?1 AND closed = 0);
+            "#,
+            params![raffle, username, weight, now],
+        )?;
+
+        Ok(entered > 0)
+    }
+
+    /// The number of people who have entered the raffle with the ID `raffle`.
+    pub fn entries(&self, raffle: i64) -> Result<usize, RaffleError> {
+        let conn = self.conn_pool.get()?;
+
+        let entries: i64 = conn.query_row(
+            r#"
+            SELECT COUNT(*)
+            FROM raffle_entries
+            WHERE raffle = ?1;
+            "#,
+            params![raffle],
+            |row| row.get(0),
+        )?;
+
+        Ok(entries as usize)
+    }
+
+    /// Draw a winner from the entries to `raffle` who haven't already been
+    /// drawn, using `rng`.
+    ///
+    /// Returns `None` if there's nobody left who can win.
+    pub fn draw<R: Rng + ?Sized>(
+        &self,
+        raffle: &Raffle,
+        rng: &mut R,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Winner>, RaffleError> {
+        let mut conn = self.conn_pool.get()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let winner = draw_winner(&tx, raffle, rng, now)?;
+
+        tx.commit()?;
+
+        Ok(winner)
+    }
+
+    /// Mark the latest winner of `raffle` as re-rolled and draw another one
+    /// using `rng`.
+    ///
+    /// Returns `None` if there was no winner to re-roll, and otherwise the
+    /// re-rolled winner along with the new one, if there's anyone left who can
+    /// win.
+    pub fn reroll<R: Rng + ?Sized>(
+        &self,
+        raffle: &Raffle,
+        rng: &mut R,
+        now: DateTime<Utc>,
+    ) -> Result<Option<(String, Option<Winner>)>, RaffleError> {
+        let mut conn = self.conn_pool.get()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let latest = tx
+            .query_row(
+                r#"
+                SELECT position, username
+                FROM raffle_winners
+                WHERE raffle = ?1 AND rerolled = 0
+                ORDER BY position DESC
+                LIMIT 1;
+                "#,
+                params![raffle.id],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?;
+
+        let (position, rerolled) = match latest {
+            Some(latest) => latest,
+            None => return Ok(None),
+        };
+
+        tx.execute(
+            r#"
+            UPDATE raffle_winners
+            SET rerolled = 1
+            WHERE raffle = ?1 AND position = ?2;
+            "#,
+            params![raffle.id, position],
+        )?;
+
+        let winner = draw_winner(&tx, raffle, rng, now)?;
+
+        tx.commit()?;
+
+        Ok(Some((rerolled, winner)))
+    }
+
+    /// Every winner drawn from the raffle with the ID `raffle`, in the order
+    /// they were drawn, along with whether they were re-rolled.
+    pub fn winners(&self, raffle: i64) -> Result<Vec<(String, bool)>, RaffleError> {
+        let conn = self.conn_pool.get()?;
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT username, rerolled
+            FROM raffle_winners
+            WHERE raffle = ?1
+            ORDER BY position;
+            "#,
+        )?;
+
+        let winners = stmt
+            .query_map(params![raffle], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(winners)
+    }
+
+    /// Give entries from users with `role` in `channel` `weight` chances to
+    /// win.
+    pub fn set_luck(&self, channel: &str, role: Role, weight: u32) -> Result<(), RaffleError> {
+        let conn = self.conn_pool.get()?;
+
+        conn.execute(
+            r#"
+            INSERT INTO raffle_luck (channel, role, weight)
+            VALUES (?1, ?2, ?3)
+            ON CONFLICT(channel, role) DO UPDATE SET weight = excluded.weight;
+            "#,
+            params![channel, role.to_string(), weight],
+        )?;
+
+        Ok(())
+    }
+
+    /// The number of chances to win an entry from a user with `role` in
+    /// `channel` gets.
+    ///
+    /// This is the weight set for the highest role at or below `role`, so
+    /// moderators who are also subscribers don't lose their subscriber luck,
+    /// or 1 if no weight has been set for any of them.
+    pub fn luck(&self, channel: &str, role: Role) -> Result<u32, RaffleError> {
+        let conn = self.conn_pool.get()?;
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT role, weight
+            FROM raffle_luck
+            WHERE channel = ?1;
+            "#,
+        )?;
+
+        let weights = stmt
+            .query_map(params![channel], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let weight = weights
+            .into_iter()
+            .filter_map(|(name, weight)| Some((name.parse::<Role>().ok()?, weight)))
+            .filter(|(luck_role, _)| *luck_role <= role)
+            .max_by_key(|(luck_role, _)| *luck_role)
+            .map(|(_, weight)| weight)
+            .unwrap_or(1);
+
+        Ok(weight)
+    }
+}
+
+/// Draw a winner from the entries to `raffle` who can still win, and record
+/// them as its next winner.
+fn draw_winner<R: Rng + ?Sized>(
+    tx: &Transaction<'_>,
+    raffle: &Raffle,
+    rng: &mut R,
+    now: DateTime<Utc>,
+) -> Result<Option<Winner>, RaffleError> {
+    // Nobody can win the same raffle twice, even if their win was re-rolled,
+    // and with `exclude_winners` nobody who won an earlier raffle in the same
+    // channel can win either.
+    let mut stmt = tx.prepare(
+        r#"
+        SELECT username, weight
+        FROM raffle_entries
+        WHERE raffle = ?1
+            AND username NOT IN (
+                SELECT username
+                FROM raffle_winners
+                WHERE raffle = ?1
+            )
+            AND NOT (?3 AND username IN (
+                SELECT winners.username
+                FROM raffle_winners AS winners
+                JOIN raffles ON raffles.id = winners.raffle
+                WHERE raffles.channel = ?2 AND raffles.id < ?1 AND winners.rerolled = 0
+            ))
+        ORDER BY username;
+        "#,
+    )?;
+
+    let entries = stmt
+        .query_map(
+            params![raffle.id, raffle.channel, raffle.exclude_winners],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?)),
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let weights = entries
+        .iter()
+        .map(|(_, weight)| *weight)
+        .collect::<Vec<_>>();
+
+    let index = match pick(&weights, rng) {
+        Some(index) => index,
+        None => return Ok(None),
+    };
+
+    let username = entries[index].0.clone();
+
+    tx.execute(
+        r#"
+        INSERT INTO raffle_winners (raffle, position, username, drawn)
+        VALUES (
+            ?1,
+            (SELECT COUNT(*) FROM raffle_winners WHERE raffle = ?1),
+            ?2,
+            ?3
+        );
+        "#,
+        params![raffle.id, username, now],
+    )?;
+
+    Ok(Some(Winner {
+        username,
+        entries: entries.len(),
+    }))
+}
+
+#[derive(Debug, Error)]
+pub enum RaffleError {
+    #[error("rusqlite error: {0}")]
+    Rusqlite(#[from] rusqlite::Error),
+
+    #[error("r2d2 error: {0}")]
+    R2d2(#[from] r2d2::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::DerefMut;
+
+    use rand::{rngs::StdRng, SeedableRng};
+    use tempfile::{tempdir, TempDir};
+
+    use super::*;
+
+    fn storage() -> (TempDir, RaffleStore) {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let manager = SqliteConnectionManager::file(dir.path().join("db.sqlite3"));
+        let conn_pool = Pool::new(manager).expect("creating a connection pool should succeed");
+
+        let mut conn = conn_pool
+            .get()
+            .expect("getting a connection should succeed");
+        crate::db::migrations::runner()
+            .run(conn.deref_mut())
+            .expect("migrations should succeed");
+
+        (dir, RaffleStore::new(conn_pool))
+    }
+
+    #[test]
+    fn pick_is_fair() {
+        let weights = [1, 2, 3, 4];
+        let total = weights.iter().sum::<u32>();
+        let draws = 100_000;
+
+        let mut rng = StdRng::seed_from_u64(0x0b0e);
+        let mut counts = [0u32; 4];
+
+        for _ in 0..draws {
+            let index = pick(&weights, &mut rng).expect("there are weights to pick from");
+            counts[index] += 1;
+        }
+
+        // Pearson's chi-squared test against the weights. With 3 degrees of
+        // freedom, a fair draw is above 16.27 less than 0.1% of the time.
+        let chi_squared = counts
+            .iter()
+            .zip(weights.iter())
+            .map(|(&observed, &weight)| {
+                let expected = f64::from(draws) * f64::from(weight) / f64::from(total);
+                (f64::from(observed) - expected).powi(2) / expected
+            })
+            .sum::<f64>();
+
+        assert!(chi_squared < 16.27, "chi squared was {}", chi_squared);
+        assert_eq!(pick(&[], &mut rng), None);
+        assert!(pick(&[u32::MAX, u32::MAX, u32::MAX], &mut rng).is_some());
+    }
+
+    #[test]
+    fn entries_are_deduplicated() {
+        let (_dir, store) = storage();
+        let now = Utc::now();
+
+        let raffle = store
+            .start("channel", "Join", None, false, now)
+            .expect("starting a raffle should succeed");
+        assert_eq!(raffle.keyword, "join");
+        assert!(raffle.is_entry(" JOIN "));
+
+        assert!(store.enter(raffle.id, "someone", 1, now).unwrap());
+        assert!(!store.enter(raffle.id, "someone", 2, now).unwrap());
+        assert_eq!(store.entries(raffle.id).unwrap(), 1);
+    }
+
+    #[test]
+    fn closed_raffles_take_no_entries() {
+        let (_dir, store) = storage();
+        let now = Utc::now();
+
+        let raffle = store
+            .start("channel", "join", None, false, now)
+            .expect("starting a raffle should succeed");
+        store.close(raffle.id).expect("closing should succeed");
+
+        assert!(!store.enter(raffle.id, "someone", 1, now).unwrap());
+        assert_eq!(store.entries(raffle.id).unwrap(), 0);
+    }
+
+    #[test]
+    fn draws_and_rerolls_are_recorded() {
+        let (_dir, store) = storage();
+        let mut rng = StdRng::seed_from_u64(1);
+        let now = Utc::now();
+
+        let first = store.start("channel", "join", None, false, now).unwrap();
+        store.enter(first.id, "someone", 1, now).unwrap();
+        store.close(first.id).unwrap();
+
+        let winner = store.draw(&first, &mut rng, now).unwrap();
+        assert_eq!(
+            winner,
+            Some(Winner {
+                username: "someone".to_owned(),
+                entries: 1,
+            })
+        );
+
+        // Nobody can win twice.
+        assert_eq!(store.draw(&first, &mut rng, now).unwrap(), None);
+
+        let second = store.start("channel", "join", None, true, now).unwrap();
+        store.enter(second.id, "someone", 1, now).unwrap();
+        store.enter(second.id, "other", 1, now).unwrap();
+        store.enter(second.id, "third", 1, now).unwrap();
+
+        // The winner of the first raffle is excluded.
+        let winner = store.draw(&second, &mut rng, now).unwrap().unwrap();
+        assert_ne!(winner.username, "someone");
+        assert_eq!(winner.entries, 2);
+
+        let (rerolled, new) = store.reroll(&second, &mut rng, now).unwrap().unwrap();
+        assert_eq!(rerolled, winner.username);
+        let new = new.expect("there's one entry left who can win");
+        assert_ne!(new.username, "someone");
+        assert_ne!(new.username, winner.username);
+
+        assert_eq!(
+            store.winners(second.id).unwrap(),
+            vec![(winner.username, true), (new.username, false)]
+        );
+    }
+
+    #[test]
+    fn luck_applies_to_higher_roles() {
+        let (_dir, store) = storage();
+
+        store.set_luck("channel", Role::Subscriber, 2).unwrap();
+
+        assert_eq!(store.luck("channel", Role::Everyone).unwrap(), 1);
+        assert_eq!(store.luck("channel", Role::Subscriber).unwrap(), 2);
+        assert_eq!(store.luck("channel", Role::Moderator).unwrap(), 2);
+        assert_eq!(store.luck("other", Role::Subscriber).unwrap(), 1);
+
+        store.set_luck("channel", Role::Vip, 3).unwrap();
+        assert_eq!(store.luck("channel", Role::Moderator).unwrap(), 3);
+    }
+}
@@ -0,0 +1,48 @@
+//! Descriptions of built-in commands, used to generate help text.
+
+use crate::msg::Role;
+
+/// A description of a built-in command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuiltIn {
+    /// The keyword that comes after the prefix to run this command.
+    pub name: &'static str,
+    /// The ways the command can be written, without the prefix.
+    pub syntax: &'static [&'static str],
+    /// A short description of what the command does.
+    pub description: &'static str,
+    /// The role a user needs to run this command.
+    pub role: Role,
+    /// The anchor of the section of the help page covering this command.
+    pub anchor: &'static str,
+}
+
+impl BuiltIn {
+    /// A usage line for this command, with every form of its syntax prefixed by
+    /// `prefix`.
+    pub fn usage(&self, prefix: char) -> String {
+        self.syntax
+            .iter()
+            .map(|syntax| format!("{}{}", prefix, syntax))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+}
+
+/// The built-in commands handled by the bot itself, rather than by any module.
+pub const CORE_COMMANDS: &[BuiltIn] = &[
+    BuiltIn {
+        name: "commands",
+        syntax: &["commands"],
+        description: "List the commands you can run",
+        role: Role::Everyone,
+        anchor: "help",
+    },
+    BuiltIn {
+        name: "help",
+        syntax: &["help [command]"],
+        description: "Get help with a command",
+        role: Role::Everyone,
+        anchor: "help",
+    },
+];
@@ -0,0 +1,147 @@
+//! Scores that chatters win in games, such as the word game and trivia, kept
+//! separately for each game in each channel.
+
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::params;
+use thiserror::Error;
+
+/// Storage of the scores in one game in an SQLite3 database.
+#[derive(Debug, Clone)]
+pub struct ScoresStore {
+    conn_pool: Pool<SqliteConnectionManager>,
+    game: &'static str,
+}
+
+impl ScoresStore {
+    /// Create a `ScoresStore` for the game called `game`, with a connection to
+    /// a database.
+    pub fn new(conn_pool: Pool<SqliteConnectionManager>, game: &'static str) -> Self {
+        Self { conn_pool, game }
+    }
+
+    /// Give a point to `username` in `channel`, returning the number of points
+    /// they now have there.
+    pub fn add_point(&self, channel: &str, username: &str) -> Result<u32, ScoresError> {
+        let mut conn = self.conn_pool.get()?;
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            r#"
+            INSERT OR IGNORE INTO scores (game, channel, username, score)
+            VALUES (?1, ?2, ?3, 0);
+            "#,
+            params![self.game, channel, username],
+        )?;
+
+        tx.execute(
+            r#"
+            UPDATE scores
+            SET score = score + 1
+            WHERE game = ?1 AND channel = ?2 AND username = ?3;
+            "#,
+            params![self.game, channel, username],
+        )?;
+
+        let score = tx.query_row(
+            r#"
+            SELECT score
+            FROM scores
+            WHERE game = ?1 AND channel = ?2 AND username = ?3;
+            "#,
+            params![self.game, channel, username],
+            |row| row.get(0),
+        )?;
+
+        tx.commit()?;
+
+        Ok(score)
+    }
+
+    /// The `limit` users with the most points in `channel`, most first.
+    pub fn leaderboard(
+        &self,
+        channel: &str,
+        limit: usize,
+    ) -> Result<Vec<(String, u32)>, ScoresError> {
+        let conn = self.conn_pool.get()?;
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT username, score
+            FROM scores
+            WHERE game = ?1 AND channel = ?2
+            ORDER BY score DESC, username
+            LIMIT ?3;
+            "#,
+        )?;
+
+        let leaders = stmt
+            .query_map(params![self.game, channel, limit as i64], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(leaders)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ScoresError {
+    #[error("rusqlite error: {0}")]
+    Rusqlite(#[from] rusqlite::Error),
+
+    #[error("r2d2 error: {0}")]
+    R2d2(#[from] r2d2::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::DerefMut;
+
+    use tempfile::{tempdir, TempDir};
+
+    use super::*;
+
+    fn storage() -> (TempDir, Pool<SqliteConnectionManager>) {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let manager = SqliteConnectionManager::file(dir.path().join("db.sqlite3"));
+        let conn_pool = Pool::new(manager).expect("creating a connection pool should succeed");
+
+        let mut conn = conn_pool
+            .get()
+            .expect("getting a connection should succeed");
+        crate::db::migrations::runner()
+            .run(conn.deref_mut())
+            .expect("migrations should succeed");
+
+        (dir, conn_pool)
+    }
+
+    #[test]
+    fn leaderboard_orders_by_score() {
+        let (_dir, conn_pool) = storage();
+        let store = ScoresStore::new(conn_pool.clone(), "wordgame");
+        let other_game = ScoresStore::new(conn_pool, "trivia");
+
+        assert_eq!(store.add_point("channel", "a").unwrap(), 1);
+        assert_eq!(store.add_point("channel", "b").unwrap(), 1);
+        assert_eq!(store.add_point("channel", "b").unwrap(), 2);
+        assert_eq!(store.add_point("other", "a").unwrap(), 1);
+        assert_eq!(other_game.add_point("channel", "a").unwrap(), 1);
+        assert_eq!(other_game.add_point("channel", "a").unwrap(), 2);
+
+        assert_eq!(
+            store.leaderboard("channel", 5).unwrap(),
+            vec![("b".to_owned(), 2), ("a".to_owned(), 1)]
+        );
+        assert_eq!(
+            store.leaderboard("channel", 1).unwrap(),
+            vec![("b".to_owned(), 2)]
+        );
+        assert_eq!(
+            other_game.leaderboard("channel", 5).unwrap(),
+            vec![("a".to_owned(), 2)]
+        );
+    }
+}
@@ -0,0 +1,480 @@
+//! Trivia questions, stored in named banks.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, OptionalExtension};
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Articles that don't have to be included in an answer.
+const ARTICLES: &[&str] = &["a", "an", "the"];
+
+/// A question, with the answers that are accepted for it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Question {
+    pub question: String,
+    /// The answer that is shown if nobody gets it right.
+    pub answer: String,
+    /// Other answers that are also accepted.
+    #[serde(default)]
+    pub alternatives: Vec<String>,
+}
+
+impl Question {
+    /// Whether `guess` is close enough to one of the accepted answers.
+    ///
+    /// Case, punctuation and leading articles are ignored, and the more
+    /// letters an answer has, the more typos are allowed in it. Answers with
+    /// numbers in them have to be exact, since being a digit off is just
+    /// wrong.
+    pub fn accepts(&self, guess: &str) -> bool {
+        let guess = normalise(guess);
+
+        if guess.is_empty() {
+            return false;
+        }
+
+        iter_answers(self).any(|answer| {
+            let answer = normalise(answer);
+
+            let allowed = if answer.contains(|c: char| c.is_ascii_digit()) {
+                0
+            } else {
+                answer.chars().count() / 5
+            };
+
+            edit_distance(&guess, &answer) <= allowed
+        })
+    }
+}
+
+fn iter_answers(question: &Question) -> impl Iterator<Item = &str> {
+    std::iter::once(question.answer.as_str())
+        .chain(question.alternatives.iter().map(String::as_str))
+}
+
+/// Lowercase `text`, replace punctuation with spaces, collapse whitespace and
+/// remove a leading article, unless it's the only word.
+fn normalise(text: &str) -> String {
+    let text = text
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .to_lowercase();
+
+    let mut words = text.split_whitespace().collect::<Vec<_>>();
+
+    if words.len() > 1 && ARTICLES.contains(&words[0]) {
+        words.remove(0);
+    }
+
+    words.join(" ")
+}
+
+/// The Levenshtein distance between `a` and `b`: the number of characters
+/// that have to be inserted, removed or replaced to turn one into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, b) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(a != *b);
+            let insert = current[j] + 1;
+            let remove = previous[j + 1] + 1;
+
+            current.push(replace.min(insert).min(remove));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Read a bank of questions from a file.
+///
+/// Files ending in `.json` should contain an array of objects, each with a
+/// `question`, an `answer` and optionally a list of `alternatives`. Files
+/// ending in `.csv` should have a question, then its answer, then any
+/// alternatives on each line, with an optional `question,answer` header.
+pub fn read_bank<P: AsRef<Path>>(path: P) -> Result<Vec<Question>, TriviaError> {
+    let path = path.as_ref();
+
+    let parse: fn(&str) -> Result<Vec<Question>, TriviaError> =
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => |text| Ok(serde_json::from_str(text)?),
+            Some("csv") => parse_csv,
+            _ => return Err(TriviaError::UnknownFormat(path.to_owned())),
+        };
+
+    let text = fs::read_to_string(path).map_err(|source| TriviaError::Read {
+        path: path.to_owned(),
+        source,
+    })?;
+
+    let questions = parse(&text)?;
+
+    for (index, question) in questions.iter().enumerate() {
+        if question.question.trim().is_empty() || normalise(&question.answer).is_empty() {
+            return Err(TriviaError::InvalidQuestion(index + 1));
+        }
+    }
+
+    if questions.is_empty() {
+        return Err(TriviaError::Empty);
+    }
+
+    Ok(questions)
+}
+
+/// Parse questions from CSV, where fields can be wrapped in double quotes to
+/// include commas, and double quotes inside them are written twice.
+fn parse_csv(text: &str) -> Result<Vec<Question>, TriviaError> {
+    let mut questions = vec![];
+
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut fields = parse_csv_line(line)
+            .ok_or(TriviaError::Csv(index + 1))?
+            .into_iter()
+            .map(|field| field.trim().to_owned());
+
+        let (question, answer) = match (fields.next(), fields.next()) {
+            (Some(question), Some(answer)) => (question, answer),
+            _ => return Err(TriviaError::Csv(index + 1)),
+        };
+
+        if index == 0
+            && question.eq_ignore_ascii_case("question")
+            && answer.eq_ignore_ascii_case("answer")
+        {
+            continue;
+        }
+
+        questions.push(Question {
+            question,
+            answer,
+            alternatives: fields.filter(|field| !field.is_empty()).collect(),
+        });
+    }
+
+    Ok(questions)
+}
+
+/// Split a line of CSV into its fields, or return `None` if a quoted field
+/// isn't closed.
+fn parse_csv_line(line: &str) -> Option<Vec<String>> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (false, '"') if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            (false, ',') => fields.push(std::mem::take(&mut field)),
+            (_, c) => field.push(c),
+        }
+    }
+
+    if quoted {
+        return None;
+    }
+
+    fields.push(field);
+
+    Some(fields)
+}
+
+/// Storage of trivia questions in an SQLite3 database.
+#[derive(Debug, Clone)]
+pub struct TriviaStore {
+    conn_pool: Pool<SqliteConnectionManager>,
+}
+
+impl TriviaStore {
+    /// Create a `TriviaStore` with a connection to a database.
+    pub fn new(conn_pool: Pool<SqliteConnectionManager>) -> Self {
+        Self { conn_pool }
+    }
+
+    /// Save `questions` in the bank called `bank`, replacing any questions
+    /// already in it.
+    pub fn set_bank(&self, bank: &str, questions: &[Question]) -> Result<(), TriviaError> {
+        let mut conn = self.conn_pool.get()?;
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            r#"
+            DELETE FROM trivia_questions
+            WHERE bank = ?1;
+            "#,
+            params![bank],
+        )?;
+
+        tx.execute(
+            r#"
+            DELETE FROM trivia_alternatives
+            WHERE bank = ?1;
+            "#,
+            params![bank],
+        )?;
+
+        {
+            let mut insert_question = tx.prepare(
+                r#"
+                INSERT INTO trivia_questions (bank, position, question, answer)
+                VALUES (?1, ?2, ?3, ?4);
+                "#,
+            )?;
+
+            let mut insert_alternative = tx.prepare(
+                r#"
+                INSERT INTO trivia_alternatives (bank, position, answer)
+                VALUES (?1, ?2, ?3);
+                "#,
+            )?;
+
+            for (position, question) in questions.iter().enumerate() {
+                insert_question.execute(params![
+                    bank,
+                    position as i64,
+                    question.question,
+                    question.answer
+                ])?;
+
+                for alternative in question.alternatives.iter() {
+                    insert_alternative.execute(params![bank, position as i64, alternative])?;
+                }
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Pick a question at random from the bank called `bank`, or from every
+    /// bank if it's `None`.
+    pub fn random_question(&self, bank: Option<&str>) -> Result<Option<Question>, TriviaError> {
+        let conn = self.conn_pool.get()?;
+
+        let found = conn
+            .query_row(
+                r#"
+                SELECT bank, position, question, answer
+                FROM trivia_questions
+                WHERE ?1 IS NULL OR bank = ?1
+                ORDER BY RANDOM()
+                LIMIT 1;
+                "#,
+                params![bank],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                    ))
+                },
+            )
+            .optional()?;
+
+        let (bank, position, question, answer) = match found {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT answer
+            FROM trivia_alternatives
+            WHERE bank = ?1 AND position = ?2;
+            "#,
+        )?;
+
+        let alternatives = stmt
+            .query_map(params![bank, position], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+
+        Ok(Some(Question {
+            question,
+            answer,
+            alternatives,
+        }))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TriviaError {
+    #[error("failed to read {}: {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+
+    #[error("don't know how to read questions from {}, expected a .json or .csv file", .0.display())]
+    UnknownFormat(PathBuf),
+
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("line {0} should have a question and an answer")]
+    Csv(usize),
+
+    #[error("question {0} has an empty question or answer")]
+    InvalidQuestion(usize),
+
+    #[error("there are no questions")]
+    Empty,
+
+    #[error("rusqlite error: {0}")]
+    Rusqlite(#[from] rusqlite::Error),
+
+    #[error("r2d2 error: {0}")]
+    R2d2(#[from] r2d2::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::DerefMut;
+
+    use tempfile::{tempdir, TempDir};
+
+    use super::*;
+
+    fn storage() -> (TempDir, TriviaStore) {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let manager = SqliteConnectionManager::file(dir.path().join("db.sqlite3"));
+        let conn_pool = Pool::new(manager).expect("creating a connection pool should succeed");
+
+        let mut conn = conn_pool
+            .get()
+            .expect("getting a connection should succeed");
+        crate::db::migrations::runner()
+            .run(conn.deref_mut())
+            .expect("migrations should succeed");
+
+        (dir, TriviaStore::new(conn_pool))
+    }
+
+    fn question(question: &str, answer: &str, alternatives: &[&str]) -> Question {
+        Question {
+            question: question.to_owned(),
+            answer: answer.to_owned(),
+            alternatives: alternatives.iter().map(|ea| ea.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn accepts_close_answers() {
+        let capital = question("Capital of France?", "Paris", &[]);
+        assert!(capital.accepts("paris"));
+        assert!(capital.accepts("Paris!"));
+        assert!(capital.accepts("pariss"));
+        assert!(!capital.accepts("london"));
+        assert!(!capital.accepts("it's paris"));
+
+        let band = question("Who sang Hey Jude?", "The Beatles", &["Beatles"]);
+        assert!(band.accepts("beatles"));
+        assert!(band.accepts("the beetles"));
+
+        let vitamin = question("Which vitamin is retinol?", "A", &[]);
+        assert!(vitamin.accepts("a"));
+        assert!(!vitamin.accepts("b"));
+
+        let year = question("Moon landing?", "1969", &[]);
+        assert!(year.accepts("1969"));
+        assert!(!year.accepts("1968"));
+    }
+
+    #[test]
+    fn parses_csv() {
+        let questions = parse_csv(
+            "question,answer\n\
+             Capital of France?,Paris\n\
+             \"Who sang \"\"Hey Jude\"\"?\",The Beatles,Beatles\n",
+        )
+        .expect("valid CSV should parse");
+
+        assert_eq!(
+            questions,
+            vec![
+                question("Capital of France?", "Paris", &[]),
+                question("Who sang \"Hey Jude\"?", "The Beatles", &["Beatles"]),
+            ]
+        );
+
+        assert!(matches!(
+            parse_csv("No answer here\n"),
+            Err(TriviaError::Csv(1))
+        ));
+        assert!(matches!(
+            parse_csv("\"Unclosed,quote\n"),
+            Err(TriviaError::Csv(1))
+        ));
+    }
+
+    #[test]
+    fn reads_json_bank() {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let path = dir.path().join("bank.json");
+        fs::write(
+            &path,
+            r#"[
+                {"question": "Capital of France?", "answer": "Paris"},
+                {"question": "Which vitamin is retinol?", "answer": "A"}
+            ]"#,
+        )
+        .expect("writing the bank should succeed");
+
+        assert_eq!(
+            read_bank(&path).expect("valid JSON should be read"),
+            vec![
+                question("Capital of France?", "Paris", &[]),
+                question("Which vitamin is retinol?", "A", &[]),
+            ]
+        );
+
+        assert!(matches!(
+            read_bank(dir.path().join("bank.txt")),
+            Err(TriviaError::UnknownFormat(_))
+        ));
+    }
+
+    #[test]
+    fn stores_banks() {
+        let (_dir, store) = storage();
+
+        let geography = vec![question("Capital of France?", "Paris", &["Paris, France"])];
+        store
+            .set_bank("geography", &geography)
+            .expect("saving a bank should succeed");
+
+        assert_eq!(
+            store.random_question(Some("geography")).unwrap().as_ref(),
+            geography.first()
+        );
+        assert_eq!(
+            store.random_question(None).unwrap().as_ref(),
+            geography.first()
+        );
+        assert_eq!(store.random_question(Some("music")).unwrap(), None);
+    }
+}
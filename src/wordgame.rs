@@ -0,0 +1,95 @@
+//! A word guessing game like the one the word search solves, where chatters
+//! try to find a secret word picked by the bot.
+
+use std::cmp::Ordering;
+
+use rand::prelude::IteratorRandom;
+
+use crate::wordsearch::{list::WordList, WordSearch};
+
+/// A game in progress, with a secret word from a word list.
+#[derive(Debug, Clone)]
+pub struct Game {
+    words: WordList,
+    secret: String,
+    guesses: usize,
+}
+
+/// How close a guess was to the secret word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The secret word comes after the guess.
+    Higher { distance: usize },
+    /// The secret word comes before the guess.
+    Lower { distance: usize },
+    /// The guess was the secret word.
+    Correct,
+}
+
+impl Game {
+    /// Start a game with a secret word picked at random from `words`.
+    pub fn new(words: WordList) -> Self {
+        let secret = words
+            .words()
+            .iter()
+            .choose(&mut rand::thread_rng())
+            .expect("word lists are never empty")
+            .to_owned();
+
+        Self::with_secret(words, secret)
+    }
+
+    /// Start a game where the secret word is `secret`.
+    pub fn with_secret(words: WordList, secret: String) -> Self {
+        Self {
+            words,
+            secret,
+            guesses: 0,
+        }
+    }
+
+    pub fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    /// The number of guesses made so far.
+    pub fn guesses(&self) -> usize {
+        self.guesses
+    }
+
+    /// Guess `word`, returning `None` without counting the guess if it isn't
+    /// in the word list.
+    pub fn guess(&mut self, word: &str) -> Option<Outcome> {
+        self.words
+            .words()
+            .binary_search_by(|ea| ea.as_str().cmp(word))
+            .ok()?;
+
+        self.guesses += 1;
+
+        let distance = WordSearch::hamming_distance(word, &self.secret);
+
+        Some(match self.secret.as_str().cmp(word) {
+            Ordering::Greater => Outcome::Higher { distance },
+            Ordering::Less => Outcome::Lower { distance },
+            Ordering::Equal => Outcome::Correct,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guesses_give_direction_and_distance() {
+        let words = "apple\nbanana\ncherry\n".parse::<WordList>().unwrap();
+        let mut game = Game::with_secret(words, "banana".to_owned());
+
+        assert_eq!(game.guess("apple"), Some(Outcome::Higher { distance: 6 }));
+        assert_eq!(game.guess("cherry"), Some(Outcome::Lower { distance: 6 }));
+        assert_eq!(game.guess("durian"), None);
+        assert_eq!(game.guess("banana"), Some(Outcome::Correct));
+        assert_eq!(game.guesses(), 3);
+    }
+}
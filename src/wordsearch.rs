@@ -1,11 +1,107 @@
-lazy_static::lazy_static! {
-    static ref ALL: Vec<String> = {
-        include_str!("../assets/words.txt").split_whitespace().map(ToOwned::to_owned).collect()
-    };
+pub mod auto;
+pub mod bench;
+pub mod list;
+pub mod session;
+
+use std::{cmp::Ordering, fmt, iter, str::FromStr};
+
+use thiserror::Error;
+
+use self::list::WordList;
+
+/// The largest number of possible words that [`Strategy::Optimal`] will
+/// consider every guess for. Above this, it falls back to
+/// [`Strategy::Midpoint`], since the number of comparisons grows with the
+/// square of the number of possible words.
+pub const OPTIMAL_MAX_CANDIDATES: usize = 250;
+
+/// How a [`WordSearch`] chooses its next guess.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    /// Guess the word in the middle of the possible words.
+    Midpoint,
+    /// Guess the possible word that leaves the fewest possible words on
+    /// average, over every answer it could turn out to be.
+    #[default]
+    Optimal,
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "midpoint" => Ok(Strategy::Midpoint),
+            "optimal" => Ok(Strategy::Optimal),
+            other => Err(format!(
+                "invalid strategy {:?}, expected midpoint or optimal",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strategy::Midpoint => write!(f, "midpoint"),
+            Strategy::Optimal => write!(f, "optimal"),
+        }
+    }
+}
+
+/// Which side of the answer a guessed word was on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// The answer comes after the word.
+    Lower,
+    /// The answer comes before the word.
+    Upper,
+}
+
+/// Something learned about the answer from a guess.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    pub bound: Bound,
+    pub word: String,
+    /// The Hamming distance between the word and the answer, if it was given.
+    pub distance: Option<usize>,
+}
+
+impl Constraint {
+    /// Whether the answer could be `word`, according to this constraint.
+    fn allows(&self, word: &str) -> bool {
+        let side = match self.bound {
+            Bound::Lower => word > self.word.as_str(),
+            Bound::Upper => word < self.word.as_str(),
+        };
+
+        side && match self.distance {
+            Some(distance) => WordSearch::hamming_distance(&self.word, word) == distance,
+            None => true,
+        }
+    }
+}
+
+/// Reasons a [`Constraint`] couldn't be applied to a [`WordSearch`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ConstraintError {
+    #[error("{0} isn't in the word list")]
+    UnknownWord(String),
+
+    #[error("no possible words would be left")]
+    Contradiction,
 }
 
 pub struct WordSearch {
+    words: WordList,
     possible: Vec<String>,
+    strategy: Strategy,
+    /// Whether the game gives the distance between each guess and the answer.
+    distances: bool,
+    /// The constraints that have narrowed down `possible`, in the order they
+    /// were applied.
+    history: Vec<Constraint>,
 }
 
 impl Default for WordSearch {
@@ -16,48 +112,209 @@ impl Default for WordSearch {
 
 impl WordSearch {
     pub fn new() -> Self {
-        let mut ws = Self { possible: vec![] };
+        Self::with_strategy(Strategy::default())
+    }
+
+    pub fn with_strategy(strategy: Strategy) -> Self {
+        Self::with_words(WordList::built_in(), strategy)
+    }
+
+    /// Create a `WordSearch` that chooses from the words in `words`.
+    pub fn with_words(words: WordList, strategy: Strategy) -> Self {
+        let mut ws = Self {
+            words,
+            possible: vec![],
+            strategy,
+            distances: true,
+            history: vec![],
+        };
         ws.reset();
         ws
     }
 
+    /// Choose guesses knowing whether the game gives the distance between
+    /// each guess and the answer, which it's assumed to by default.
+    pub fn distances(mut self, distances: bool) -> Self {
+        self.distances = distances;
+        self
+    }
+
+    /// Recreate a search from the words in `words` by applying `history` in
+    /// order.
+    ///
+    /// Any constraints that can't be applied are skipped, which can only
+    /// happen if the history didn't come from a search using the same words.
+    pub fn replay<I>(words: WordList, strategy: Strategy, history: I) -> Self
+    where
+        I: IntoIterator<Item = Constraint>,
+    {
+        let mut ws = Self::with_words(words, strategy);
+
+        for constraint in history {
+            let _ = ws.apply(constraint);
+        }
+
+        ws
+    }
+
+    /// The number of words the answer could still be.
+    pub fn remaining(&self) -> usize {
+        self.possible.len()
+    }
+
+    /// The closest words known to come before and after the answer, if any.
+    pub fn bounds(&self) -> (Option<&str>, Option<&str>) {
+        let bound = |wanted| {
+            self.history
+                .iter()
+                .filter(move |constraint| constraint.bound == wanted)
+                .map(|constraint| constraint.word.as_str())
+        };
+
+        (bound(Bound::Lower).max(), bound(Bound::Upper).min())
+    }
+
     pub fn reset(&mut self) {
-        self.possible = ALL[..].to_vec();
+        self.possible = self.words.words().to_vec();
+        self.history.clear();
     }
 
-    pub fn guess(&self) -> &str {
-        let mid = self.possible.len() / 2;
-        &self.possible[mid]
+    /// The constraints applied since the search was last reset.
+    pub fn history(&self) -> &[Constraint] {
+        &self.history
     }
 
-    pub fn set_lower(&mut self, word: &str, distance: Option<usize>) {
-        if let Some(index) = self.possible.iter().position(|ea| *ea == word) {
-            let sliced = self.possible[(index + 1)..].to_vec();
+    /// Narrow down the possible words using `constraint`.
+    ///
+    /// If `constraint` contradicts the constraints already applied, so that no
+    /// possible words would be left, it's rejected and nothing changes.
+    pub fn apply(&mut self, constraint: Constraint) -> Result<(), ConstraintError> {
+        if self.words.words().binary_search(&constraint.word).is_err() {
+            return Err(ConstraintError::UnknownWord(constraint.word));
+        }
 
-            if let Some(distance) = distance {
-                self.possible = sliced
-                    .into_iter()
-                    .filter(|ea| Self::hamming_distance(word, ea) == distance)
-                    .collect();
-            } else {
-                self.possible = sliced;
-            }
+        let possible = self
+            .possible
+            .iter()
+            .filter(|ea| constraint.allows(ea))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if possible.is_empty() {
+            return Err(ConstraintError::Contradiction);
         }
+
+        self.possible = possible;
+        self.history.push(constraint);
+
+        Ok(())
     }
 
-    pub fn set_upper(&mut self, word: &str, distance: Option<usize>) {
-        if let Some(index) = self.possible.iter().position(|ea| *ea == word) {
-            let sliced = self.possible[..index].to_vec();
+    /// Remove the last constraint that was applied, returning it.
+    pub fn undo(&mut self) -> Option<Constraint> {
+        let last = self.history.pop()?;
+        let history = std::mem::take(&mut self.history);
 
-            if let Some(distance) = distance {
-                self.possible = sliced
-                    .into_iter()
-                    .filter(|ea| Self::hamming_distance(word, ea) == distance)
-                    .collect();
-            } else {
-                self.possible = sliced;
-            }
+        self.reset();
+
+        for constraint in history {
+            let _ = self.apply(constraint);
+        }
+
+        Some(last)
+    }
+
+    /// The next guess, or `None` if there are no possible words left.
+    pub fn guess(&self) -> Option<&str> {
+        if self.possible.is_empty() {
+            return None;
         }
+
+        Some(match self.strategy {
+            Strategy::Optimal if self.possible.len() <= OPTIMAL_MAX_CANDIDATES => {
+                self.optimal_guess()
+            }
+            _ => self.midpoint_guess(),
+        })
+    }
+
+    fn midpoint_guess(&self) -> &str {
+        let mid = self.possible.len() / 2;
+        &self.possible[mid]
+    }
+
+    /// Find the possible word that minimises the expected number of possible
+    /// words left after guessing it, assuming every possible word is equally
+    /// likely to be the answer.
+    ///
+    /// Each answer gives an outcome of whether it comes before or after the
+    /// guess, and its distance from the guess if the game gives distances.
+    /// The expected number of words
+    /// left is proportional to the sum of the squares of the number of answers
+    /// giving each outcome, so that's what is minimised. Ties go to the word
+    /// closest to the midpoint.
+    fn optimal_guess(&self) -> &str {
+        let mid = self.possible.len() / 2;
+
+        // Outcomes are counted at `2 * distance`, plus one if the answer comes
+        // after the guess. No distance can be longer than the longest word.
+        let longest = self.possible.iter().map(String::len).max().unwrap_or(0);
+        let mut outcomes = vec![0usize; 2 * (longest + 1)];
+
+        let (_, best) = self
+            .possible
+            .iter()
+            .enumerate()
+            .map(|(index, guess)| {
+                outcomes.iter_mut().for_each(|count| *count = 0);
+
+                for answer in self.possible.iter() {
+                    let after = match answer.cmp(guess) {
+                        Ordering::Equal => continue,
+                        ordering => ordering == Ordering::Greater,
+                    };
+
+                    let distance = if self.distances {
+                        Self::hamming_distance(guess, answer)
+                    } else {
+                        0
+                    };
+
+                    outcomes[2 * distance + after as usize] += 1;
+                }
+
+                let score = outcomes.iter().map(|count| count * count).sum::<usize>();
+
+                ((score, index.max(mid) - index.min(mid)), guess)
+            })
+            .min_by_key(|(key, _)| *key)
+            .expect("there should be at least one possible word");
+
+        best
+    }
+
+    pub fn set_lower(
+        &mut self,
+        word: &str,
+        distance: Option<usize>,
+    ) -> Result<(), ConstraintError> {
+        self.apply(Constraint {
+            bound: Bound::Lower,
+            word: word.to_owned(),
+            distance,
+        })
+    }
+
+    pub fn set_upper(
+        &mut self,
+        word: &str,
+        distance: Option<usize>,
+    ) -> Result<(), ConstraintError> {
+        self.apply(Constraint {
+            bound: Bound::Upper,
+            word: word.to_owned(),
+            distance,
+        })
     }
 
     pub fn hamming_distance(word1: &str, word2: &str) -> usize {
@@ -67,14 +324,148 @@ impl WordSearch {
         } else {
             (word1, word2)
         };
-        // Generate the correct amount of spaces to 'pad' the shorter string.
-        let append_spaces = " ".repeat(w1.len() - w2.len());
-        // Push spaces to the shorter string.
-        let w2_padded = w2.to_owned() + &append_spaces;
+        // 'Pad' the shorter string with spaces, up to the length of the longer
+        // one.
+        let w2_padded = w2.chars().chain(iter::repeat(' '));
         // Calculating the Hamming distance
-        w1.chars()
-            .zip(w2_padded.chars())
-            .filter(|(x, y)| x != y)
-            .count()
+        w1.chars().zip(w2_padded).filter(|(x, y)| x != y).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hamming_distance_pads() {
+        assert_eq!(WordSearch::hamming_distance("abc", "abc"), 0);
+        assert_eq!(WordSearch::hamming_distance("abc", "abd"), 1);
+        assert_eq!(WordSearch::hamming_distance("abcde", "abd"), 3);
+    }
+
+    #[test]
+    fn replay_matches_history() {
+        let mut ws = WordSearch::new();
+        ws.set_lower("banana", None).unwrap();
+        ws.set_upper("water", Some(3)).unwrap();
+        ws.set_lower("apple", None).unwrap();
+
+        assert_eq!(ws.history().len(), 3);
+
+        let replayed = WordSearch::replay(
+            WordList::built_in(),
+            Strategy::default(),
+            ws.history().to_vec(),
+        );
+
+        assert_eq!(replayed.possible, ws.possible);
+        assert_eq!(replayed.history(), ws.history());
+    }
+
+    #[test]
+    fn rejects_contradictions() {
+        let words = "apple\nbanana\ncherry\ndate\n".parse::<WordList>().unwrap();
+        let mut ws = WordSearch::with_words(words, Strategy::default());
+
+        ws.set_lower("banana", None).unwrap();
+
+        assert_eq!(
+            ws.set_upper("apple", None),
+            Err(ConstraintError::Contradiction)
+        );
+        assert_eq!(
+            ws.set_lower("banana", Some(1)),
+            Err(ConstraintError::Contradiction)
+        );
+        assert_eq!(
+            ws.set_lower("durian", None),
+            Err(ConstraintError::UnknownWord("durian".to_owned()))
+        );
+
+        // Rejected constraints don't change anything.
+        assert_eq!(ws.remaining(), 2);
+        assert_eq!(ws.history().len(), 1);
+        assert!(ws.guess().is_some());
+    }
+
+    #[test]
+    fn undo_and_bounds() {
+        let words = "apple\nbanana\ncherry\ndate\nelderberry\n"
+            .parse::<WordList>()
+            .unwrap();
+        let mut ws = WordSearch::with_words(words, Strategy::default());
+
+        ws.set_lower("apple", None).unwrap();
+        ws.set_lower("banana", None).unwrap();
+        ws.set_upper("elderberry", None).unwrap();
+
+        assert_eq!(ws.bounds(), (Some("banana"), Some("elderberry")));
+        assert_eq!(ws.remaining(), 2);
+
+        assert_eq!(
+            ws.undo().map(|constraint| constraint.word),
+            Some("elderberry".to_owned())
+        );
+        assert_eq!(
+            ws.undo().map(|constraint| constraint.word),
+            Some("banana".to_owned())
+        );
+
+        assert_eq!(ws.bounds(), (Some("apple"), None));
+        assert_eq!(ws.remaining(), 4);
+    }
+
+    #[test]
+    fn optimal_without_distances_splits_evenly() {
+        for words in [
+            "apple\nbanana\ncherry\ndate\nelderberry\n",
+            "ab\nac\nbc\nbd\nbe\ncd\n",
+        ] {
+            let words = words.parse::<WordList>().unwrap();
+            let optimal = WordSearch::with_words(words.clone(), Strategy::Optimal).distances(false);
+            let midpoint = WordSearch::with_words(words, Strategy::Midpoint);
+
+            assert_eq!(optimal.guess(), midpoint.guess());
+        }
+    }
+
+    /// Check that the optimal strategy needs fewer guesses in total than the
+    /// midpoint strategy, to find every `step`th word in the built-in list.
+    fn assert_optimal_beats_midpoint(step: usize) {
+        let words = WordList::built_in();
+        let answers = words.words().iter().step_by(step).collect::<Vec<_>>();
+
+        let total = |strategy| {
+            answers
+                .iter()
+                .map(|answer| {
+                    bench::solve(&words, strategy, answer, true)
+                        .expect("every answer should be found")
+                })
+                .sum::<usize>()
+        };
+
+        let midpoint = total(Strategy::Midpoint);
+        let optimal = total(Strategy::Optimal);
+
+        assert!(
+            optimal < midpoint,
+            "optimal strategy should need fewer guesses on average ({} vs {})",
+            optimal,
+            midpoint
+        );
+    }
+
+    #[test]
+    fn optimal_needs_fewer_guesses() {
+        // Every 13th word keeps the simulation quick, while still covering the
+        // whole list.
+        assert_optimal_beats_midpoint(13);
+    }
+
+    #[test]
+    #[ignore = "simulates a search for every word in the list"]
+    fn optimal_needs_fewer_guesses_for_every_word() {
+        assert_optimal_beats_midpoint(1);
     }
 }
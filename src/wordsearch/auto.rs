@@ -0,0 +1,214 @@
+//! Playing the word search game automatically, by reading the game bot's
+//! replies to each guess.
+
+use std::time::Duration;
+
+use regex::Regex;
+use thiserror::Error;
+
+/// The default pattern for a reply saying the answer comes after a word.
+pub const DEFAULT_LOWER_PATTERN: &str =
+    r"(?i)\b(?P<word>[a-z]+) is before the word\b(?:.*?\bdistance:?\s*(?P<distance>\d+))?";
+
+/// The default pattern for a reply saying the answer comes before a word.
+pub const DEFAULT_UPPER_PATTERN: &str =
+    r"(?i)\b(?P<word>[a-z]+) is after the word\b(?:.*?\bdistance:?\s*(?P<distance>\d+))?";
+
+/// The default pattern for a reply saying the answer has been found.
+pub const DEFAULT_FOUND_PATTERN: &str = r"(?i)\b(?P<word>[a-z]+) is the word\b";
+
+/// The default shortest time between two guesses in the same channel.
+pub const DEFAULT_GUESS_INTERVAL: Duration = Duration::from_secs(3);
+
+/// How to recognise the game bot's replies to guesses, and how quickly to
+/// guess.
+///
+/// The lower and upper patterns must have a `word` group, which captures the
+/// word that was guessed, and can have a `distance` group, which captures its
+/// distance from the answer.
+#[derive(Debug, Clone)]
+pub struct AutoPlay {
+    game_bot: String,
+    lower: Regex,
+    upper: Regex,
+    found: Regex,
+    guess_interval: Duration,
+}
+
+/// What a reply from the game bot said about a guess.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Feedback {
+    /// The answer comes after `word`.
+    Lower {
+        word: String,
+        distance: Option<usize>,
+    },
+    /// The answer comes before `word`.
+    Upper {
+        word: String,
+        distance: Option<usize>,
+    },
+    /// The answer has been found.
+    Found,
+}
+
+impl AutoPlay {
+    /// Create an `AutoPlay` that reads replies from the user `game_bot`, using
+    /// the default patterns and guess interval.
+    pub fn new<S: ToString>(game_bot: S) -> Self {
+        Self {
+            game_bot: game_bot.to_string().to_lowercase(),
+            lower: Regex::new(DEFAULT_LOWER_PATTERN)
+                .expect("default lower pattern should be valid"),
+            upper: Regex::new(DEFAULT_UPPER_PATTERN)
+                .expect("default upper pattern should be valid"),
+            found: Regex::new(DEFAULT_FOUND_PATTERN)
+                .expect("default found pattern should be valid"),
+            guess_interval: DEFAULT_GUESS_INTERVAL,
+        }
+    }
+
+    /// Set the pattern for a reply saying the answer comes after a word.
+    pub fn lower_pattern(mut self, pattern: &str) -> Result<Self, AutoPlayError> {
+        self.lower = Self::word_pattern(pattern)?;
+        Ok(self)
+    }
+
+    /// Set the pattern for a reply saying the answer comes before a word.
+    pub fn upper_pattern(mut self, pattern: &str) -> Result<Self, AutoPlayError> {
+        self.upper = Self::word_pattern(pattern)?;
+        Ok(self)
+    }
+
+    /// Set the pattern for a reply saying the answer has been found.
+    pub fn found_pattern(mut self, pattern: &str) -> Result<Self, AutoPlayError> {
+        self.found = Regex::new(pattern)?;
+        Ok(self)
+    }
+
+    /// Set the shortest time between two guesses in the same channel.
+    pub fn guess_interval(mut self, guess_interval: Duration) -> Self {
+        self.guess_interval = guess_interval;
+        self
+    }
+
+    /// The login of the game bot, in lowercase.
+    pub fn game_bot(&self) -> &str {
+        &self.game_bot
+    }
+
+    /// The shortest time between two guesses in the same channel.
+    pub fn interval(&self) -> Duration {
+        self.guess_interval
+    }
+
+    /// Work out what a message from the game bot said, if it was a reply to a
+    /// guess.
+    pub fn feedback(&self, text: &str) -> Option<Feedback> {
+        let word_and_distance = |pattern: &Regex| {
+            pattern.captures(text).and_then(|captures| {
+                let word = captures.name("word")?.as_str().to_lowercase();
+                let distance = captures
+                    .name("distance")
+                    .and_then(|distance| distance.as_str().parse().ok());
+
+                Some((word, distance))
+            })
+        };
+
+        if self.found.is_match(text) {
+            Some(Feedback::Found)
+        } else if let Some((word, distance)) = word_and_distance(&self.lower) {
+            Some(Feedback::Lower { word, distance })
+        } else if let Some((word, distance)) = word_and_distance(&self.upper) {
+            Some(Feedback::Upper { word, distance })
+        } else {
+            None
+        }
+    }
+
+    fn word_pattern(pattern: &str) -> Result<Regex, AutoPlayError> {
+        let regex = Regex::new(pattern)?;
+
+        if regex.capture_names().flatten().any(|name| name == "word") {
+            Ok(regex)
+        } else {
+            Err(AutoPlayError::NoWordGroup(pattern.to_owned()))
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AutoPlayError {
+    #[error("invalid pattern: {0}")]
+    Regex(#[from] regex::Error),
+
+    #[error("pattern {0:?} has no `word` group")]
+    NoWordGroup(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_patterns() {
+        let auto = AutoPlay::new("Ferris_Bot");
+        assert_eq!(auto.game_bot(), "ferris_bot");
+
+        assert_eq!(
+            auto.feedback("@oxbow Apple is before the word, distance 3"),
+            Some(Feedback::Lower {
+                word: "apple".to_owned(),
+                distance: Some(3),
+            })
+        );
+        assert_eq!(
+            auto.feedback("@oxbow zebra is after the word"),
+            Some(Feedback::Upper {
+                word: "zebra".to_owned(),
+                distance: None,
+            })
+        );
+        assert_eq!(
+            auto.feedback("@oxbow zebra is after the word! Distance: 4"),
+            Some(Feedback::Upper {
+                word: "zebra".to_owned(),
+                distance: Some(4),
+            })
+        );
+        // Other numbers in the reply aren't distances.
+        assert_eq!(
+            auto.feedback("@oxbow apple is before the word! 3 guesses so far"),
+            Some(Feedback::Lower {
+                word: "apple".to_owned(),
+                distance: None,
+            })
+        );
+        assert_eq!(
+            auto.feedback("@oxbow mango is the word!"),
+            Some(Feedback::Found)
+        );
+        assert_eq!(auto.feedback("hello everyone"), None);
+    }
+
+    #[test]
+    fn custom_patterns() {
+        let auto = AutoPlay::new("game")
+            .lower_pattern(r"(?P<word>\w+) < answer \((?P<distance>\d+)\)")
+            .expect("pattern should be valid");
+
+        assert_eq!(
+            auto.feedback("apple < answer (2)"),
+            Some(Feedback::Lower {
+                word: "apple".to_owned(),
+                distance: Some(2),
+            })
+        );
+
+        assert!(matches!(
+            AutoPlay::new("game").upper_pattern(r"\w+ > answer"),
+            Err(AutoPlayError::NoWordGroup(_))
+        ));
+    }
+}
@@ -0,0 +1,117 @@
+//! Playing a [`WordSearch`] against every answer in a word list, to compare
+//! strategies and word lists without having to play the real game.
+
+use std::cmp::Ordering;
+
+use serde::Serialize;
+
+use super::{list::WordList, Strategy, WordSearch};
+
+/// Play a game against `answer`, returning the number of guesses it took to
+/// find it.
+///
+/// If `distances` is `false`, the search is only told whether the answer
+/// comes before or after each guess, as if the game never gave distances, and
+/// chooses its guesses knowing that.
+/// Returns `None` if the search ran out of words without finding the answer,
+/// which can only happen if `answer` isn't in `words`.
+pub fn solve(words: &WordList, strategy: Strategy, answer: &str, distances: bool) -> Option<usize> {
+    let mut ws = WordSearch::with_words(words.clone(), strategy).distances(distances);
+
+    // Every guess rules out at least the word that was guessed, so no game can
+    // take more guesses than there are words.
+    for guesses in 1..=words.words().len() {
+        let guess = ws.guess()?.to_owned();
+        let distance = Some(WordSearch::hamming_distance(&guess, answer)).filter(|_| distances);
+
+        match answer.cmp(&guess) {
+            Ordering::Equal => return Some(guesses),
+            Ordering::Greater => ws.set_lower(&guess, distance).ok()?,
+            Ordering::Less => ws.set_upper(&guess, distance).ok()?,
+        }
+    }
+
+    None
+}
+
+/// Statistics about the number of guesses taken to find a set of answers.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    /// The number of answers that were found.
+    pub games: usize,
+    /// The number of answers that couldn't be found.
+    pub failures: usize,
+    pub mean: f64,
+    /// The 95th percentile, using the nearest-rank method.
+    pub p95: usize,
+    pub max: usize,
+}
+
+impl Summary {
+    /// Summarise the results of playing a game against each answer, as
+    /// returned by [`solve`].
+    pub fn new<I>(results: I) -> Self
+    where
+        I: IntoIterator<Item = Option<usize>>,
+    {
+        let (found, failed): (Vec<_>, Vec<_>) = results.into_iter().partition(Option::is_some);
+        let mut guesses = found.into_iter().flatten().collect::<Vec<_>>();
+        guesses.sort_unstable();
+
+        let games = guesses.len();
+        let mean = if games == 0 {
+            0.0
+        } else {
+            guesses.iter().sum::<usize>() as f64 / games as f64
+        };
+        let p95 = match (games as f64 * 0.95).ceil() as usize {
+            0 => 0,
+            rank => guesses[rank - 1],
+        };
+
+        Self {
+            games,
+            failures: failed.len(),
+            mean,
+            p95,
+            max: guesses.last().copied().unwrap_or(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarises_guesses() {
+        let mut results = (1..=20).map(Some).collect::<Vec<_>>();
+        results.push(None);
+
+        assert_eq!(
+            Summary::new(results),
+            Summary {
+                games: 20,
+                failures: 1,
+                mean: 10.5,
+                p95: 19,
+                max: 20,
+            }
+        );
+    }
+
+    #[test]
+    fn solves_with_and_without_distances() {
+        let words = "apple\nbanana\ncherry\ndate\nelderberry\n"
+            .parse::<WordList>()
+            .unwrap();
+
+        for answer in words.words() {
+            for distances in [true, false] {
+                assert!(solve(&words, Strategy::Midpoint, answer, distances).is_some());
+            }
+        }
+
+        assert_eq!(solve(&words, Strategy::Midpoint, "durian", true), None);
+    }
+}
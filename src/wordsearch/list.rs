@@ -0,0 +1,409 @@
+//! Lists of the words a word search can choose from, which have to match the
+//! list used by the game exactly.
+
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::params;
+use thiserror::Error;
+
+lazy_static::lazy_static! {
+    static ref BUILT_IN: WordList = include_str!("../../assets/words.txt")
+        .parse()
+        .expect("the built-in word list should be valid");
+}
+
+/// A list of words, sorted in the same order as the game sorts them and
+/// without any duplicates.
+///
+/// The game compares words byte by byte, the same as [`str::cmp`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordList {
+    words: Arc<[String]>,
+}
+
+impl WordList {
+    /// The word list built into the bot, which matches the one used by the
+    /// game bot in Stuck Overflow's channel.
+    pub fn built_in() -> Self {
+        BUILT_IN.clone()
+    }
+
+    /// Create a `WordList` from words that should already be in order,
+    /// checking that they are.
+    pub fn from_words(words: Vec<String>) -> Result<Self, WordListError> {
+        Self::validate(
+            words
+                .iter()
+                .enumerate()
+                .map(|(index, word)| (index + 1, word.as_str())),
+        )?;
+
+        Ok(Self {
+            words: words.into(),
+        })
+    }
+
+    /// Read a `WordList` from a file with one word on each line.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, WordListError> {
+        fs::read_to_string(path.as_ref())
+            .map_err(|source| WordListError::Read {
+                path: path.as_ref().to_owned(),
+                source,
+            })?
+            .parse()
+    }
+
+    /// The words in the list, in order.
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    /// Check that the words given with their line numbers are non-empty,
+    /// sorted and de-duplicated.
+    fn validate<'a, I>(words: I) -> Result<(), WordListError>
+    where
+        I: IntoIterator<Item = (usize, &'a str)>,
+    {
+        let mut previous: Option<(usize, &str)> = None;
+
+        for (line, word) in words {
+            if word.is_empty() || word.contains(char::is_whitespace) {
+                return Err(WordListError::InvalidWord {
+                    line,
+                    word: word.to_owned(),
+                });
+            }
+
+            if let Some((previous_line, previous)) = previous {
+                if word == previous {
+                    return Err(WordListError::Duplicate {
+                        line,
+                        word: word.to_owned(),
+                        first_line: previous_line,
+                    });
+                } else if word < previous {
+                    return Err(WordListError::Unsorted {
+                        line,
+                        word: word.to_owned(),
+                        previous: previous.to_owned(),
+                    });
+                }
+            }
+
+            previous = Some((line, word));
+        }
+
+        if previous.is_none() {
+            Err(WordListError::Empty)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl FromStr for WordList {
+    type Err = WordListError;
+
+    /// Parse a `WordList` from text with one word on each line. Blank lines
+    /// are ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, word)| !word.is_empty());
+
+        Self::validate(words.clone())?;
+
+        Ok(Self {
+            words: words.map(|(_, word)| word.to_owned()).collect(),
+        })
+    }
+}
+
+/// Where the word list for a channel comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum WordListSource {
+    /// The list built into the bot.
+    #[default]
+    BuiltIn,
+    /// A file with one word on each line.
+    File(PathBuf),
+    /// A list saved in the database with the given name.
+    Database(String),
+}
+
+impl WordListSource {
+    /// Load the word list from this source.
+    pub fn load(&self, store: &WordListStore) -> Result<WordList, WordListError> {
+        match self {
+            WordListSource::BuiltIn => Ok(WordList::built_in()),
+            WordListSource::File(path) => WordList::from_file(path),
+            WordListSource::Database(name) => store
+                .get_list(name)?
+                .ok_or_else(|| WordListError::NotFound(name.to_owned())),
+        }
+    }
+}
+
+impl FromStr for WordListSource {
+    type Err = WordListError;
+
+    /// Parse a source written as `builtin`, `file:PATH` or `db:NAME`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "builtin" => Ok(WordListSource::BuiltIn),
+            Some(("file", path)) if !path.is_empty() => Ok(WordListSource::File(path.into())),
+            Some(("db", name)) if !name.is_empty() => Ok(WordListSource::Database(name.to_owned())),
+            _ => Err(WordListError::InvalidSource(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for WordListSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WordListSource::BuiltIn => write!(f, "builtin"),
+            WordListSource::File(path) => write!(f, "file:{}", path.display()),
+            WordListSource::Database(name) => write!(f, "db:{}", name),
+        }
+    }
+}
+
+/// The word list used in each channel, which can be shared by everything that
+/// needs to use the same list as the game.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelLists {
+    lists: Arc<RwLock<HashMap<String, WordList>>>,
+}
+
+impl ChannelLists {
+    /// The word list to use in `channel`, which is the built-in list unless
+    /// another one has been set.
+    pub fn get(&self, channel: &str) -> WordList {
+        self.lists
+            .read()
+            .expect("word lists lock poisoned")
+            .get(channel)
+            .cloned()
+            .unwrap_or_else(WordList::built_in)
+    }
+
+    /// Use `list` in `channel`.
+    pub fn set(&self, channel: &str, list: WordList) {
+        self.lists
+            .write()
+            .expect("word lists lock poisoned")
+            .insert(channel.to_owned(), list);
+    }
+}
+
+/// Storage of named word lists in an SQLite3 database.
+#[derive(Debug, Clone)]
+pub struct WordListStore {
+    conn_pool: Pool<SqliteConnectionManager>,
+}
+
+impl WordListStore {
+    /// Create a `WordListStore` with a connection to a database.
+    pub fn new(conn_pool: Pool<SqliteConnectionManager>) -> Self {
+        Self { conn_pool }
+    }
+
+    /// Save `list` with the name `name`, replacing any existing list with the
+    /// same name.
+    pub fn set_list(&self, name: &str, list: &WordList) -> Result<(), WordListError> {
+        let mut conn = self.conn_pool.get()?;
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            r#"
+            DELETE FROM word_lists
+            WHERE name = ?1;
+            "#,
+            params![name],
+        )?;
+
+        {
+            let mut stmt = tx.prepare(
+                r#"
+                INSERT INTO word_lists (name, position, word)
+                VALUES (?1, ?2, ?3);
+                "#,
+            )?;
+
+            for (position, word) in list.words().iter().enumerate() {
+                stmt.execute(params![name, position as i64, word])?;
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Get the list with the name `name`, if there is one, checking that it's
+    /// still valid.
+    pub fn get_list(&self, name: &str) -> Result<Option<WordList>, WordListError> {
+        let conn = self.conn_pool.get()?;
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT word
+            FROM word_lists
+            WHERE name = ?1
+            ORDER BY position;
+            "#,
+        )?;
+
+        let words = stmt
+            .query_map(params![name], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+
+        if words.is_empty() {
+            Ok(None)
+        } else {
+            WordList::from_words(words).map(Some)
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum WordListError {
+    #[error("word list is empty")]
+    Empty,
+
+    #[error("invalid word {word:?} on line {line}")]
+    InvalidWord { line: usize, word: String },
+
+    #[error("{word:?} on line {line} is already on line {first_line}")]
+    Duplicate {
+        line: usize,
+        word: String,
+        first_line: usize,
+    },
+
+    #[error("{word:?} on line {line} should come before {previous:?}")]
+    Unsorted {
+        line: usize,
+        word: String,
+        previous: String,
+    },
+
+    #[error("failed to read {}: {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+
+    #[error("no word list called {0} in the database")]
+    NotFound(String),
+
+    #[error("invalid word list source {0:?}, expected builtin, file:PATH or db:NAME")]
+    InvalidSource(String),
+
+    #[error("rusqlite error: {0}")]
+    Rusqlite(#[from] rusqlite::Error),
+
+    #[error("r2d2 error: {0}")]
+    R2d2(#[from] r2d2::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::DerefMut;
+
+    use tempfile::{tempdir, TempDir};
+
+    use super::*;
+
+    #[test]
+    fn built_in_valid() {
+        assert_eq!(
+            WordList::built_in().words().first().map(String::as_str),
+            Some("aardvark")
+        );
+    }
+
+    #[test]
+    fn rejects_unsorted() {
+        assert!(matches!(
+            "apple\nbanana\naardvark\n".parse::<WordList>(),
+            Err(WordListError::Unsorted { line: 3, .. })
+        ));
+
+        // Uppercase letters sort before lowercase ones, as the game sorts them.
+        assert!(matches!(
+            "apple\nBanana\n".parse::<WordList>(),
+            Err(WordListError::Unsorted { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        assert!(matches!(
+            "apple\n\napple\n".parse::<WordList>(),
+            Err(WordListError::Duplicate {
+                line: 3,
+                first_line: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn parses_sources() {
+        assert_eq!(
+            "builtin".parse::<WordListSource>().ok(),
+            Some(WordListSource::BuiltIn)
+        );
+        assert_eq!(
+            "file:words.txt".parse::<WordListSource>().ok(),
+            Some(WordListSource::File("words.txt".into()))
+        );
+        assert_eq!(
+            "db:ferris".parse::<WordListSource>().ok(),
+            Some(WordListSource::Database("ferris".to_owned()))
+        );
+        assert!("ferris".parse::<WordListSource>().is_err());
+    }
+
+    fn storage() -> (TempDir, WordListStore) {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let manager = SqliteConnectionManager::file(dir.path().join("db.sqlite3"));
+        let conn_pool = Pool::new(manager).expect("creating a connection pool should succeed");
+
+        let mut conn = conn_pool
+            .get()
+            .expect("getting a connection should succeed");
+        crate::db::migrations::runner()
+            .run(conn.deref_mut())
+            .expect("migrations should succeed");
+
+        (dir, WordListStore::new(conn_pool))
+    }
+
+    #[test]
+    fn save_and_load() {
+        let (_dir, store) = storage();
+        let list = "apple\nbanana\ncherry\n".parse::<WordList>().unwrap();
+
+        store
+            .set_list("fruit", &list)
+            .expect("saving should succeed");
+        assert_eq!(store.get_list("fruit").unwrap(), Some(list));
+
+        let shorter = "apple\n".parse::<WordList>().unwrap();
+        store
+            .set_list("fruit", &shorter)
+            .expect("replacing should succeed");
+        assert_eq!(store.get_list("fruit").unwrap(), Some(shorter));
+
+        assert_eq!(store.get_list("vegetables").unwrap(), None);
+    }
+}
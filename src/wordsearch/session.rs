@@ -0,0 +1,339 @@
+//! Storage of word searches in progress, so they can be resumed after the bot
+//! restarts.
+//!
+//! Only the constraints learned from each guess are stored, rather than the
+//! words that are still possible, so a search is restored by replaying them
+//! against the channel's word list.
+
+use chrono::{DateTime, Utc};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, OptionalExtension};
+use thiserror::Error;
+
+use super::{Bound, Constraint};
+
+/// A word search saved in the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub channel: String,
+    pub started: DateTime<Utc>,
+    /// The constraints applied to the search, in order.
+    pub history: Vec<Constraint>,
+}
+
+/// Storage of word search sessions in an SQLite3 database.
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    conn_pool: Pool<SqliteConnectionManager>,
+}
+
+impl SessionStore {
+    /// Create a `SessionStore` with a connection to a database.
+    pub fn new(conn_pool: Pool<SqliteConnectionManager>) -> Self {
+        Self { conn_pool }
+    }
+
+    /// Start a new session in `channel`, replacing any existing one.
+    pub fn start(&self, channel: &str, started: DateTime<Utc>) -> Result<(), SessionError> {
+        let mut conn = self.conn_pool.get()?;
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            r#"
+            DELETE FROM word_search_constraints
+            WHERE channel = ?1;
+            "#,
+            params![channel],
+        )?;
+
+        tx.execute(
+            r#"
+            INSERT OR REPLACE INTO word_search_sessions (channel, started)
+            VALUES (?1, ?2);
+            "#,
+            params![channel, started],
+        )?;
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Add `constraint` to the end of the history of the session in `channel`.
+    pub fn add_constraint(
+        &self,
+        channel: &str,
+        constraint: &Constraint,
+    ) -> Result<(), SessionError> {
+        let conn = self.conn_pool.get()?;
+
+        conn.execute(
+            r#"
+            INSERT INTO word_search_constraints (channel, position, bound, word, distance)
+            SELECT ?1, COALESCE(MAX(position) + 1, 0), ?2, ?3, ?4
+            FROM word_search_constraints
+            WHERE channel = ?1;
+            "#,
+            params![
+                channel,
+                bound_name(constraint.bound),
+                constraint.word,
+                constraint.distance.map(|distance| distance as i64),
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Remove the last constraint from the history of the session in
+    /// `channel`, returning `false` if there weren't any.
+    pub fn undo(&self, channel: &str) -> Result<bool, SessionError> {
+        let conn = self.conn_pool.get()?;
+
+        let deleted = conn.execute(
+            r#"
+            DELETE FROM word_search_constraints
+            WHERE channel = ?1
+            AND position = (
+                SELECT MAX(position)
+                FROM word_search_constraints
+                WHERE channel = ?1
+            );
+            "#,
+            params![channel],
+        )?;
+
+        Ok(deleted > 0)
+    }
+
+    /// End the session in `channel`, returning `false` if there wasn't one.
+    pub fn end(&self, channel: &str) -> Result<bool, SessionError> {
+        let mut conn = self.conn_pool.get()?;
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            r#"
+            DELETE FROM word_search_constraints
+            WHERE channel = ?1;
+            "#,
+            params![channel],
+        )?;
+
+        let deleted = tx.execute(
+            r#"
+            DELETE FROM word_search_sessions
+            WHERE channel = ?1;
+            "#,
+            params![channel],
+        )?;
+
+        tx.commit()?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Get the session in `channel`, if there is one.
+    pub fn get(&self, channel: &str) -> Result<Option<Session>, SessionError> {
+        let conn = self.conn_pool.get()?;
+
+        let started = conn
+            .query_row(
+                r#"
+                SELECT started
+                FROM word_search_sessions
+                WHERE channel = ?1;
+                "#,
+                params![channel],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let started = match started {
+            Some(started) => started,
+            None => return Ok(None),
+        };
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT bound, word, distance
+            FROM word_search_constraints
+            WHERE channel = ?1
+            ORDER BY position;
+            "#,
+        )?;
+
+        let history = stmt
+            .query_map(params![channel], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<i64>>(2)?,
+                ))
+            })?
+            .map(|row| {
+                let (bound, word, distance) = row?;
+
+                Ok(Constraint {
+                    bound: parse_bound(&bound)?,
+                    word,
+                    distance: distance.map(|distance| distance as usize),
+                })
+            })
+            .collect::<Result<Vec<_>, SessionError>>()?;
+
+        Ok(Some(Session {
+            channel: channel.to_owned(),
+            started,
+            history,
+        }))
+    }
+
+    /// Get every saved session.
+    pub fn sessions(&self) -> Result<Vec<Session>, SessionError> {
+        let channels = {
+            let conn = self.conn_pool.get()?;
+
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT channel
+                FROM word_search_sessions
+                ORDER BY channel;
+                "#,
+            )?;
+
+            let channels = stmt
+                .query_map([], |row| row.get(0))?
+                .collect::<Result<Vec<String>, _>>()?;
+
+            channels
+        };
+
+        channels
+            .iter()
+            .filter_map(|channel| self.get(channel).transpose())
+            .collect()
+    }
+}
+
+fn bound_name(bound: Bound) -> &'static str {
+    match bound {
+        Bound::Lower => "lower",
+        Bound::Upper => "upper",
+    }
+}
+
+fn parse_bound(name: &str) -> Result<Bound, SessionError> {
+    match name {
+        "lower" => Ok(Bound::Lower),
+        "upper" => Ok(Bound::Upper),
+        other => Err(SessionError::InvalidBound(other.to_owned())),
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error("invalid bound {0:?} in saved word search")]
+    InvalidBound(String),
+
+    #[error("rusqlite error: {0}")]
+    Rusqlite(#[from] rusqlite::Error),
+
+    #[error("r2d2 error: {0}")]
+    R2d2(#[from] r2d2::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::DerefMut;
+
+    use tempfile::{tempdir, TempDir};
+
+    use super::*;
+
+    fn storage() -> (TempDir, SessionStore) {
+        let dir = tempdir().expect("creating a temporary directory should succeed");
+        let manager = SqliteConnectionManager::file(dir.path().join("db.sqlite3"));
+        let conn_pool = Pool::new(manager).expect("creating a connection pool should succeed");
+
+        let mut conn = conn_pool
+            .get()
+            .expect("getting a connection should succeed");
+        crate::db::migrations::runner()
+            .run(conn.deref_mut())
+            .expect("migrations should succeed");
+
+        (dir, SessionStore::new(conn_pool))
+    }
+
+    fn constraint(bound: Bound, word: &str, distance: Option<usize>) -> Constraint {
+        Constraint {
+            bound,
+            word: word.to_owned(),
+            distance,
+        }
+    }
+
+    #[test]
+    fn save_and_restore() {
+        let (_dir, store) = storage();
+        let started = Utc::now();
+
+        store.start("a", started).expect("starting should succeed");
+        store.start("b", started).expect("starting should succeed");
+
+        let history = vec![
+            constraint(Bound::Lower, "banana", Some(5)),
+            constraint(Bound::Upper, "water", None),
+        ];
+        for constraint in history.iter() {
+            store
+                .add_constraint("a", constraint)
+                .expect("adding a constraint should succeed");
+        }
+
+        let sessions = store.sessions().expect("loading should succeed");
+        assert_eq!(
+            sessions,
+            vec![
+                Session {
+                    channel: "a".to_owned(),
+                    started,
+                    history,
+                },
+                Session {
+                    channel: "b".to_owned(),
+                    started,
+                    history: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn restart_and_end() {
+        let (_dir, store) = storage();
+
+        store.start("a", Utc::now()).unwrap();
+        store
+            .add_constraint("a", &constraint(Bound::Lower, "banana", None))
+            .unwrap();
+
+        store
+            .add_constraint("a", &constraint(Bound::Upper, "water", None))
+            .unwrap();
+        assert!(store.undo("a").expect("undoing should succeed"));
+        assert_eq!(
+            store.get("a").unwrap().unwrap().history,
+            vec![constraint(Bound::Lower, "banana", None)]
+        );
+
+        // Starting again forgets everything learned in the last session.
+        store.start("a", Utc::now()).unwrap();
+        assert_eq!(store.get("a").unwrap().unwrap().history, vec![]);
+
+        assert!(store.end("a").expect("ending should succeed"));
+        assert!(!store.end("a").expect("ending should succeed"));
+        assert_eq!(store.get("a").unwrap(), None);
+    }
+}